@@ -0,0 +1,104 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! Resizes a small multiport at each tag, in a program with
+//! a growing number of unrelated reactors. The dataflow info
+//! (plans, levels and cleanup tables) is only updated where
+//! the dependency graph changed after each batch of mutations,
+//! so the cost of the dataflow update should not grow with
+//! the number of unrelated reactors.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use reactor_rt::assembly::PortKind;
+use reactor_rt::builder::ProgramBuilder;
+use reactor_rt::SchedulerOptions;
+
+const TAGS: u64 = 100;
+
+fn build(reactors: usize) -> ProgramBuilder {
+    let mut builder = ProgramBuilder::new();
+    let main = builder.add_main(());
+
+    // a pipeline of relays that is never triggered
+    let mut upstream = None;
+    for _ in 0..reactors {
+        let relay = builder.add_child(main, "relay", ());
+        let inp = builder.new_port::<u32, _>(relay, "in", PortKind::Input);
+        let out = builder.new_port::<u32, _>(relay, "out", PortKind::Output);
+        builder
+            .add_reaction(relay, move |ctx, _, c| {
+                let v = ctx.get(c.port(inp)).unwrap();
+                ctx.set(c.port_mut(out), v + 1)
+            })
+            .triggered_by(inp)
+            .effects(out);
+        if let Some(upstream) = upstream {
+            builder.connect(upstream, inp);
+        }
+        upstream = Some(out);
+    }
+
+    let control = builder.add_child(main, "control", ());
+    let out = builder.new_multiport::<u32, _>(control, "out", PortKind::Output, 1);
+    let out = Arc::new(Mutex::new(out));
+    let t = builder.new_timer(control, "t", Duration::ZERO, Duration::from_millis(1));
+    builder
+        .add_reaction(control, move |_, _, c| {
+            let out = out.clone();
+            c.mutate(move |b| {
+                let mut out = out.lock().unwrap();
+                let width = if out.len() == 1 { 2 } else { 1 };
+                *out = b.resize_multiport(*out, width);
+            })
+        })
+        .triggered_by(t);
+    builder
+}
+
+fn bench_mutations(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Mutations");
+    group.sample_size(10);
+    for reactors in [10, 100, 1000] {
+        group.bench_with_input(BenchmarkId::new("reactors", reactors), &reactors, |b, &reactors| {
+            b.iter_batched(
+                || build(reactors),
+                |builder| {
+                    builder.run(SchedulerOptions {
+                        timeout: Some(Duration::from_millis(TAGS - 1)),
+                        fast: true,
+                        ..Default::default()
+                    })
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_mutations);
+criterion_main!(benches);
@@ -0,0 +1,92 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! Sets a few channels of a very wide multiport at each tag,
+//! and reads the present ones downstream. The cost per tag
+//! should be proportional to the number of present channels,
+//! and not to the width of the multiport. Channels are set in
+//! descending order, which is the worst case for keeping the
+//! set of present channels sorted.
+
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use reactor_rt::assembly::PortKind;
+use reactor_rt::builder::ProgramBuilder;
+use reactor_rt::SchedulerOptions;
+
+const WIDTH: usize = 10_000;
+const TAGS: u64 = 1000;
+
+fn build(present: usize) -> ProgramBuilder {
+    let mut builder = ProgramBuilder::new();
+    let main = builder.add_main(());
+    let source = builder.add_child(main, "source", ());
+    let sink = builder.add_child(main, "sink", 0u64);
+    let out = builder.new_multiport::<u32, _>(source, "out", PortKind::Output, WIDTH);
+    let inp = builder.new_multiport::<u32, _>(sink, "in", PortKind::Input, WIDTH);
+    builder.connect_multiports(out, inp);
+    let t = builder.new_timer(source, "t", Duration::ZERO, Duration::from_millis(1));
+
+    let stride = WIDTH / present;
+    builder
+        .add_reaction(source, move |ctx, _, c| {
+            let out = c.multiport_mut(out);
+            for i in (0..present).rev() {
+                ctx.set(&mut out[i * stride], i as u32);
+            }
+        })
+        .triggered_by(t)
+        .effects(out);
+    builder
+        .add_reaction(sink, move |_, sum, c| {
+            *sum += c.multiport(inp).enumerate_values().map(|(_, v)| v as u64).sum::<u64>();
+        })
+        .triggered_by(inp);
+    builder
+}
+
+fn bench_wide_multiport(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Wide multiport");
+    group.sample_size(10);
+    for present in [10, 100, 1000] {
+        group.bench_with_input(BenchmarkId::new("present channels", present), &present, |b, &present| {
+            b.iter_batched(
+                || build(present),
+                |builder| {
+                    builder.run(SchedulerOptions {
+                        timeout: Some(Duration::from_millis(TAGS - 1)),
+                        fast: true,
+                        ..Default::default()
+                    })
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_wide_multiport);
+criterion_main!(benches);
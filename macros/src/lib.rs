@@ -0,0 +1,119 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! Attribute macros to write reactors for `reactor_rt` in
+//! plain Rust, without going through LFC. They generate the
+//! same code as LFC does for a reactor without children:
+//! an adapter type implementing `ReactorInitializer` and
+//! `ReactorBehavior`.
+//!
+//! The `#[reactor]` attribute is placed both on the struct
+//! that declares the reactor and on the impl block that
+//! declares its reactions. Fields of the struct are state
+//! variables, except those annotated with one of
+//! - `#[input]`, `#[output]`, optionally with `(width = <expr>)`
+//!   for multiports,
+//! - `#[logical_action]`, `#[physical_action]`, optionally
+//!   with `(min_delay = <expr>)`,
+//! - `#[timer]`, optionally with `(offset = <expr>, period = <expr>)`.
+//!
+//! Those are moved into the generated adapter type, named
+//! after the struct with an `Adapter` suffix. The parameters
+//! of the reactor are the struct itself, with only its state
+//! variables.
+//!
+//! Reactions are methods annotated with
+//! `#[reaction(triggers(..), uses(..), mutable(..), effects(..))]`.
+//! They take a `&mut ReactionCtx`, then one reference per component
+//! they access, named like the field of the component. Effects, and
+//! inputs whose value the reaction takes (`mutable`), are taken
+//! by mutable reference. `startup` and `shutdown` may be
+//! used as triggers. Reactions are ordered by priority in the
+//! order they are declared.
+//!
+//! ```
+//! use reactor_rt::prelude::*;
+//! use reactor_rt::{SchedulerOptions, SyncScheduler};
+//! use reactor_rt_macros::reactor;
+//!
+//! #[reactor]
+//! pub struct Doubler {
+//!     count: u32,
+//!     #[input]
+//!     inp: Port<u32>,
+//!     #[output]
+//!     out: Port<u32>,
+//! }
+//!
+//! #[reactor]
+//! impl Doubler {
+//!     #[reaction(triggers(inp), effects(out))]
+//!     fn double(&mut self, ctx: &mut ReactionCtx, inp: &Port<u32>, out: &mut Port<u32>) {
+//!         self.count += 1;
+//!         ctx.set(out, 2 * ctx.get(inp).unwrap());
+//!     }
+//! }
+//!
+//! // DoublerAdapter is generated
+//! SyncScheduler::run_main::<DoublerAdapter>(SchedulerOptions::default(), Doubler { count: 0 });
+//! ```
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, Item};
+
+mod reactions;
+mod state;
+
+/// Declares a reactor, see the crate documentation.
+#[proc_macro_attribute]
+pub fn reactor(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        let attr = proc_macro2::TokenStream::from(attr);
+        return syn::Error::new_spanned(attr, "#[reactor] takes no arguments")
+            .into_compile_error()
+            .into();
+    }
+    let result = match parse_macro_input!(item as Item) {
+        Item::Struct(item) => state::expand(item),
+        Item::Impl(item) => reactions::expand(item),
+        other => Err(syn::Error::new_spanned(
+            other,
+            "#[reactor] may only be placed on a struct or on its impl block",
+        )),
+    };
+    result.unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Declares a reaction. This is only valid on a method of an
+/// impl block annotated with `#[reactor]`, which processes it.
+#[proc_macro_attribute]
+pub fn reaction(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut result = proc_macro2::TokenStream::from(item);
+    let error = syn::Error::new(
+        proc_macro2::Span::call_site(),
+        "#[reaction] must be placed on a method of an impl block annotated with #[reactor]",
+    );
+    result.extend(error.into_compile_error());
+    result.into()
+}
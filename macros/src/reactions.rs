@@ -0,0 +1,291 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! Expansion of `#[reactor]` on an impl block. This generates
+//! the `ReactorInitializer` and `ReactorBehavior` impls of
+//! the adapter, using the hidden items generated on the struct.
+
+use proc_macro2::{Ident, TokenStream};
+use quote::{quote, quote_spanned};
+use syn::{FnArg, ImplItem, ImplItemFn, ItemImpl, Meta, Pat, Type};
+
+use crate::state::{adapter_name, component_field, effects_method};
+
+/// A method annotated with `#[reaction]`.
+struct Reaction {
+    method: Ident,
+    triggers: Vec<Ident>,
+    uses: Vec<Ident>,
+    /// Input ports whose value may be taken or mutated.
+    mutable: Vec<Ident>,
+    effects: Vec<Ident>,
+    /// Components passed to the method, and whether they're
+    /// passed by mutable reference.
+    params: Vec<(Ident, bool)>,
+}
+
+impl Reaction {
+    fn parse(attr: &syn::Attribute, method: &ImplItemFn) -> syn::Result<Reaction> {
+        let mut reaction = Reaction {
+            method: method.sig.ident.clone(),
+            triggers: Vec::new(),
+            uses: Vec::new(),
+            mutable: Vec::new(),
+            effects: Vec::new(),
+            params: Vec::new(),
+        };
+
+        if let Meta::List(_) = attr.meta {
+            attr.parse_nested_meta(|meta| {
+                let list = if meta.path.is_ident("triggers") {
+                    &mut reaction.triggers
+                } else if meta.path.is_ident("uses") {
+                    &mut reaction.uses
+                } else if meta.path.is_ident("mutable") {
+                    &mut reaction.mutable
+                } else if meta.path.is_ident("effects") {
+                    &mut reaction.effects
+                } else {
+                    return Err(meta.error("unsupported property, expected one of triggers, uses, mutable, effects"));
+                };
+                meta.parse_nested_meta(|component| match component.path.get_ident() {
+                    Some(name) => {
+                        list.push(name.clone());
+                        Ok(())
+                    }
+                    None => Err(component.error("expected the name of a component")),
+                })
+            })?;
+        }
+
+        for name in reaction.uses.iter().chain(&reaction.mutable).chain(&reaction.effects) {
+            if is_special(name) {
+                return Err(syn::Error::new_spanned(name, "startup and shutdown may only be triggers"));
+            }
+        }
+
+        let mut inputs = method.sig.inputs.iter();
+        if !matches!(inputs.next(), Some(FnArg::Receiver(_))) {
+            return Err(syn::Error::new_spanned(&method.sig, "A reaction must take self by reference"));
+        }
+        if inputs.next().is_none() {
+            return Err(syn::Error::new_spanned(
+                &method.sig,
+                "A reaction must take a &mut ReactionCtx as its first parameter",
+            ));
+        }
+        for input in inputs {
+            let FnArg::Typed(typed) = input else { unreachable!() };
+            let Pat::Ident(pat) = &*typed.pat else {
+                return Err(syn::Error::new_spanned(&typed.pat, "expected the name of a component"));
+            };
+            let Type::Reference(ty) = &*typed.ty else {
+                return Err(syn::Error::new_spanned(&typed.ty, "Components are passed by reference"));
+            };
+            let name = &pat.ident;
+            let mutable = ty.mutability.is_some();
+            let is_effect = reaction.effects.contains(name);
+            let is_mutable = reaction.mutable.contains(name);
+            if is_special(name) || !(is_effect || is_mutable || reaction.triggers.contains(name) || reaction.uses.contains(name))
+            {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    format!("`{}` is not declared as a trigger, use or effect of this reaction", name),
+                ));
+            } else if mutable && !(is_effect || is_mutable) {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    "Only effects and mutable inputs may be passed by mutable reference",
+                ));
+            } else if !mutable && is_effect {
+                return Err(syn::Error::new_spanned(ty, "Effects are passed by mutable reference"));
+            } else if !mutable && is_mutable {
+                return Err(syn::Error::new_spanned(ty, "Mutable inputs are passed by mutable reference"));
+            }
+            reaction.params.push((name.clone(), mutable));
+        }
+
+        Ok(reaction)
+    }
+
+    /// Statements that declare the dependencies of the reaction.
+    fn declarations(&self, rid: &Ident) -> TokenStream {
+        let triggers = self.triggers.iter().map(|name| {
+            let id = match name.to_string().as_str() {
+                "startup" => quote! { ::reactor_rt::assembly::TriggerId::STARTUP },
+                "shutdown" => quote! { ::reactor_rt::assembly::TriggerId::SHUTDOWN },
+                _ => {
+                    let field = component_field(name);
+                    quote_spanned! {name.span()=> ::reactor_rt::assembly::TriggerLike::get_id(&__self.#field) }
+                }
+            };
+            quote! { __assembler.declare_triggers(#id, #rid)?; }
+        });
+        let uses = self.uses.iter().map(|name| {
+            let field = component_field(name);
+            quote_spanned! {name.span()=>
+                __assembler.declare_uses(#rid, ::reactor_rt::assembly::TriggerLike::get_id(&__self.#field))?;
+            }
+        });
+        let mutable = self.mutable.iter().map(|name| {
+            let field = component_field(name);
+            quote_spanned! {name.span()=>
+                __assembler.declare_mutable_input(#rid, ::reactor_rt::assembly::TriggerLike::get_id(&__self.#field))?;
+            }
+        });
+        let effects = self.effects.iter().map(|name| {
+            let method = effects_method(name);
+            quote_spanned! {name.span()=> __self.#method(__assembler, #rid)?; }
+        });
+        quote! { #(#triggers)* #(#uses)* #(#mutable)* #(#effects)* }
+    }
+
+    /// Expression that calls the reaction from the adapter.
+    fn call(&self) -> TokenStream {
+        let method = &self.method;
+        let args = self.params.iter().map(|(name, mutable)| {
+            let field = component_field(name);
+            if *mutable {
+                quote! { &mut self.#field }
+            } else {
+                quote! { &self.#field }
+            }
+        });
+        quote! { self.__impl.#method(ctx, #(#args),*) }
+    }
+}
+
+fn is_special(name: &Ident) -> bool {
+    name == "startup" || name == "shutdown"
+}
+
+pub(crate) fn expand(mut item: ItemImpl) -> syn::Result<TokenStream> {
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&item.generics, "Generic reactors are not supported"));
+    }
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(syn::Error::new_spanned(
+            path,
+            "#[reactor] must be placed on an inherent impl block",
+        ));
+    }
+    let name = match &*item.self_ty {
+        Type::Path(path) if path.qself.is_none() && path.path.get_ident().is_some() => path.path.get_ident().unwrap().clone(),
+        other => {
+            return Err(syn::Error::new_spanned(
+                other,
+                "expected the name of a struct annotated with #[reactor]",
+            ))
+        }
+    };
+    let adapter = adapter_name(&name);
+
+    let mut reactions = Vec::new();
+    for impl_item in &mut item.items {
+        let ImplItem::Fn(method) = impl_item else { continue };
+        if let Some(pos) = method.attrs.iter().position(|a| a.path().is_ident("reaction")) {
+            let attr = method.attrs.remove(pos);
+            reactions.push(Reaction::parse(&attr, method)?);
+        }
+    }
+
+    let num_user_reactions = reactions.len();
+    let labels = reactions.iter().enumerate().map(|(i, r)| {
+        let label = r.method.to_string();
+        quote! { __labels[#i] = ::std::option::Option::Some(#label); }
+    });
+    let rids: Vec<Ident> = (0..num_user_reactions)
+        .map(|i| quote::format_ident!("__react_{}", i))
+        .collect();
+    let declarations = reactions.iter().zip(&rids).map(|(r, rid)| r.declarations(rid));
+    let rid_ixs = 0..num_user_reactions;
+    let calls = reactions.iter().map(Reaction::call);
+
+    Ok(quote! {
+        #item
+
+        impl #adapter {
+            #[doc(hidden)]
+            const __NUM_USER_REACTIONS: usize = #num_user_reactions;
+            #[doc(hidden)]
+            const __NUM_REACTIONS: usize = Self::__NUM_USER_REACTIONS + 2 * Self::__NUM_TIMERS;
+        }
+
+        impl ::reactor_rt::assembly::ReactorInitializer for #adapter {
+            type Wrapped = #name;
+            type Params = #name;
+            const MAX_REACTION_ID: ::reactor_rt::LocalReactionId =
+                ::reactor_rt::LocalReactionId::new(#adapter::__NUM_REACTIONS as _);
+
+            fn assemble(
+                __params: Self::Params,
+                __ctx: ::reactor_rt::assembly::AssemblyCtx<Self>,
+            ) -> ::reactor_rt::assembly::AssemblyResult<::reactor_rt::assembly::FinishedReactor<Self>> {
+                // synthetic timer reactions have no label
+                #[allow(unused_mut)]
+                let mut __labels: [::std::option::Option<&'static str>; #adapter::__NUM_REACTIONS] =
+                    [::std::option::Option::None; #adapter::__NUM_REACTIONS];
+                #(#labels)*
+
+                __ctx.assemble(|__ctx| {
+                    __ctx.assemble_self(
+                        |__assembler, __id| Self::__user_assemble(__assembler, __id, __params),
+                        #adapter::__NUM_USER_REACTIONS,
+                        __labels,
+                        |__assembler, __self, __reactions| {
+                            let [#(#rids,)* ..] = __reactions;
+                            #(#declarations)*
+                            __self.__declare_timers(__assembler, &__reactions[#adapter::__NUM_USER_REACTIONS..])
+                        },
+                    )
+                })
+            }
+        }
+
+        impl ::reactor_rt::ReactorBehavior for #adapter {
+            #[inline]
+            fn id(&self) -> ::reactor_rt::ReactorId {
+                self.__id
+            }
+
+            fn react(&mut self, ctx: &mut ::reactor_rt::ReactionCtx, rid: ::reactor_rt::LocalReactionId) {
+                match rid.raw() as usize {
+                    #( #rid_ixs => #calls, )*
+                    __rid if __rid < #adapter::__NUM_REACTIONS => {
+                        self.__react_timer(ctx, __rid - #adapter::__NUM_USER_REACTIONS)
+                    }
+                    _ => panic!(
+                        "Invalid reaction ID: {} should be < {}",
+                        rid,
+                        <Self as ::reactor_rt::assembly::ReactorInitializer>::MAX_REACTION_ID
+                    ),
+                }
+            }
+
+            fn cleanup_tag(&mut self, ctx: &::reactor_rt::CleanupCtx) {
+                self.__cleanup_tag(ctx)
+            }
+        }
+    })
+}
@@ -0,0 +1,315 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! Expansion of `#[reactor]` on a struct. This splits the
+//! struct into the state struct and the adapter type, which
+//! owns the components. Dependencies on components are declared
+//! through hidden methods of the adapter, which the expansion
+//! of the impl block calls by name.
+
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Attribute, Expr, Field, Fields, ItemStruct, Meta};
+
+/// Name of the adapter type generated for the given reactor.
+pub(crate) fn adapter_name(reactor: &Ident) -> Ident {
+    format_ident!("{}Adapter", reactor)
+}
+
+/// Name of the adapter field that holds the given component.
+pub(crate) fn component_field(name: &Ident) -> Ident {
+    format_ident!("__{}", name)
+}
+
+/// Name of the hidden method that declares the given component as an effect.
+pub(crate) fn effects_method(name: &Ident) -> Ident {
+    format_ident!("__effects_{}", name)
+}
+
+enum ComponentKind {
+    Port { kind: Ident, width: Option<Expr> },
+    LogicalAction { min_delay: Option<Expr> },
+    PhysicalAction { min_delay: Option<Expr> },
+    Timer { offset: Option<Expr>, period: Option<Expr> },
+}
+
+struct Component {
+    field: Field,
+    kind: ComponentKind,
+}
+
+impl Component {
+    /// Returns the field back if it is a state variable.
+    fn parse(mut field: Field) -> syn::Result<Result<Component, Field>> {
+        let mut kind = None;
+        let mut rest = Vec::with_capacity(field.attrs.len());
+        for attr in std::mem::take(&mut field.attrs) {
+            let Some(parsed) = ComponentKind::parse(&attr)? else {
+                rest.push(attr);
+                continue;
+            };
+            if kind.is_some() {
+                return Err(syn::Error::new_spanned(attr, "A field may declare at most one component"));
+            }
+            kind = Some(parsed);
+        }
+        field.attrs = rest;
+        Ok(match kind {
+            Some(kind) => Ok(Component { field, kind }),
+            None => Err(field),
+        })
+    }
+
+    fn name(&self) -> &Ident {
+        self.field.ident.as_ref().unwrap()
+    }
+
+    /// Expression that creates the component with a ComponentCreator.
+    fn initializer(&self) -> TokenStream {
+        let lf_name = self.name().to_string();
+        let opt = |e: &Option<Expr>| match e {
+            Some(e) => quote! { ::std::option::Option::Some(#e) },
+            None => quote! { ::std::option::Option::None },
+        };
+        let or_zero = |e: &Option<Expr>| match e {
+            Some(e) => quote! { #e },
+            None => quote! { ::reactor_rt::Duration::ZERO },
+        };
+        match &self.kind {
+            ComponentKind::Port { kind, width: None } => {
+                quote! { __assembler.new_port(#lf_name, ::reactor_rt::assembly::PortKind::#kind) }
+            }
+            ComponentKind::Port { kind, width: Some(width) } => {
+                quote! { __assembler.new_multiport(#lf_name, ::reactor_rt::assembly::PortKind::#kind, #width)? }
+            }
+            ComponentKind::LogicalAction { min_delay } => {
+                let min_delay = opt(min_delay);
+                quote! { __assembler.new_logical_action(#lf_name, #min_delay) }
+            }
+            ComponentKind::PhysicalAction { min_delay } => {
+                let min_delay = opt(min_delay);
+                quote! { __assembler.new_physical_action(#lf_name, #min_delay) }
+            }
+            ComponentKind::Timer { offset, period } => {
+                let (offset, period) = (or_zero(offset), or_zero(period));
+                quote! { __assembler.new_timer(#lf_name, #offset, #period) }
+            }
+        }
+    }
+
+    fn cleanup(&self) -> Option<TokenStream> {
+        let field = component_field(self.name());
+        let method = match &self.kind {
+            ComponentKind::Port { width: None, .. } => quote! { cleanup_port },
+            ComponentKind::Port { width: Some(_), .. } => quote! { cleanup_multiport },
+            ComponentKind::LogicalAction { .. } => quote! { cleanup_logical_action },
+            ComponentKind::PhysicalAction { .. } => quote! { cleanup_physical_action },
+            ComponentKind::Timer { .. } => return None,
+        };
+        Some(quote! { ctx.#method(&mut self.#field); })
+    }
+
+    /// Hidden method that declares this component as the effect
+    /// of a reaction. Timers cannot be the effect of a reaction.
+    fn effects_method(&self) -> Option<TokenStream> {
+        let field = component_field(self.name());
+        let body = match &self.kind {
+            ComponentKind::Port { width: None, .. } => quote! { __assembler.effects_port(__reaction, &self.#field) },
+            ComponentKind::Port { width: Some(_), .. } => quote! { __assembler.effects_multiport(__reaction, &self.#field) },
+            ComponentKind::LogicalAction { .. } => quote! { __assembler.effects_logical_action(__reaction, &self.#field) },
+            ComponentKind::PhysicalAction { .. } => quote! { __assembler.effects_physical_action(__reaction, &self.#field) },
+            ComponentKind::Timer { .. } => return None,
+        };
+        let method = effects_method(self.name());
+        Some(quote_spanned! {self.field.span()=>
+            #[doc(hidden)]
+            fn #method(
+                &self,
+                __assembler: &mut ::reactor_rt::assembly::DependencyDeclarator<Self>,
+                __reaction: ::reactor_rt::assembly::GlobalReactionId,
+            ) -> ::reactor_rt::assembly::AssemblyResult<()> {
+                #body
+            }
+        })
+    }
+}
+
+impl ComponentKind {
+    /// Returns None if the attribute is not a component attribute.
+    fn parse(attr: &Attribute) -> syn::Result<Option<Self>> {
+        let Some(ident) = attr.path().get_ident() else {
+            return Ok(None);
+        };
+        let (names, mut values): (&[&str], [Option<Expr>; 2]) = match ident.to_string().as_str() {
+            "input" | "output" => (&["width"], Default::default()),
+            "logical_action" | "physical_action" => (&["min_delay"], Default::default()),
+            "timer" => (&["offset", "period"], Default::default()),
+            _ => return Ok(None),
+        };
+
+        if let Meta::List(_) = attr.meta {
+            attr.parse_nested_meta(|meta| {
+                match names.iter().position(|name| meta.path.is_ident(name)) {
+                    Some(i) if values[i].is_none() => values[i] = Some(meta.value()?.parse()?),
+                    Some(_) => return Err(meta.error("duplicate property")),
+                    None => return Err(meta.error(format!("unsupported property, expected one of {:?}", names))),
+                }
+                Ok(())
+            })?;
+        }
+
+        let [first, second] = values;
+        Ok(Some(match ident.to_string().as_str() {
+            "input" => ComponentKind::Port { kind: format_ident!("Input"), width: first },
+            "output" => ComponentKind::Port { kind: format_ident!("Output"), width: first },
+            "logical_action" => ComponentKind::LogicalAction { min_delay: first },
+            "physical_action" => ComponentKind::PhysicalAction { min_delay: first },
+            _ => ComponentKind::Timer { offset: first, period: second },
+        }))
+    }
+}
+
+pub(crate) fn expand(mut item: ItemStruct) -> syn::Result<TokenStream> {
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&item.generics, "Generic reactors are not supported"));
+    }
+    let Fields::Named(fields) = &mut item.fields else {
+        return Err(syn::Error::new_spanned(&item.fields, "A reactor must have named fields"));
+    };
+
+    let mut state_fields = Punctuated::new();
+    let mut components = Vec::new();
+    for field in std::mem::take(&mut fields.named) {
+        match Component::parse(field)? {
+            Ok(component) => components.push(component),
+            Err(state) => state_fields.push(state),
+        }
+    }
+    fields.named = state_fields;
+
+    let vis = &item.vis;
+    let name = &item.ident;
+    let adapter = adapter_name(name);
+
+    let field_decls = components.iter().map(|c| {
+        let Field { attrs, vis, ty, .. } = &c.field;
+        let field = component_field(c.name());
+        quote! { #(#attrs)* #vis #field: #ty }
+    });
+    let field_inits = components.iter().map(|c| {
+        let field = component_field(c.name());
+        let init = c.initializer();
+        quote! { #field: #init }
+    });
+    let cleanups = components.iter().filter_map(Component::cleanup);
+    let effects_methods = components.iter().filter_map(Component::effects_method);
+
+    let timers: Vec<Ident> = components
+        .iter()
+        .filter(|c| matches!(c.kind, ComponentKind::Timer { .. }))
+        .map(|c| component_field(c.name()))
+        .collect();
+    let num_timers = timers.len();
+    // Each timer has two synthetic reactions: the first one
+    // bootstraps the timer at startup, the second reschedules
+    // it when it fires.
+    let bootstrap_ix = (0..num_timers).map(|i| 2 * i);
+    let reschedule_ix = (0..num_timers).map(|i| 2 * i + 1);
+    let react_timer = if timers.is_empty() {
+        quote! {
+            unreachable!("Reactor has no timers, invalid reaction index {}", __index)
+        }
+    } else {
+        quote! {
+            match __index {
+                #( #bootstrap_ix => ctx.bootstrap_timer(&mut self.#timers), )*
+                #( #reschedule_ix => ctx.reschedule_timer(&mut self.#timers), )*
+                _ => unreachable!("Invalid timer reaction index {}", __index),
+            }
+        }
+    };
+
+    Ok(quote! {
+        #item
+
+        /// Adapter generated by `#[reactor]`, which owns the
+        /// components of the reactor.
+        #vis struct #adapter {
+            __id: ::reactor_rt::ReactorId,
+            __impl: #name,
+            #( #field_decls, )*
+        }
+
+        #[allow(dead_code, unused_variables)]
+        impl #adapter {
+            #[doc(hidden)]
+            const __NUM_TIMERS: usize = #num_timers;
+
+            #[doc(hidden)]
+            fn __user_assemble(
+                __assembler: &mut ::reactor_rt::assembly::ComponentCreator<Self>,
+                __id: ::reactor_rt::ReactorId,
+                __impl: #name,
+            ) -> ::reactor_rt::assembly::AssemblyResult<Self> {
+                ::std::result::Result::Ok(Self {
+                    __id,
+                    __impl,
+                    #( #field_inits, )*
+                })
+            }
+
+            #[doc(hidden)]
+            fn __cleanup_tag(&mut self, ctx: &::reactor_rt::CleanupCtx) {
+                #( #cleanups )*
+            }
+
+            /// Declares the dependencies of the synthetic timer reactions.
+            #[doc(hidden)]
+            fn __declare_timers(
+                &self,
+                __assembler: &mut ::reactor_rt::assembly::DependencyDeclarator<Self>,
+                __reactions: &[::reactor_rt::assembly::GlobalReactionId],
+            ) -> ::reactor_rt::assembly::AssemblyResult<()> {
+                #(
+                    let (__bootstrap, __reschedule) = (__reactions[0], __reactions[1]);
+                    __assembler.declare_triggers(::reactor_rt::assembly::TriggerId::STARTUP, __bootstrap)?;
+                    __assembler.effects_timer(__bootstrap, &self.#timers)?;
+                    // this also orders the reschedule reaction after the user reactions
+                    __assembler.declare_triggers(::reactor_rt::assembly::TriggerLike::get_id(&self.#timers), __reschedule)?;
+                    let __reactions = &__reactions[2..];
+                )*
+                ::std::result::Result::Ok(())
+            }
+
+            #[doc(hidden)]
+            fn __react_timer(&mut self, ctx: &mut ::reactor_rt::ReactionCtx, __index: usize) {
+                #react_timer
+            }
+
+            #( #effects_methods )*
+        }
+    })
+}
@@ -0,0 +1,220 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use std::sync::{Arc, Mutex};
+
+use reactor_rt::prelude::*;
+use reactor_rt::{SchedulerOptions, SyncScheduler, Timer};
+use reactor_rt_macros::reactor;
+
+type Log<T> = Arc<Mutex<Vec<T>>>;
+
+#[reactor]
+struct Ticker {
+    log: Log<String>,
+    ticks: u32,
+    #[timer(period = Duration::from_millis(10))]
+    tick: Timer,
+    #[timer(offset = Duration::from_millis(25))]
+    once: Timer,
+}
+
+#[reactor]
+impl Ticker {
+    #[reaction(triggers(tick))]
+    fn on_tick(&mut self, ctx: &mut ReactionCtx) {
+        self.ticks += 1;
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("tick at {:?}", ctx.get_elapsed_logical_time()));
+    }
+
+    #[reaction(triggers(once), uses(tick))]
+    fn on_once(&mut self, ctx: &mut ReactionCtx, tick: &Timer) {
+        let tick_present = ctx.is_present(tick);
+        self.log.lock().unwrap().push(format!("once, tick present: {}", tick_present));
+    }
+
+    #[reaction(triggers(shutdown))]
+    fn on_shutdown(&mut self, _ctx: &mut ReactionCtx) {
+        self.log.lock().unwrap().push(format!("shutdown after {} ticks", self.ticks));
+    }
+}
+
+#[test]
+fn test_timers_and_shutdown() {
+    let log: Log<String> = Default::default();
+    let options = SchedulerOptions {
+        timeout: Some(Duration::from_millis(35)),
+        ..Default::default()
+    };
+    SyncScheduler::run_main::<TickerAdapter>(options, Ticker { log: log.clone(), ticks: 0 });
+
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![
+            "tick at 0ns",
+            "tick at 10ms",
+            "tick at 20ms",
+            "once, tick present: false",
+            "tick at 30ms",
+            "shutdown after 4 ticks",
+        ]
+    );
+}
+
+#[reactor]
+struct Throttle {
+    log: Log<Duration>,
+    #[timer(period = Duration::from_millis(10))]
+    tick: Timer,
+}
+
+#[reactor]
+impl Throttle {
+    #[reaction(triggers(tick))]
+    fn on_tick(&mut self, ctx: &mut ReactionCtx) {
+        self.log.lock().unwrap().push(ctx.get_elapsed_logical_time());
+    }
+
+    #[reaction(triggers(tick))]
+    fn speed_up(&mut self, ctx: &mut ReactionCtx, tick: &Timer) {
+        if ctx.get_elapsed_logical_time() == Duration::from_millis(10) {
+            ctx.set_timer_period(tick, Duration::from_millis(5));
+        }
+    }
+}
+
+#[test]
+fn test_timer_period_changed_by_a_reaction_is_used_next() {
+    let log: Log<Duration> = Default::default();
+    let options = SchedulerOptions {
+        timeout: Some(Duration::from_millis(20)),
+        ..Default::default()
+    };
+    SyncScheduler::run_main::<ThrottleAdapter>(options, Throttle { log: log.clone() });
+
+    let expected: Vec<_> = [0, 10, 15, 20].iter().map(|ms| Duration::from_millis(*ms)).collect();
+    assert_eq!(*log.lock().unwrap(), expected);
+}
+
+#[reactor]
+struct Counter {
+    log: Log<(u32, Duration)>,
+    #[logical_action(min_delay = Duration::from_millis(1))]
+    next: LogicalAction<u32>,
+}
+
+#[reactor]
+impl Counter {
+    #[reaction(triggers(startup), effects(next))]
+    fn start(&mut self, ctx: &mut ReactionCtx, next: &mut LogicalAction<u32>) {
+        ctx.schedule_with_v(next, Some(0), Asap);
+    }
+
+    #[reaction(triggers(next), effects(next))]
+    fn count(&mut self, ctx: &mut ReactionCtx, next: &mut LogicalAction<u32>) {
+        let v = ctx.get(next).unwrap();
+        self.log.lock().unwrap().push((v, ctx.get_elapsed_logical_time()));
+        if v < 2 {
+            ctx.schedule_with_v(next, Some(v + 1), Asap);
+        } else {
+            ctx.request_stop(Asap);
+        }
+    }
+}
+
+#[test]
+fn test_logical_action() {
+    let log: Log<(u32, Duration)> = Default::default();
+    SyncScheduler::run_main::<CounterAdapter>(SchedulerOptions::default(), Counter { log: log.clone() });
+
+    let ms = Duration::from_millis;
+    assert_eq!(*log.lock().unwrap(), vec![(0, ms(1)), (1, ms(2)), (2, ms(3))]);
+}
+
+const WIDTH: usize = 3;
+
+#[reactor]
+struct Broadcast {
+    log: Log<Vec<Option<u32>>>,
+    #[output(width = WIDTH)]
+    out: Multiport<u32>,
+}
+
+#[reactor]
+impl Broadcast {
+    #[reaction(triggers(startup), effects(out))]
+    fn send(&mut self, ctx: &mut ReactionCtx, out: &mut Multiport<u32>) {
+        for (i, channel) in out.iter_mut().enumerate().skip(1) {
+            ctx.set(channel, i as u32);
+        }
+    }
+
+    #[reaction(triggers(startup), uses(out))]
+    fn check(&self, ctx: &mut ReactionCtx, out: &Multiport<u32>) {
+        let values = out.iter().map(|channel| ctx.get(channel)).collect();
+        self.log.lock().unwrap().push(values);
+    }
+}
+
+#[test]
+fn test_multiport_effects_are_ordered_by_priority() {
+    let log: Log<Vec<Option<u32>>> = Default::default();
+    SyncScheduler::run_main::<BroadcastAdapter>(SchedulerOptions::default(), Broadcast { log: log.clone() });
+
+    assert_eq!(*log.lock().unwrap(), vec![vec![None, Some(1), Some(2)]]);
+}
+
+#[reactor]
+struct Buffer {
+    log: Log<Vec<u32>>,
+    #[output]
+    out: Port<Vec<u32>>,
+}
+
+#[reactor]
+impl Buffer {
+    #[reaction(triggers(startup), effects(out))]
+    fn fill(&mut self, ctx: &mut ReactionCtx, out: &mut Port<Vec<u32>>) {
+        ctx.set(out, vec![1, 2]);
+    }
+
+    #[reaction(triggers(out), mutable(out))]
+    fn drain(&mut self, ctx: &mut ReactionCtx, out: &mut Port<Vec<u32>>) {
+        let mut values = ctx.take(out).unwrap();
+        values.push(3);
+        assert!(!ctx.is_present(out));
+        self.log.lock().unwrap().push(values);
+    }
+}
+
+#[test]
+fn test_mutable_input() {
+    let log: Log<Vec<u32>> = Default::default();
+    SyncScheduler::run_main::<BufferAdapter>(SchedulerOptions::default(), Buffer { log: log.clone() });
+
+    assert_eq!(*log.lock().unwrap(), vec![vec![1, 2, 3]]);
+}
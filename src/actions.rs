@@ -60,11 +60,13 @@ pub(crate) struct Action<Kind, T: Sync> {
 impl<K, T: Sync> Action<K, T> {
     /// Record a future value that can be queried at a future logical time.
     /// Note that we don't check that the given time is in the future. If it's
-    /// in the past, the value will never be reclaimed.
-    ///
-    ///
+    /// in the past, the value will never be reclaimed. Values at
+    /// [EventTag::FOREVER] are dropped, as that tag never occurs.
     #[inline]
     pub(crate) fn schedule_future_value(&mut self, time: EventTag, value: Option<T>) {
+        if time.is_forever() {
+            return;
+        }
         match self.map.entry(Reverse(time)) {
             Entry::Vacant(e) => e.insert(value),
             Entry::Occupied(ref mut e) => {
@@ -80,6 +82,27 @@ impl<K, T: Sync> Action<K, T> {
         self.map.remove(&Reverse(*time)).flatten()
     }
 
+    /// Drop the value scheduled for the given tag. Returns
+    /// whether the action was scheduled at that tag.
+    #[inline]
+    pub(crate) fn cancel_value(&mut self, time: &EventTag) -> bool {
+        self.map.remove(&Reverse(*time)).is_some()
+    }
+
+    /// Returns the tags for which a value is currently
+    /// scheduled, in ascending order.
+    pub(crate) fn scheduled_tags(&self) -> Vec<EventTag> {
+        let mut tags: Vec<EventTag> = self.map.iter().map(|(Reverse(tag), _)| *tag).collect();
+        tags.sort();
+        tags
+    }
+
+    /// Returns the earliest tag strictly after the given one
+    /// for which a value is scheduled.
+    pub(crate) fn next_scheduled_tag(&self, after: EventTag) -> Option<EventTag> {
+        self.map.iter().map(|(Reverse(tag), _)| *tag).filter(|tag| *tag > after).min()
+    }
+
     fn new_impl(id: TriggerId, min_delay: Option<Duration>, _is_logical: bool) -> Self {
         Action {
             min_delay: min_delay.unwrap_or(Duration::ZERO),
@@ -91,6 +114,12 @@ impl<K, T: Sync> Action<K, T> {
     }
 }
 
+impl<T: Sync, K> TriggerLike for Action<K, T> {
+    fn get_id(&self) -> TriggerId {
+        self.id
+    }
+}
+
 impl<T: Sync, K> ReactionTrigger<T> for Action<K, T> {
     #[inline]
     fn is_present(&self, now: &EventTag, _start: &Instant) -> bool {
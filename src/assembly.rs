@@ -5,6 +5,7 @@ use AssemblyErrorImpl::*;
 pub use crate::ids::GlobalReactionId;
 // this is where most of the stuff is implemented
 pub use crate::scheduler::assembly_impl::*;
+use crate::scheduler::GraphId;
 pub use crate::triggers::{TriggerId, TriggerLike};
 use crate::{DebugInfoRegistry, LocalReactionId, ReactorBehavior};
 pub(crate) type PortId = TriggerId;
@@ -48,9 +49,29 @@ impl AssemblyError {
 
 pub(crate) enum AssemblyErrorImpl {
     CyclicDependency(PortId, PortId),
-    CyclicDependencyGraph,
+    /// The dependency graph has a cycle, which goes through
+    /// the given nodes in order.
+    CyclicDependencyGraph(Vec<GraphId>),
     CannotBind(PortId, PortId),
     IdOverflow,
+    /// A reaction takes the value of a port (first reaction),
+    /// which another reaction may read after it (second reaction).
+    MutableInputConflict(PortId, GlobalReactionId, GlobalReactionId),
+    /// The sides of a zipped binding have different widths.
+    /// Fields are the first port and width of the upstream,
+    /// then the same for the downstream. A port is None if
+    /// the side is empty.
+    WidthMismatch(Option<PortId>, usize, Option<PortId>, usize),
+    /// The width of the downstream of an iterated binding is not
+    /// a multiple of the width of the upstream. Same fields as
+    /// [Self::WidthMismatch].
+    IteratedWidthMismatch(Option<PortId>, usize, Option<PortId>, usize),
+    /// Two multiports of a bank that are interleaved do not have
+    /// the same width. Fields are both multiports and their width.
+    InterleavedWidthMismatch(PortId, usize, PortId, usize),
+    /// The program has lints, and the scheduler was
+    /// configured to deny them (see [crate::SchedulerOptions::deny_lints]).
+    DeniedLints(Vec<Lint>),
 }
 
 impl AssemblyError {
@@ -61,13 +82,114 @@ impl AssemblyError {
                 debug.fmt_component(upstream),
                 debug.fmt_component(downstream)
             ),
-            CyclicDependencyGraph => "Cyclic dependency graph".to_string(),
+            CyclicDependencyGraph(ref cycle) => {
+                let path: Vec<String> = cycle
+                    .iter()
+                    .chain(cycle.first())
+                    .map(|node| match *node {
+                        GraphId::Reaction(id) => debug.fmt_reaction(id).to_string(),
+                        GraphId::Trigger(id) => debug.fmt_component(id).to_string(),
+                    })
+                    .collect();
+                format!("Cyclic dependency graph: {}", path.join(" -> "))
+            }
             CannotBind(upstream, downstream) => format!(
                 "Cannot bind {} to {}, downstream is already bound",
                 debug.fmt_component(upstream),
                 debug.fmt_component(downstream)
             ),
             IdOverflow => "Overflow when allocating component ID".to_string(),
+            MutableInputConflict(port, reaction, other) => format!(
+                "Reaction {} declares {} as mutable, but reaction {} may read it concurrently or afterwards",
+                debug.fmt_reaction(reaction),
+                debug.fmt_component(port),
+                debug.fmt_reaction(other)
+            ),
+            WidthMismatch(upstream, up_width, downstream, down_width) => format!(
+                "Cannot bind {} to {}, their widths differ",
+                fmt_ports(debug, upstream, up_width),
+                fmt_ports(debug, downstream, down_width)
+            ),
+            IteratedWidthMismatch(upstream, up_width, downstream, down_width) => format!(
+                "Cannot bind {} to {}, the downstream width is not a multiple of the upstream width",
+                fmt_ports(debug, upstream, up_width),
+                fmt_ports(debug, downstream, down_width)
+            ),
+            InterleavedWidthMismatch(first, first_width, other, other_width) => format!(
+                "Cannot interleave multiports {} (width {}) and {} (width {}) of the same bank, their widths differ",
+                debug.fmt_component(first),
+                first_width,
+                debug.fmt_component(other),
+                other_width
+            ),
+            DeniedLints(ref lints) => {
+                let mut result = format!("Found {} lint(s), which are denied:", lints.len());
+                for lint in lints {
+                    result.push_str("\n  ");
+                    result.push_str(&lint.display(debug));
+                }
+                result
+            }
+        }
+    }
+}
+
+/// Formats one side of a binding, given its first port and its width.
+fn fmt_ports(debug: &DebugInfoRegistry, first: Option<PortId>, width: usize) -> String {
+    match first {
+        Some(port) => format!("{} (width {})", debug.fmt_component(port), width),
+        None => "no port (width 0)".to_string(),
+    }
+}
+
+/// A suspicious construct in the program, which does not
+/// prevent it from running, but is most likely a mistake.
+/// Those are reported as warnings when the program starts.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum Lint {
+    /// An input port that is neither bound nor set by a reaction.
+    UnconnectedInput(PortId),
+    /// An output port that is neither bound nor read by a reaction.
+    UnusedOutput(PortId),
+    /// A reaction without triggers, which never executes.
+    UntriggeredReaction(GlobalReactionId),
+    /// An action that no reaction declares to schedule.
+    UnscheduledAction(TriggerId),
+    /// A zipped binding whose sides have different widths
+    /// (first upstream port and width, then the same for the
+    /// downstream). The excess ports are left unbound.
+    ZipWidthMismatch(PortId, usize, PortId, usize),
+    /// An iterated binding where the downstream width is not a
+    /// multiple of the upstream width. Same fields as [Self::ZipWidthMismatch].
+    IteratedWidthMismatch(PortId, usize, PortId, usize),
+}
+
+impl Lint {
+    pub(crate) fn display(&self, debug: &DebugInfoRegistry) -> String {
+        match *self {
+            Lint::UnconnectedInput(port) => format!("Input port {} is never bound or set", debug.fmt_component(port)),
+            Lint::UnusedOutput(port) => format!("Output port {} is never bound or read", debug.fmt_component(port)),
+            Lint::UntriggeredReaction(reaction) => {
+                format!(
+                    "Reaction {} has no triggers and will never execute",
+                    debug.fmt_reaction(reaction)
+                )
+            }
+            Lint::UnscheduledAction(action) => format!("Action {} is never scheduled", debug.fmt_component(action)),
+            Lint::ZipWidthMismatch(upstream, up_width, downstream, down_width) => format!(
+                "Binding from {} to {} has mismatched widths ({} vs {}), excess ports are left unbound",
+                debug.fmt_component(upstream),
+                debug.fmt_component(downstream),
+                up_width,
+                down_width
+            ),
+            Lint::IteratedWidthMismatch(upstream, up_width, downstream, down_width) => format!(
+                "Iterated binding from {} to {} has mismatched widths ({} is not a multiple of {})",
+                debug.fmt_component(upstream),
+                debug.fmt_component(downstream),
+                down_width,
+                up_width
+            ),
         }
     }
 }
@@ -0,0 +1,40 @@
+//! Module containing an API to build reactor programs
+//! by hand, without the Lingua Franca code generator.
+//!
+//! The entry point is [ProgramBuilder]. Reactors and their
+//! components are declared on the builder, which hands out
+//! typed handles. Reactions are closures, whose dependencies
+//! are declared like in LF. For instance, the following program
+//! forwards a value from one reactor to another:
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use reactor_rt::builder::*;
+//! use reactor_rt::assembly::PortKind;
+//! use reactor_rt::SchedulerOptions;
+//!
+//! let received = Arc::new(Mutex::new(None));
+//!
+//! let mut builder = ProgramBuilder::new();
+//! let main = builder.add_main(());
+//! let source = builder.add_child(main, "source", 42u32);
+//! let sink = builder.add_child(main, "sink", received.clone());
+//! let out = builder.new_port::<u32, _>(source, "out", PortKind::Output);
+//! let inp = builder.new_port::<u32, _>(sink, "in", PortKind::Input);
+//! builder.connect(out, inp);
+//!
+//! builder
+//!     .add_reaction(source, move |ctx, state, components| ctx.set(components.port_mut(out), *state))
+//!     .on_startup()
+//!     .effects(out);
+//! builder
+//!     .add_reaction(sink, move |ctx, state, components| {
+//!         *state.lock().unwrap() = ctx.get(components.port(inp));
+//!     })
+//!     .triggered_by(inp);
+//!
+//! builder.run(SchedulerOptions::default());
+//! assert_eq!(*received.lock().unwrap(), Some(42));
+//! ```
+
+pub use crate::scheduler::builder_impl::*;
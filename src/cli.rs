@@ -0,0 +1,543 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! Parsing of the [SchedulerOptions] and of the parameters of
+//! the main reactor from the command line and the environment.
+//! This is enabled by the crate feature `cli`.
+//!
+//! The recognized options are
+//! - `-o`, `--timeout <time>`, or `REACTOR_TIMEOUT`: see [SchedulerOptions::timeout],
+//! - `-w`, `--workers <n>`, or `REACTOR_WORKERS`: see [SchedulerOptions::threads],
+//! - `-k`, `--keepalive [<bool>]`, or `REACTOR_KEEPALIVE`: see [SchedulerOptions::keep_alive],
+//! - `-f`, `--fast [<bool>]`, or `REACTOR_FAST`: see [SchedulerOptions::fast],
+//! - `--dump-graph [<bool>]`, or `REACTOR_DUMP_GRAPH`: see [SchedulerOptions::dump_graph],
+//! - `--log-level <level>`, or `REACTOR_LOG_LEVEL`: the maximum level of
+//!   the log records of the runtime, eg `info` or `trace`,
+//! - `--<param> <value>`, or `REACTOR_PARAM_<PARAM>`: a parameter of the main
+//!   reactor declared with [Cli::param]. In the name of the environment
+//!   variable, the name of the parameter is uppercased and dashes are
+//!   replaced by underscores,
+//! - `-c`, `--config <file>`, or `REACTOR_CONFIG`: a configuration file
+//!   for the options and parameters, with the crate feature `config`
+//!   (see the `config` module).
+//!
+//! Times are written like in LF, eg `20 ms` or `3sec`. Options
+//! given on the command line override environment variables,
+//! which override the configuration file.
+//!
+//! ```no_run
+//! use reactor_rt::cli::Cli;
+//! use std::time::Duration;
+//!
+//! let args = Cli::new("sensors")
+//!     .param::<Duration>("period", "Period of the sensors")
+//!     .param::<u32>("count", "Number of sensors")
+//!     .parse_or_exit();
+//! args.apply_log_level();
+//!
+//! let period = args.param("period", Duration::from_millis(10));
+//! let count = args.param("count", 4u32);
+//! // run the program with args.options
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use log::LevelFilter;
+
+use crate::{try_parse_duration, Duration, SchedulerOptions};
+
+/// Prefix of the environment variables read by [Cli].
+pub const ENV_PREFIX: &str = "REACTOR_";
+
+/// A type of parameter of the main reactor,
+/// which may be parsed from a string.
+pub trait ParamValue: Sized {
+    /// Name of the type in the help message.
+    const TYPE_NAME: &'static str;
+
+    /// Parse a value, or return an error message.
+    fn parse_param(s: &str) -> Result<Self, String>;
+}
+
+impl ParamValue for Duration {
+    const TYPE_NAME: &'static str = "time";
+
+    fn parse_param(s: &str) -> Result<Self, String> {
+        try_parse_duration(s.trim())
+    }
+}
+
+macro_rules! impl_param_value_from_str {
+    ($($t:ty => $name:literal),*$(,)?) => {
+        $(
+        impl ParamValue for $t {
+            const TYPE_NAME: &'static str = $name;
+
+            fn parse_param(s: &str) -> Result<Self, String> {
+                <$t>::from_str(s.trim()).map_err(|e| e.to_string())
+            }
+        }
+        )*
+    };
+}
+
+impl_param_value_from_str! {
+    bool => "bool",
+    char => "char",
+    u8 => "int", u16 => "int", u32 => "int", u64 => "int", usize => "int",
+    i8 => "int", i16 => "int", i32 => "int", i64 => "int", isize => "int",
+    f32 => "float", f64 => "float",
+}
+
+impl ParamValue for String {
+    const TYPE_NAME: &'static str = "string";
+
+    fn parse_param(s: &str) -> Result<Self, String> {
+        Ok(s.to_owned())
+    }
+}
+
+/// A parameter declared with [Cli::param].
+struct ParamDecl {
+    name: &'static str,
+    help: &'static str,
+    type_name: &'static str,
+    check: fn(&str) -> Result<(), String>,
+}
+
+/// Parser for the command line and environment of a reactor
+/// program, see the [module documentation](self).
+pub struct Cli {
+    program: String,
+    defaults: SchedulerOptions,
+    params: Vec<ParamDecl>,
+}
+
+/// The result of parsing with a [Cli].
+pub struct CliArgs {
+    /// The scheduler options, as given by the defaults of
+    /// the [Cli] and overridden by the user.
+    pub options: SchedulerOptions,
+    /// The maximum level of log records, if one was given.
+    pub log_level: Option<LevelFilter>,
+    /// Values of the parameters that were given.
+    params: HashMap<&'static str, String>,
+    /// Types of all the declared parameters.
+    param_types: HashMap<&'static str, &'static str>,
+}
+
+/// Error returned by [Cli::parse_from].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CliError {
+    /// Option `--help` was given. This contains the
+    /// usage message, which should be printed.
+    Help(String),
+    /// The option is not known.
+    UnknownOption(String),
+    /// The option requires a value, which was not given.
+    MissingValue(String),
+    /// The value of the option (or environment variable) is invalid.
+    InvalidValue {
+        /// The option or environment variable.
+        option: String,
+        /// The value that was given.
+        value: String,
+        /// Why the value is invalid.
+        message: String,
+    },
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Help(usage) => write!(f, "{}", usage),
+            CliError::UnknownOption(option) => write!(f, "Unknown option '{}'", option),
+            CliError::MissingValue(option) => write!(f, "Option '{}' requires a value", option),
+            CliError::InvalidValue { option, value, message } => {
+                write!(f, "Invalid value '{}' for '{}': {}", value, option, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// The options of the scheduler.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Opt {
+    Timeout,
+    Workers,
+    KeepAlive,
+    Fast,
+    DumpGraph,
+    LogLevel,
+    #[cfg(feature = "config")]
+    Config,
+}
+
+impl Opt {
+    pub(crate) const ALL: &'static [Opt] = &[
+        Opt::Timeout,
+        Opt::Workers,
+        Opt::KeepAlive,
+        Opt::Fast,
+        Opt::DumpGraph,
+        Opt::LogLevel,
+        #[cfg(feature = "config")]
+        Opt::Config,
+    ];
+
+    /// Long name, short name, value, and help message.
+    fn describe(self) -> (&'static str, Option<char>, &'static str, &'static str) {
+        match self {
+            Opt::Timeout => (
+                "timeout",
+                Some('o'),
+                "<time>",
+                "Stop the program after the given logical time",
+            ),
+            Opt::Workers => ("workers", Some('w'), "<n>", "Number of worker threads, zero for one per core"),
+            Opt::KeepAlive => (
+                "keepalive",
+                Some('k'),
+                "[<bool>]",
+                "Wait for asynchronous events when the event queue is empty",
+            ),
+            Opt::Fast => (
+                "fast",
+                Some('f'),
+                "[<bool>]",
+                "Do not wait for physical time to catch up with logical time",
+            ),
+            Opt::DumpGraph => (
+                "dump-graph",
+                None,
+                "[<bool>]",
+                "Dump the dependency graph to a file before starting",
+            ),
+            Opt::LogLevel => (
+                "log-level",
+                None,
+                "<level>",
+                "Maximum level of log records (off, error, warn, info, debug, trace)",
+            ),
+            #[cfg(feature = "config")]
+            Opt::Config => (
+                "config",
+                Some('c'),
+                "<file>",
+                "Load options and parameters from a TOML or JSON file",
+            ),
+        }
+    }
+
+    /// Returns the option with the given long name.
+    #[cfg(feature = "config")]
+    pub(crate) fn from_name(name: &str) -> Option<Opt> {
+        Self::ALL.iter().copied().find(|opt| opt.describe().0 == name)
+    }
+
+    fn is_flag(self) -> bool {
+        matches!(self, Opt::KeepAlive | Opt::Fast | Opt::DumpGraph)
+    }
+
+    fn env_var(self) -> String {
+        let (long, ..) = self.describe();
+        format!("{}{}", ENV_PREFIX, long.to_uppercase().replace('-', "_"))
+    }
+
+    fn from_arg(arg: &str) -> Option<Opt> {
+        Self::ALL.iter().copied().find(|opt| {
+            let (long, short, ..) = opt.describe();
+            arg.strip_prefix("--") == Some(long) || short.map_or(false, |c| arg == format!("-{}", c))
+        })
+    }
+}
+
+impl Cli {
+    /// Create a parser for the given program. The program name
+    /// is only used in the usage message.
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            defaults: SchedulerOptions::default(),
+            params: Vec::new(),
+        }
+    }
+
+    /// Set the options that are used when they are not
+    /// overridden by the user. By default, this is
+    /// [SchedulerOptions::default].
+    pub fn defaults(mut self, options: SchedulerOptions) -> Self {
+        self.defaults = options;
+        self
+    }
+
+    /// Declare a parameter of the main reactor. Its value is
+    /// checked to have type `T` when parsing, and may then
+    /// be retrieved with [CliArgs::param].
+    ///
+    /// # Panics
+    ///
+    /// If the name is already used by another parameter or option.
+    pub fn param<T: ParamValue>(mut self, name: &'static str, help: &'static str) -> Self {
+        assert!(
+            Opt::from_arg(&format!("--{}", name)).is_none() && name != "help" && self.param_decl(name).is_none(),
+            "Parameter name '{}' is already in use",
+            name
+        );
+        self.params.push(ParamDecl {
+            name,
+            help,
+            type_name: T::TYPE_NAME,
+            check: |s| T::parse_param(s).map(drop),
+        });
+        self
+    }
+
+    fn param_decl(&self, name: &str) -> Option<&ParamDecl> {
+        self.params.iter().find(|p| p.name == name)
+    }
+
+    fn param_env_var(name: &str) -> String {
+        format!("{}PARAM_{}", ENV_PREFIX, name.to_uppercase().replace('-', "_"))
+    }
+
+    /// Returns the usage message, printed for `--help`.
+    pub fn usage(&self) -> String {
+        fn line(option: String, help: &str, env: String) -> String {
+            format!("  {:<28} {} [env: {}]", option, help, env)
+        }
+
+        let mut lines = vec![
+            format!("Usage: {} [OPTIONS]", self.program),
+            String::new(),
+            "Options:".to_owned(),
+        ];
+        for &opt in Opt::ALL {
+            let (long, short, value, help) = opt.describe();
+            let short = short.map_or("    ".to_owned(), |c| format!("-{}, ", c));
+            lines.push(line(format!("{}--{} {}", short, long, value), help, opt.env_var()));
+        }
+        lines.push(format!("  {:<28} Print this message", "-h, --help"));
+        if !self.params.is_empty() {
+            lines.push(String::new());
+            lines.push("Parameters of the main reactor:".to_owned());
+        }
+        for param in &self.params {
+            let option = format!("    --{} <{}>", param.name, param.type_name);
+            lines.push(line(option, param.help, Self::param_env_var(param.name)));
+        }
+        lines.join("\n")
+    }
+
+    /// Parse the arguments of the process (without the program
+    /// name) and the environment. On error, prints the message
+    /// and exits the process, with status zero for `--help`.
+    pub fn parse_or_exit(self) -> CliArgs {
+        let usage = self.usage();
+        match self.parse_from(std::env::args().skip(1), std::env::vars()) {
+            Ok(args) => args,
+            Err(CliError::Help(usage)) => {
+                println!("{}", usage);
+                std::process::exit(0)
+            }
+            Err(e) => {
+                eprintln!("{}\n\n{}", e, usage);
+                std::process::exit(2)
+            }
+        }
+    }
+
+    /// Parse the given arguments, which do not include the
+    /// program name, and the given environment variables.
+    /// Variables that don't start with [ENV_PREFIX] are ignored.
+    pub fn parse_from(
+        mut self,
+        args: impl IntoIterator<Item = String>,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<CliArgs, CliError> {
+        let defaults = std::mem::take(&mut self.defaults);
+        // Values are collected before being applied, as
+        // those of a config file come first.
+        let mut assignments = Vec::new();
+
+        // environment variables, in a stable order
+        let mut env: Vec<(String, String)> = env.into_iter().filter(|(k, _)| k.starts_with(ENV_PREFIX)).collect();
+        env.sort();
+        for (var, value) in env {
+            if let Some(opt) = Opt::ALL.iter().copied().find(|opt| opt.env_var() == var) {
+                assignments.push(Assignment::Option(opt, var, value));
+            } else if let Some(param) = self.params.iter().find(|p| Self::param_env_var(p.name) == var) {
+                assignments.push(Assignment::Param(param, var, value));
+            } else {
+                warn!("Ignoring unknown environment variable {}", var);
+            }
+        }
+
+        let mut args = args.into_iter().peekable();
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                return Err(CliError::Help(self.usage()));
+            }
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name.to_owned(), Some(value.to_owned())),
+                _ => (arg.clone(), None),
+            };
+
+            if let Some(opt) = Opt::from_arg(&name) {
+                let value = match inline_value {
+                    Some(value) => value,
+                    // flags may be followed by a boolean
+                    None if opt.is_flag() => match args.peek().map(String::as_str) {
+                        Some("true") | Some("false") => args.next().unwrap(),
+                        _ => "true".to_owned(),
+                    },
+                    None => args.next().ok_or_else(|| CliError::MissingValue(name.clone()))?,
+                };
+                assignments.push(Assignment::Option(opt, name, value));
+            } else if let Some(param) = name.strip_prefix("--").and_then(|n| self.param_decl(n)) {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => args.next().ok_or_else(|| CliError::MissingValue(name.clone()))?,
+                };
+                assignments.push(Assignment::Param(param, name, value));
+            } else {
+                return Err(CliError::UnknownOption(arg));
+            }
+        }
+
+        let mut parsed = CliArgs {
+            options: defaults,
+            log_level: None,
+            params: HashMap::new(),
+            param_types: self.params.iter().map(|p| (p.name, p.type_name)).collect(),
+        };
+
+        #[cfg(feature = "config")]
+        if let Some((name, path)) = assignments.iter().rev().find_map(|a| match a {
+            Assignment::Option(Opt::Config, name, path) => Some((name, path)),
+            _ => None,
+        }) {
+            let invalid = |message: String| CliError::InvalidValue { option: name.clone(), value: path.clone(), message };
+            let config = crate::config::Config::load(path).map_err(|e| invalid(e.to_string()))?;
+            config.apply_options(&mut parsed.options);
+            parsed.log_level = config.log_level();
+            for param in &self.params {
+                if let Some(value) = config.raw_param(param.name) {
+                    let key = format!("params.{}", param.name);
+                    self.set_param(&mut parsed, param, &key, value.clone())
+                        .map_err(|e| invalid(e.to_string()))?;
+                }
+            }
+            parsed.options.config = std::sync::Arc::new(config);
+        }
+
+        for assignment in assignments {
+            match assignment {
+                Assignment::Option(opt, name, value) => {
+                    apply_option(&mut parsed.options, &mut parsed.log_level, opt, &value)
+                        .map_err(|message| CliError::InvalidValue { option: name, value, message })?;
+                }
+                Assignment::Param(param, name, value) => self.set_param(&mut parsed, param, &name, value)?,
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn set_param(&self, parsed: &mut CliArgs, param: &ParamDecl, name: &str, value: String) -> Result<(), CliError> {
+        match (param.check)(&value) {
+            Ok(()) => {
+                parsed.params.insert(param.name, value);
+                Ok(())
+            }
+            Err(message) => Err(CliError::InvalidValue { option: name.to_owned(), value, message }),
+        }
+    }
+}
+
+/// A value given for an option or parameter, with the
+/// name of the option or variable it was given with.
+enum Assignment<'a> {
+    Option(Opt, String, String),
+    Param(&'a ParamDecl, String, String),
+}
+
+/// Set the option to the given value, or return an error
+/// message if the value is invalid.
+pub(crate) fn apply_option(
+    options: &mut SchedulerOptions,
+    log_level: &mut Option<LevelFilter>,
+    opt: Opt,
+    value: &str,
+) -> Result<(), String> {
+    match opt {
+        Opt::Timeout => options.timeout = Some(Duration::parse_param(value)?),
+        Opt::Workers => options.threads = usize::parse_param(value)?,
+        Opt::KeepAlive => options.keep_alive = bool::parse_param(value)?,
+        Opt::Fast => options.fast = bool::parse_param(value)?,
+        Opt::DumpGraph => options.dump_graph = bool::parse_param(value)?,
+        Opt::LogLevel => *log_level = Some(LevelFilter::from_str(value.trim()).map_err(|e| e.to_string())?),
+        // loaded before the other options
+        #[cfg(feature = "config")]
+        Opt::Config => {}
+    }
+    Ok(())
+}
+
+impl CliArgs {
+    /// Returns the value of a parameter declared with
+    /// [Cli::param], or the default if it was not given.
+    ///
+    /// # Panics
+    ///
+    /// If the parameter was not declared, or was declared
+    /// with another type.
+    pub fn param<T: ParamValue>(&self, name: &str, default: T) -> T {
+        let type_name = self
+            .param_types
+            .get(name)
+            .unwrap_or_else(|| panic!("Undeclared parameter '{}'", name));
+        assert_eq!(
+            *type_name,
+            T::TYPE_NAME,
+            "Parameter '{}' was declared with another type",
+            name
+        );
+        match self.params.get(name) {
+            Some(value) => T::parse_param(value).expect("value was checked while parsing"),
+            None => default,
+        }
+    }
+
+    /// Set the maximum level of the log records of the
+    /// program, if a log level was given.
+    pub fn apply_log_level(&self) {
+        if let Some(level) = self.log_level {
+            log::set_max_level(level);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! Encoding of the values of ports and actions to bytes.
+//!
+//! A [ValueCodec] may be registered for a port or action when
+//! the program is assembled, with
+//! [DependencyDeclarator::register_codec](crate::assembly::DependencyDeclarator::register_codec)
+//! or [ProgramBuilder::register_codec](crate::builder::ProgramBuilder::register_codec).
+//! Reactions may then encode and decode the values of the
+//! component with [ReactionCtx::encode] and [ReactionCtx::decode],
+//! or look up the codec generically by the [TriggerId] of the
+//! component with [ReactionCtx::codec]. This lets tools like
+//! tracing, recording or transports handle values of any type.
+//!
+//! With the crate feature `serde-codec`, `JsonCodec` encodes
+//! values with serde, in JSON.
+
+use std::any::{type_name, Any, TypeId};
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+#[cfg(feature = "serde-codec")]
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(doc)]
+use crate::{assembly::TriggerId, ReactionCtx};
+
+/// Encodes values of type `T` to bytes, and decodes them back.
+pub trait ValueCodec<T>: Send + Sync {
+    /// Encode the value.
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError>;
+
+    /// Decode a value encoded by [Self::encode].
+    fn decode(&self, bytes: &[u8]) -> Result<T, CodecError>;
+}
+
+/// A [ValueCodec] for an unknown type of values, as registered
+/// for a component. See [ReactionCtx::codec].
+pub trait DynCodec: Send + Sync {
+    /// Name of the type of values.
+    fn value_type(&self) -> &'static str;
+
+    /// ID of the type of values.
+    fn value_type_id(&self) -> TypeId;
+
+    /// Encode the value, which must have the type of the codec.
+    fn encode_any(&self, value: &dyn Any) -> Result<Vec<u8>, CodecError>;
+
+    /// Decode a value, which has the type of the codec.
+    fn decode_any(&self, bytes: &[u8]) -> Result<Box<dyn Any + Send>, CodecError>;
+}
+
+/// Error while encoding or decoding a value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CodecError {
+    /// No codec was registered for the component.
+    Unregistered,
+    /// The codec registered for the component is for
+    /// another type of values.
+    TypeMismatch {
+        /// Type of the values of the codec.
+        expected: &'static str,
+        /// Type of the value that was given or requested.
+        found: &'static str,
+    },
+    /// The codec failed, eg because the bytes are invalid.
+    Failed(String),
+}
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodecError::Unregistered => write!(f, "No codec is registered for this component"),
+            CodecError::TypeMismatch { expected, found } => {
+                write!(f, "Codec is for values of type {}, not {}", expected, found)
+            }
+            CodecError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+/// Erases the type of values of a codec.
+pub(crate) fn erase<T: Send + 'static>(codec: impl ValueCodec<T> + 'static) -> Arc<dyn DynCodec> {
+    Arc::new(Erased(codec, PhantomData))
+}
+
+struct Erased<C, T>(C, PhantomData<fn(T) -> T>);
+
+impl<C: ValueCodec<T>, T: Send + 'static> DynCodec for Erased<C, T> {
+    fn value_type(&self) -> &'static str {
+        type_name::<T>()
+    }
+
+    fn value_type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn encode_any(&self, value: &dyn Any) -> Result<Vec<u8>, CodecError> {
+        match value.downcast_ref::<T>() {
+            Some(value) => self.0.encode(value),
+            None => Err(CodecError::TypeMismatch { expected: type_name::<T>(), found: "another type" }),
+        }
+    }
+
+    fn decode_any(&self, bytes: &[u8]) -> Result<Box<dyn Any + Send>, CodecError> {
+        Ok(Box::new(self.0.decode(bytes)?))
+    }
+}
+
+/// Checks that the codec is for values of type `T`.
+pub(crate) fn check_type<T: 'static>(codec: &dyn DynCodec) -> Result<(), CodecError> {
+    if codec.value_type_id() == TypeId::of::<T>() {
+        Ok(())
+    } else {
+        Err(CodecError::TypeMismatch {
+            expected: codec.value_type(),
+            found: type_name::<T>(),
+        })
+    }
+}
+
+/// A codec that encodes values in JSON with serde.
+/// This is enabled by the crate feature `serde-codec`.
+#[cfg(feature = "serde-codec")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct JsonCodec;
+
+#[cfg(feature = "serde-codec")]
+impl<T: Serialize + DeserializeOwned> ValueCodec<T> for JsonCodec {
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        serde_json::to_vec(value).map_err(|e| CodecError::Failed(e.to_string()))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        serde_json::from_slice(bytes).map_err(|e| CodecError::Failed(e.to_string()))
+    }
+}
@@ -0,0 +1,294 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! Loading of the [SchedulerOptions] and of the parameters of
+//! reactors from a configuration file. This is enabled by the
+//! crate feature `config`, which also enables the [cli](crate::cli)
+//! module. Programs using it accept a configuration file with
+//! option `--config <file>` or variable `REACTOR_CONFIG`.
+//!
+//! The file is written in TOML (with extension `.toml`) or JSON
+//! (with extension `.json`). Table `scheduler` sets the options
+//! of the scheduler, named like the command-line options. Table
+//! `params` sets the parameters of reactors, by their path:
+//!
+//! ```toml
+//! [scheduler]
+//! timeout = "5 sec"
+//! workers = 4
+//! log-level = "info"
+//!
+//! [params]
+//! # parameter of the main reactor
+//! period = "10 ms"
+//! # parameter of the fourth reactor of bank `sensors`,
+//! # which is contained in the main reactor
+//! "main.sensors[3].period" = "20 ms"
+//! ```
+//!
+//! Paths are made of the names of the reactor instances, like
+//! in the debug information of the runtime, separated by dots.
+//! They start at the main reactor, which is named `main`, and
+//! may omit it. Nested tables are flattened to paths, so the
+//! last parameter above may also be set with a table
+//! `[params.main."sensors[3]"]`.
+//!
+//! Generated reactors read their parameters with
+//! [AssemblyCtx::param](crate::assembly::AssemblyCtx::param).
+//! Programs built with a [ProgramBuilder](crate::builder::ProgramBuilder)
+//! may use [Config::param] with [ProgramBuilder::path](crate::builder::ProgramBuilder::path).
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use log::LevelFilter;
+
+use crate::cli::{apply_option, Opt, ParamValue};
+use crate::SchedulerOptions;
+
+/// Scheduler options and reactor parameters loaded from
+/// a configuration file, see the [module documentation](self).
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    /// Options of the scheduler, which have been checked.
+    options: Vec<(Opt, String)>,
+    log_level: Option<LevelFilter>,
+    /// Values of parameters by their full path.
+    params: HashMap<String, String>,
+}
+
+/// Error returned when loading a [Config].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConfigError {
+    /// The file could not be read.
+    Io(String),
+    /// The extension of the file is neither `.toml` nor `.json`.
+    UnknownFormat(String),
+    /// The file is not valid TOML or JSON.
+    Syntax(String),
+    /// An entry of the file is invalid.
+    InvalidEntry {
+        /// The key of the entry, eg `scheduler.timeout`.
+        key: String,
+        /// Why the entry is invalid.
+        message: String,
+    },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(message) => write!(f, "Cannot read configuration: {}", message),
+            ConfigError::UnknownFormat(path) => {
+                write!(
+                    f,
+                    "Unknown configuration format for '{}', expected a .toml or .json file",
+                    path
+                )
+            }
+            ConfigError::Syntax(message) => write!(f, "Invalid configuration: {}", message),
+            ConfigError::InvalidEntry { key, message } => write!(f, "Invalid configuration entry '{}': {}", key, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A value of the file, converted to the representation
+/// of TOML and JSON alike.
+enum Value {
+    Scalar(String),
+    Table(Vec<(String, Value)>),
+    Unsupported(&'static str),
+}
+
+impl Config {
+    /// Load the file at the given path, whose format is
+    /// given by its extension.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let read = || std::fs::read_to_string(path).map_err(|e| ConfigError::Io(format!("{}: {}", path.display(), e)));
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml_str(&read()?),
+            Some("json") => Self::from_json_str(&read()?),
+            _ => Err(ConfigError::UnknownFormat(path.display().to_string())),
+        }
+    }
+
+    /// Parse a configuration written in TOML.
+    pub fn from_toml_str(text: &str) -> Result<Self, ConfigError> {
+        fn convert(value: toml::Value) -> Value {
+            match value {
+                toml::Value::String(s) => Value::Scalar(s),
+                toml::Value::Integer(i) => Value::Scalar(i.to_string()),
+                toml::Value::Float(f) => Value::Scalar(f.to_string()),
+                toml::Value::Boolean(b) => Value::Scalar(b.to_string()),
+                toml::Value::Table(t) => Value::Table(t.into_iter().map(|(k, v)| (k, convert(v))).collect()),
+                toml::Value::Array(_) => Value::Unsupported("array"),
+                toml::Value::Datetime(_) => Value::Unsupported("datetime"),
+            }
+        }
+
+        let value = text.parse::<toml::Value>().map_err(|e| ConfigError::Syntax(e.to_string()))?;
+        Self::from_value(convert(value))
+    }
+
+    /// Parse a configuration written in JSON.
+    pub fn from_json_str(text: &str) -> Result<Self, ConfigError> {
+        fn convert(value: serde_json::Value) -> Value {
+            match value {
+                serde_json::Value::String(s) => Value::Scalar(s),
+                serde_json::Value::Number(n) => Value::Scalar(n.to_string()),
+                serde_json::Value::Bool(b) => Value::Scalar(b.to_string()),
+                serde_json::Value::Object(o) => Value::Table(o.into_iter().map(|(k, v)| (k, convert(v))).collect()),
+                serde_json::Value::Array(_) => Value::Unsupported("array"),
+                serde_json::Value::Null => Value::Unsupported("null"),
+            }
+        }
+
+        let value = serde_json::from_str(text).map_err(|e| ConfigError::Syntax(e.to_string()))?;
+        Self::from_value(convert(value))
+    }
+
+    fn from_value(value: Value) -> Result<Self, ConfigError> {
+        let entries = match value {
+            Value::Table(entries) => entries,
+            _ => return Err(ConfigError::Syntax("expected a table".into())),
+        };
+        let mut config = Config::default();
+        for (key, value) in entries {
+            match key.as_str() {
+                "scheduler" => config.read_options(value)?,
+                "params" => {
+                    let mut params = HashMap::new();
+                    flatten(String::new(), value, &mut params)?;
+                    config.params = params.into_iter().map(|(path, v)| (normalize(&path), v)).collect();
+                }
+                _ => {
+                    return Err(ConfigError::InvalidEntry {
+                        key,
+                        message: "expected 'scheduler' or 'params'".into(),
+                    })
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    fn read_options(&mut self, value: Value) -> Result<(), ConfigError> {
+        let mut options = HashMap::new();
+        flatten("scheduler".into(), value, &mut options)?;
+        let mut scratch = SchedulerOptions::default();
+        for (key, value) in options {
+            let invalid = |message: String| ConfigError::InvalidEntry { key: key.clone(), message };
+            // the file cannot include another one
+            let opt = Opt::from_name(&key["scheduler.".len()..])
+                .filter(|opt| *opt != Opt::Config)
+                .ok_or_else(|| invalid("unknown option".into()))?;
+            apply_option(&mut scratch, &mut self.log_level, opt, &value).map_err(invalid)?;
+            if opt != Opt::LogLevel {
+                self.options.push((opt, value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the options given by the file.
+    pub fn apply_options(&self, options: &mut SchedulerOptions) {
+        for (opt, value) in &self.options {
+            apply_option(options, &mut None, *opt, value).expect("option was checked while loading");
+        }
+    }
+
+    /// Returns the maximum level of log records given by the file.
+    pub fn log_level(&self) -> Option<LevelFilter> {
+        self.log_level
+    }
+
+    /// Returns the value of the parameter at the given path,
+    /// eg `main.sensors[3].period`, if the file sets it.
+    pub fn param<T: ParamValue>(&self, path: &str) -> Result<Option<T>, ConfigError> {
+        let path = normalize(path);
+        match self.params.get(&path) {
+            Some(value) => T::parse_param(value)
+                .map(Some)
+                .map_err(|message| ConfigError::InvalidEntry { key: format!("params.{}", path), message }),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the unparsed value of the parameter.
+    pub(crate) fn raw_param(&self, path: &str) -> Option<&String> {
+        self.params.get(&normalize(path))
+    }
+}
+
+/// Add the scalars of the value to the map, by their
+/// dotted path prefixed by the given one.
+fn flatten(prefix: String, value: Value, out: &mut HashMap<String, String>) -> Result<(), ConfigError> {
+    match value {
+        Value::Scalar(s) => {
+            out.insert(prefix, s);
+        }
+        Value::Table(entries) => {
+            for (key, value) in entries {
+                let path = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(path, value, out)?;
+            }
+        }
+        Value::Unsupported(kind) => {
+            return Err(ConfigError::InvalidEntry {
+                key: prefix,
+                message: format!("unsupported value of type {}", kind),
+            })
+        }
+    }
+    Ok(())
+}
+
+/// Prefixes the path with the name of the main reactor
+/// if it doesn't start with it.
+fn normalize(path: &str) -> String {
+    if path == "main" || path.starts_with("main.") {
+        path.to_owned()
+    } else {
+        format!("main.{}", path)
+    }
+}
+
+/// Returns the path of a reactor in the configuration,
+/// given its path in the debug info (eg `/sensors[3]/`).
+pub(crate) fn reactor_path(inst_path: &str) -> String {
+    let mut path = String::from("main");
+    for segment in inst_path.split('/').filter(|s| !s.is_empty()) {
+        path.push('.');
+        path.push_str(segment);
+    }
+    path
+}
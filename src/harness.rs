@@ -0,0 +1,38 @@
+//! Module containing a harness to test a single reactor
+//! in isolation.
+//!
+//! A [TestHarness] wraps the reactor and binds its ports
+//! to synthetic upstream and downstream ports. Tests then
+//! feed input values at chosen tags, process tags in
+//! virtual time (without waiting for physical time), and
+//! assert on the recorded output values and on the tags
+//! at which actions are scheduled. For instance, with a
+//! reactor `DoublerAdapter` that doubles its input:
+//!
+//! ```no_run
+//! # use reactor_rt::prelude::*;
+//! # use reactor_rt::assembly::*;
+//! # use reactor_rt::harness::*;
+//! # use reactor_rt::{tag, CleanupCtx, LocalReactionId, ReactorBehavior, ReactorId};
+//! # struct DoublerAdapter { __inp: Port<u32>, __out: Port<u32> }
+//! # impl ReactorInitializer for DoublerAdapter {
+//! #     type Wrapped = (); type Params = (); const MAX_REACTION_ID: LocalReactionId = LocalReactionId::new(1);
+//! #     fn assemble(_: (), _: AssemblyCtx<Self>) -> AssemblyResult<FinishedReactor<Self>> { unimplemented!() }
+//! # }
+//! # impl ReactorBehavior for DoublerAdapter {
+//! #     fn id(&self) -> ReactorId { unimplemented!() }
+//! #     fn react(&mut self, _: &mut ReactionCtx, _: LocalReactionId) {}
+//! #     fn cleanup_tag(&mut self, _: &CleanupCtx) {}
+//! # }
+//! let mut harness = TestHarness::<DoublerAdapter>::new(());
+//! let inp = harness.input(|r| &mut r.__inp);
+//! let out = harness.output(|r| &mut r.__out);
+//!
+//! harness.run(|sim| {
+//!     sim.feed(&inp, tag!(T0 + 10 ms), 4);
+//!     sim.advance_to(tag!(T0 + 1 sec));
+//!     assert_eq!(sim.outputs(&out), vec![(tag!(T0 + 10 ms), 8)]);
+//! });
+//! ```
+
+pub use crate::scheduler::harness_impl::*;
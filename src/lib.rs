@@ -28,7 +28,10 @@
 //! Most of this crate consists of types that are manipulated
 //! only by the generated code. User-written Rust code mostly
 //! interacts with the type [ReactionCtx], which is the entry
-//! point for user documentation.
+//! point for user documentation. Programs may also be built
+//! without the code generator, using the [builder] module,
+//! and single reactors tested in isolation with the [harness]
+//! module.
 //!
 //! Crate-level features include:
 //! - `parallel-runtime`: use Rayon to execute reactions in parallel
@@ -50,6 +53,24 @@
 //!   This is a default feature.
 //! - `no-unsafe`: disable optimisations that use unsafe code in this runtime.
 //!   Just provided for comparison, should probably be removed (unsafe code is fine).
+//! - `verify-dependencies`: check at runtime that reactions only read the
+//!   components they declared as triggers or uses, only set the ports they
+//!   declared as effects, and only schedule the actions they declared
+//!   as effects. Undeclared accesses panic, instead of silently breaking
+//!   the determinism of the program. This has a runtime cost.
+//! - `macros`: re-export the `#[reactor]` and `#[reaction]` attribute
+//!   macros of the `reactor_rt_macros` crate, which generate the
+//!   code LFC would for a reactor written directly in Rust.
+//! - `cli`: enable the `cli` module, which parses the scheduler
+//!   options and the parameters of the main reactor from the
+//!   command line and the environment.
+//! - `config`: enable the `config` module, which loads the scheduler
+//!   options and the parameters of reactors from a TOML or JSON
+//!   file. This implies `cli`.
+//! - `serde-codec`: enable `codec::JsonCodec`, which encodes the
+//!   values of ports and actions with serde.
+//! - `signals`: let programs shut down gracefully on SIGINT and
+//!   SIGTERM, see [SchedulerOptions::handle_signals].
 
 // #![deny(unused_crate_dependencies)]
 #![deny(unused_extern_crates)]
@@ -85,8 +106,11 @@ pub use self::ports::*;
 pub use self::scheduler::*;
 pub use self::time::*;
 pub use self::timers::*;
+pub use self::token::*;
 pub use self::triggers::ReactionTrigger;
 pub use self::util::*;
+#[cfg(feature = "macros")]
+pub use reactor_rt_macros::{reaction, reactor};
 
 #[cfg(test)]
 pub mod test;
@@ -97,10 +121,18 @@ mod ports;
 mod scheduler;
 mod time;
 mod timers;
+mod token;
 mod triggers;
 mod util;
 
 pub mod assembly;
+pub mod builder;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod codec;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod harness;
 
 /// The prelude that is imported at the top of reactor files
 /// generated by LFC.
@@ -108,7 +140,7 @@ pub mod prelude {
     pub use crate::Offset::*;
     pub use crate::{
         after, assert_tag_is, delay, tag, AsyncCtx, Duration, EventTag, Instant, LogicalAction, Multiport, PhysicalActionRef,
-        Port, ReactionCtx, Timer,
+        Port, ReactionCtx, Timer, Token,
     };
 
     /// Alias for the unit type, so that it can be written without quotes in LF.
@@ -142,6 +174,14 @@ pub trait ReactorBehavior {
     /// Acknowledge that the given tag is done executing and
     /// free resources if need be.
     fn cleanup_tag(&mut self, ctx: &CleanupCtx);
+
+    /// Returns this reactor as [Any](std::any::Any) if it may
+    /// be mutated at runtime, which is only the case of the
+    /// reactors of a [builder::ProgramBuilder].
+    #[doc(hidden)]
+    fn as_mutable(&mut self) -> Option<&mut dyn std::any::Any> {
+        None
+    }
 }
 assert_obj_safe!(ReactorBehavior);
 
@@ -31,9 +31,11 @@ use std::fmt::Debug;
 use std::ops::Deref;
 use std::ops::{DerefMut, Index, IndexMut};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use std::time::Instant;
 
-use atomic_refcell::AtomicRefCell;
+use atomic_refcell::{AtomicRef, AtomicRefCell};
 use AssemblyErrorImpl::{CannotBind, CyclicDependency};
 
 use crate::assembly::{AssemblyError, AssemblyErrorImpl, PortId, PortKind, TriggerId, TriggerLike};
@@ -52,9 +54,9 @@ use crate::{EventTag, ReactionTrigger};
 /// upstream port binding.
 ///
 /// Those structural constraints are trusted to have been
-/// verified by the code generator. If necessary we may be
-/// able to add conditional compilation flags that enable
-/// runtime checks.
+/// verified by the code generator. The `verify-dependencies`
+/// feature checks at runtime that reactions only read and set
+/// the ports they declared a dependency on.
 ///
 pub struct Port<T: Sync> {
     id: TriggerId,
@@ -121,10 +123,28 @@ impl<T: Sync> Port<T> {
                 f(cell_borrow.deref())
             }
 
-            /// Set the value, see [super::ReactionCtx::set]
-            /// Note: we use a closure to process the dependencies to
-            /// avoid having to clone the dependency list just to return it.
-            pub(crate) fn set_impl(&mut self, new_value: Option<T>) {
+            /// Applies the closure to the value, which may be
+            /// taken or mutated. This bypasses the bind status
+            /// of the port, see [super::ReactionCtx::take].
+            /// Multiports are notified if the value is taken.
+            pub(crate) fn use_mut<R>(&mut self, f: impl FnOnce(&mut Option<T>) -> R) -> R {
+                use atomic_refcell::AtomicRef;
+                let cell_ref: AtomicRef<Rc<PortCell<T>>> = AtomicRefCell::borrow(&self.upstream_binding);
+                let class_cell: &PortCell<T> = Rc::borrow(cell_ref.deref());
+                let mut value = class_cell.value.borrow_mut();
+
+                let was_present = value.is_some();
+                let result = f(value.deref_mut());
+                if was_present && value.is_none() {
+                    class_cell.notify_presence(false);
+                }
+                result
+            }
+
+            /// Write the value. If `notify` is true, multiports
+            /// that contain a port bound to this one are notified
+            /// that the channel is present or absent.
+            fn write_value(&mut self, new_value: Option<T>, notify: bool) {
                 use atomic_refcell::AtomicRef;
 
                 debug_assert_ne!(self.bind_status, BindStatus::Bound, "Cannot set a bound port ({:?})", self.id);
@@ -132,6 +152,9 @@ impl<T: Sync> Port<T> {
                 let cell_ref: AtomicRef<Rc<PortCell<T>>> = AtomicRefCell::borrow(&self.upstream_binding);
                 let class_cell: &PortCell<T> = Rc::borrow(cell_ref.deref());
 
+                if notify {
+                    class_cell.notify_presence(new_value.is_some());
+                }
                 *class_cell.value.borrow_mut() = new_value;
             }
 
@@ -160,14 +183,50 @@ impl<T: Sync> Port<T> {
                 f(opt)
             }
 
+             /// Returns a mutable reference to the value. This bypasses
+             /// the bind status of the port, see [super::ReactionCtx::take].
+             /// Multiports are not notified if the value is taken
+             /// through the reference, see [Self::use_mut].
+             pub(crate) fn get_mut(&mut self) -> &mut Option<T> {
+                 let binding: &UnsafeCell<Rc<PortCell<T>>> = Rc::borrow(&self.upstream_binding);
+                 unsafe {
+                     let cell = &*binding.get();
+                     &mut *cell.value.get()
+                 }
+             }
+
+             /// Applies the closure to the value, which may be
+             /// taken or mutated. Multiports are notified if the
+             /// value is taken.
              #[inline]
-             pub(crate) fn set_impl(&mut self, new_value: Option<T>) {
+             pub(crate) fn use_mut<R>(&mut self, f: impl FnOnce(&mut Option<T>) -> R) -> R {
+                 let binding: &UnsafeCell<Rc<PortCell<T>>> = Rc::borrow(&self.upstream_binding);
+                 unsafe {
+                     let cell: &Rc<PortCell<T>> = &*binding.get();
+                     let value = &mut *cell.value.get();
+                     let was_present = value.is_some();
+                     let result = f(value);
+                     if was_present && value.is_none() {
+                         cell.notify_presence(false);
+                     }
+                     result
+                 }
+             }
+
+             /// Write the value. If `notify` is true, multiports
+             /// that contain a port bound to this one are notified
+             /// that the channel is present or absent.
+             #[inline]
+             fn write_value(&mut self, new_value: Option<T>, notify: bool) {
                 debug_assert_ne!(self.bind_status, BindStatus::Bound, "Cannot set a bound port");
 
                 let binding: &UnsafeCell<Rc<PortCell<T>>> = Rc::borrow(&self.upstream_binding);
 
                 unsafe {
                     let cell: &Rc<PortCell<T>> = &*binding.get();
+                    if notify {
+                        cell.notify_presence(new_value.is_some());
+                    }
                     // note: using write instead of replace would not drop the old value
                     cell.value.get().replace(new_value);
                 }
@@ -175,16 +234,38 @@ impl<T: Sync> Port<T> {
         }
     }
 
+    /// Set the value, see [super::ReactionCtx::set]
+    #[inline]
+    pub(crate) fn set_impl(&mut self, new_value: Option<T>) {
+        self.write_value(new_value, true)
+    }
+
     /// Called at the end of a tag.
     #[inline]
     pub(crate) fn clear_value(&mut self) {
         // If this port is bound, then some other port has
         // a reference to the same cell but is not bound.
+        // Multiports reset their presence set themselves.
         if self.bind_status != BindStatus::Bound {
-            self.set_impl(None)
+            self.write_value(None, false)
         }
     }
 
+    /// Record that this port is the given channel of a multiport,
+    /// so that the multiport is notified when the value is set.
+    fn add_presence_hook(&mut self, presence: &Rc<ChannelPresence>, channel: usize) {
+        let class = {
+            cfg_if! {
+                if #[cfg(feature = "no-unsafe")] {
+                    AtomicRefCell::borrow(&self.upstream_binding)
+                } else {
+                    unsafe { self.upstream_binding.get().as_ref().unwrap() }
+                }
+            }
+        };
+        class.update_presence_hooks(|hooks| hooks.push((self.id, Rc::clone(presence), channel)));
+    }
+
     pub(crate) fn forward_to(&mut self, downstream: &mut Port<T>) -> Result<(), AssemblyError> {
         let mut mut_downstream_cell = {
             cfg_if! {
@@ -217,14 +298,80 @@ impl<T: Sync> Port<T> {
             .borrow_mut()
             .insert(downstream.id, Rc::clone(&downstream.upstream_binding));
 
-        let new_binding = Rc::clone(&my_class);
+        let new_binding = Rc::clone(&*my_class);
 
         mut_downstream_cell.check_cycle(&self.id, &downstream.id)?;
 
-        mut_downstream_cell.set_upstream(&my_class);
+        // the multiports that contain ports of the downstream
+        // class now need to be notified when our value is set
+        mut_downstream_cell
+            .update_presence_hooks(|downstream_hooks| my_class.update_presence_hooks(|hooks| hooks.append(downstream_hooks)));
+        mut_downstream_cell.set_upstream(&*my_class);
+        // the ports bound to the downstream are now transitively
+        // bound to us, and follow our equiv class from now on
+        my_class
+            .downstreams
+            .borrow_mut()
+            .extend(mut_downstream_cell.downstreams.borrow_mut().drain());
         *mut_downstream_cell.deref_mut() = new_binding;
         Ok(())
     }
+
+    /// Unbind this port from its upstream port, which was
+    /// removed from the program by a mutation. The given ports,
+    /// which are bound to this one directly or transitively,
+    /// stay bound to it. This port may then be set or bound again.
+    pub(crate) fn unbind(&mut self, downstream: &[PortId]) {
+        if self.bind_status != BindStatus::Bound {
+            return;
+        }
+        let old_class = self.class();
+        let new_class: Rc<PortCell<T>> = Default::default();
+        {
+            let mut old_downstreams = old_class.downstreams.borrow_mut();
+            let mut new_downstreams = new_class.downstreams.borrow_mut();
+            old_downstreams.remove(&self.id);
+            for id in downstream {
+                if let Some(cell) = old_downstreams.remove(id) {
+                    PortCell::repoint(&cell, &new_class);
+                    new_downstreams.insert(*id, cell);
+                }
+            }
+        }
+        old_class.update_presence_hooks(|old_hooks| {
+            let (moved, kept) = old_hooks
+                .drain(..)
+                .partition(|(id, ..)| *id == self.id || downstream.contains(id));
+            *old_hooks = kept;
+            new_class.update_presence_hooks(|new_hooks| *new_hooks = moved);
+        });
+        PortCell::repoint(&self.upstream_binding, &new_class);
+        self.bind_status = BindStatus::Free;
+    }
+
+    /// Forget the given ports, which were bound to this one
+    /// directly or transitively, and were removed from the
+    /// program by a mutation.
+    pub(crate) fn forget_downstreams(&mut self, removed: &[PortId]) {
+        let class = self.class();
+        let mut downstreams = class.downstreams.borrow_mut();
+        for id in removed {
+            downstreams.remove(id);
+        }
+        class.update_presence_hooks(|hooks| hooks.retain(|(id, ..)| !removed.contains(id)));
+    }
+
+    /// Returns the equiv class of this port. This clones the
+    /// Rc, which is only done between tags.
+    fn class(&self) -> Rc<PortCell<T>> {
+        cfg_if! {
+            if #[cfg(feature = "no-unsafe")] {
+                Rc::clone(&*AtomicRefCell::borrow(&self.upstream_binding))
+            } else {
+                unsafe { Rc::clone(&*self.upstream_binding.get()) }
+            }
+        }
+    }
 }
 
 impl<T: Sync> ReactionTrigger<T> for Port<T> {
@@ -306,9 +453,35 @@ struct PortCell<T: Sync> {
     /// - if you then try binding C -> A, then we can know
     ///   that C is in the downstream of A, indicating that there is a cycle.
     downstreams: Downstreams<T>,
+
+    /// The multiport channels that belong to this equiv class,
+    /// as their ID, the presence set of their multiport and
+    /// their index. Those are notified when the value is set.
+    /// This is only mutated during assembly and mutations.
+    presence_hooks: AtomicRefCell<Vec<(PortId, Rc<ChannelPresence>, usize)>>,
+    /// Whether [Self::presence_hooks] is non-empty, so that
+    /// setting a port outside of a multiport skips the borrow.
+    has_presence_hooks: AtomicBool,
 }
 
 impl<T: Sync> PortCell<T> {
+    #[inline]
+    fn notify_presence(&self, present: bool) {
+        if !self.has_presence_hooks.load(Ordering::Relaxed) {
+            return;
+        }
+        for (_, presence, channel) in self.presence_hooks.borrow().iter() {
+            presence.mark(*channel, present)
+        }
+    }
+
+    fn update_presence_hooks<R>(&self, f: impl FnOnce(&mut Vec<(PortId, Rc<ChannelPresence>, usize)>) -> R) -> R {
+        let mut hooks = self.presence_hooks.borrow_mut();
+        let result = f(&mut hooks);
+        self.has_presence_hooks.store(!hooks.is_empty(), Ordering::Relaxed);
+        result
+    }
+
     fn check_cycle(&self, upstream_id: &PortId, downstream_id: &PortId) -> Result<(), AssemblyError> {
         if (*self.downstreams.borrow()).contains_key(upstream_id) {
             Err(AssemblyError(CyclicDependency(*upstream_id, *downstream_id)))
@@ -320,14 +493,19 @@ impl<T: Sync> PortCell<T> {
     /// This updates all downstreams to point to the given equiv class instead of `self`
     fn set_upstream(&self, new_binding: &Rc<PortCell<T>>) {
         for cell_rc in (*self.downstreams.borrow()).values() {
-            cfg_if! {
-                if #[cfg(feature = "no-unsafe")] {
-                    let mut ref_mut = cell_rc.borrow_mut();
-                    *ref_mut.deref_mut() = Rc::clone(new_binding);
-                } else {
-                    unsafe {
-                        *cell_rc.get() = Rc::clone(new_binding);
-                    }
+            Self::repoint(cell_rc, new_binding)
+        }
+    }
+
+    /// Make the binding cell of a port point to the given equiv class.
+    fn repoint(cell: &UncheckedCell<Rc<PortCell<T>>>, class: &Rc<PortCell<T>>) {
+        cfg_if! {
+            if #[cfg(feature = "no-unsafe")] {
+                let mut ref_mut = cell.borrow_mut();
+                *ref_mut.deref_mut() = Rc::clone(class);
+            } else {
+                unsafe {
+                    *cell.get() = Rc::clone(class);
                 }
             }
         }
@@ -339,6 +517,97 @@ impl<T: Sync> Default for PortCell<T> {
         PortCell {
             value: Default::default(),
             downstreams: Default::default(),
+            presence_hooks: Default::default(),
+            has_presence_hooks: Default::default(),
+        }
+    }
+}
+
+/// The set of channels of a multiport that are present at
+/// the current tag. This makes iterating over present channels
+/// and cleaning them up proportional to the number of present
+/// channels, instead of the width of the multiport.
+struct ChannelPresence {
+    /// Whether each channel is present. Setting a channel
+    /// only takes the lock on [Self::listed] if it was absent.
+    flags: AtomicRefCell<Vec<AtomicBool>>,
+    /// Channels that were set during the current tag. This
+    /// needs a lock because ports bound to different channels
+    /// may be set concurrently by the parallel runtime.
+    listed: Mutex<ChannelList>,
+}
+
+/// Channels of a multiport, in the order they were set. This
+/// may contain channels that were unset since, and duplicates
+/// if they were set again. It is sorted and deduplicated at
+/// most once per tag, before being iterated.
+struct ChannelList {
+    channels: Vec<usize>,
+    sorted: bool,
+}
+
+impl Default for ChannelList {
+    fn default() -> Self {
+        Self { channels: Vec::new(), sorted: true }
+    }
+}
+
+impl ChannelList {
+    fn push(&mut self, channel: usize) {
+        if self.channels.last().map_or(false, |last| *last >= channel) {
+            self.sorted = false;
+        }
+        self.channels.push(channel);
+    }
+
+    fn sort(&mut self) {
+        if !self.sorted {
+            self.channels.sort_unstable();
+            self.channels.dedup();
+            self.sorted = true;
+        }
+    }
+}
+
+impl ChannelPresence {
+    fn with_width(width: usize) -> Self {
+        Self {
+            flags: AtomicRefCell::new((0..width).map(|_| AtomicBool::new(false)).collect()),
+            listed: Default::default(),
+        }
+    }
+
+    // The flags are only read after the reactions that set
+    // them have completed, which synchronizes them already.
+    fn mark(&self, channel: usize, present: bool) {
+        let flags = self.flags.borrow();
+        if !present {
+            flags[channel].store(false, Ordering::Relaxed);
+        } else if !flags[channel].swap(true, Ordering::Relaxed) {
+            self.listed.lock().unwrap().push(channel);
+        }
+    }
+}
+
+/// Iterates over the present channels of a multiport in
+/// ascending order. This iterates over a copy of the channel
+/// list, so that the lock on the presence set is not held by
+/// the iterator, and several iterators may be used at once.
+struct PresentChannels<'a, T: Sync> {
+    ports: &'a [Port<T>],
+    flags: AtomicRef<'a, Vec<AtomicBool>>,
+    listed: std::vec::IntoIter<usize>,
+}
+
+impl<'a, T: Sync> Iterator for PresentChannels<'a, T> {
+    type Item = (usize, &'a Port<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let channel = self.listed.next()?;
+            if self.flags[channel].load(Ordering::Relaxed) {
+                return Some((channel, &self.ports[channel]));
+            }
         }
     }
 }
@@ -348,13 +617,48 @@ impl<T: Sync> Default for PortCell<T> {
 pub struct Multiport<T: Sync> {
     ports: Vec<Port<T>>,
     id: TriggerId,
+    presence: Rc<ChannelPresence>,
 }
 
 impl<T: Sync> Multiport<T> {
     /// Create a multiport from the given vector of ports.
+    /// The ports must not be bound yet.
     #[inline(always)]
-    pub(crate) fn new(ports: Vec<Port<T>>, id: TriggerId) -> Self {
-        Self { ports, id }
+    pub(crate) fn new(mut ports: Vec<Port<T>>, id: TriggerId) -> Self {
+        let presence = Rc::new(ChannelPresence::with_width(ports.len()));
+        for (i, port) in ports.iter_mut().enumerate() {
+            port.add_presence_hook(&presence, i);
+        }
+        Self { ports, id, presence }
+    }
+
+    /// Clear the value of present channels, called at the end of a tag.
+    pub(crate) fn clear_present(&mut self) {
+        let flags = self.presence.flags.borrow();
+        let mut listed = self.presence.listed.lock().unwrap();
+        for i in listed.channels.drain(..) {
+            if flags[i].swap(false, Ordering::Relaxed) {
+                self.ports[i].clear_value()
+            }
+        }
+        listed.sorted = true;
+    }
+
+    /// Append a channel, when the multiport is resized at
+    /// runtime. The port must not be bound yet.
+    pub(crate) fn push_channel(&mut self, mut port: Port<T>) {
+        self.presence.flags.borrow_mut().push(AtomicBool::new(false));
+        port.add_presence_hook(&self.presence, self.ports.len());
+        self.ports.push(port);
+    }
+
+    /// Remove the channels after the given length, when the
+    /// multiport is resized at runtime. Those must have been
+    /// unbound from the ports that remain.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.presence.listed.lock().unwrap().channels.retain(|i| *i < len);
+        self.presence.flags.borrow_mut().truncate(len);
+        self.ports.truncate(len);
     }
 
     /// Returns the number of channels.
@@ -385,14 +689,23 @@ impl<T: Sync> Multiport<T> {
     /// Iterate over only those channels that are set (have a value).
     /// Returns a tuple with their index (not necessarily contiguous).
     pub fn enumerate_set(&self) -> impl Iterator<Item = (usize, &Port<T>)> {
-        self.iter().enumerate().filter(|&(_, p)| p.is_present_now())
+        let listed = {
+            let mut listed = self.presence.listed.lock().unwrap();
+            listed.sort();
+            listed.channels.clone()
+        };
+        PresentChannels {
+            ports: &self.ports,
+            flags: self.presence.flags.borrow(),
+            listed: listed.into_iter(),
+        }
     }
 
     /// Iterate over only those channels that are set (have a value).
     /// The returned ports are not necessarily contiguous. See
     /// [Self::enumerate_set] to get access to their index.
     pub fn iterate_set(&self) -> impl Iterator<Item = &Port<T>> {
-        self.iter().filter(|&p| p.is_present_now())
+        self.enumerate_set().map(|(_, p)| p)
     }
 
     /// Iterate over only those channels that are set (have a value),
@@ -403,7 +716,7 @@ impl<T: Sync> Multiport<T> {
     where
         T: Copy,
     {
-        self.iter().filter_map(|p| p.get())
+        self.iterate_set().filter_map(|p| p.get())
     }
 
     /// Iterate over only those ports that are set (have a value),
@@ -412,7 +725,7 @@ impl<T: Sync> Multiport<T> {
     /// [Self::enumerate_values] to get access to their index.
     #[cfg(not(feature = "no-unsafe"))]
     pub fn iterate_values_ref(&self) -> impl Iterator<Item = &T> + '_ {
-        self.iter().filter_map(|p| p.get_ref())
+        self.iterate_set().filter_map(|p| p.get_ref())
     }
 
     /// Iterate over only those channels that are set (have a value),
@@ -421,14 +734,14 @@ impl<T: Sync> Multiport<T> {
     where
         T: Copy,
     {
-        self.iter().enumerate().filter_map(|(i, p)| p.get().map(|v| (i, v)))
+        self.enumerate_set().filter_map(|(i, p)| p.get().map(|v| (i, v)))
     }
 
     /// Iterate over only those channels that are set (have a value),
     /// yielding a tuple with their index in the bank and a reference to the value.
     #[cfg(not(feature = "no-unsafe"))]
     pub fn enumerate_values_ref(&self) -> impl Iterator<Item = (usize, &T)> + '_ {
-        self.iter().enumerate().filter_map(|(i, p)| p.get_ref().map(|v| (i, v)))
+        self.enumerate_set().filter_map(|(i, p)| p.get_ref().map(|v| (i, v)))
     }
 }
 
@@ -469,3 +782,259 @@ impl<T: Sync> IndexMut<usize> for Multiport<T> {
         &mut self.ports[index]
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::assembly::PortKind;
+    use crate::builder::*;
+    use crate::prelude::*;
+    use crate::test::testutil::*;
+    use crate::SchedulerOptions;
+
+    #[test]
+    fn test_parent_accesses_child_ports() {
+        let log = run_logged(SchedulerOptions::default(), |builder, main, _| {
+            let doublers = builder.add_child_bank(main, "doubler", 3, |i| i as u32);
+
+            let mut inputs = Vec::new();
+            let mut outputs = Vec::new();
+            for &doubler in &doublers {
+                let inp = builder.new_port::<u32, _>(doubler, "in", PortKind::Input);
+                let out = builder.new_port::<u32, _>(doubler, "out", PortKind::Output);
+                builder
+                    .add_reaction(doubler, move |ctx, offset, c| {
+                        let v = ctx.get(c.port(inp)).unwrap();
+                        ctx.set(c.port_mut(out), 2 * v + *offset)
+                    })
+                    .triggered_by(inp)
+                    .effects(out);
+                inputs.push(inp);
+                outputs.push(out);
+            }
+
+            let mut startup = builder
+                .add_reaction(main, {
+                    let inputs = inputs.clone();
+                    move |ctx, _, c| {
+                        for &inp in &inputs {
+                            ctx.set(c.port_mut(inp), 10)
+                        }
+                    }
+                })
+                .on_startup();
+            for &inp in &inputs {
+                startup = startup.effects(inp);
+            }
+
+            let mut sink = builder.add_reaction(main, {
+                let outputs = outputs.clone();
+                move |ctx, log, c| {
+                    for (i, &out) in outputs.iter().enumerate() {
+                        if let Some(v) = ctx.get(c.port(out)) {
+                            log.lock().unwrap().push((i, v))
+                        }
+                    }
+                }
+            });
+            for &out in &outputs {
+                sink = sink.triggered_by(out);
+            }
+        });
+
+        assert_eq!(log, vec![(0, 20), (1, 21), (2, 22)]);
+    }
+
+    /// Connects a multiport of width 3 from a `source` to a `sink`
+    /// reactor, which holds the log.
+    fn build_multiport_program<T: Send + 'static>(
+        builder: &mut ProgramBuilder,
+        main: ReactorHandle<Log<T>>,
+        log: &Log<T>,
+    ) -> (
+        ReactorHandle<()>,
+        MultiportHandle<u32>,
+        ReactorHandle<Log<T>>,
+        MultiportHandle<u32>,
+    ) {
+        let source = builder.add_child(main, "source", ());
+        let sink = builder.add_child(main, "sink", log.clone());
+        let out = builder.new_multiport::<u32, _>(source, "out", PortKind::Output, 3);
+        let inp = builder.new_multiport::<u32, _>(sink, "in", PortKind::Input, 3);
+        builder.connect_multiports(out, inp);
+        (source, out, sink, inp)
+    }
+
+    #[test]
+    fn test_multiport_connection() {
+        let log = run_logged(SchedulerOptions::default(), |builder, main, log| {
+            let (source, out, sink, inp) = build_multiport_program(builder, main, log);
+            builder
+                .add_reaction(source, move |ctx, _, c| ctx.set(c.port_mut(out.channel(1)), 5))
+                .on_startup()
+                .effects(out.channel(1));
+            builder
+                .add_reaction(sink, move |ctx, log, c| {
+                    let values: Vec<_> = c.multiport(inp).iter().map(|p| ctx.get(p)).collect();
+                    log.lock().unwrap().push(values)
+                })
+                .triggered_by(inp);
+        });
+
+        assert_eq!(log, vec![vec![None, Some(5), None]]);
+    }
+
+    #[test]
+    fn test_multiport_channels_are_cleared_between_tags() {
+        let log = run_logged(options_with_timeout(Duration::from_millis(25)), |builder, main, log| {
+            let (source, out, sink, inp) = build_multiport_program(builder, main, log);
+            let t = builder.new_timer(source, "t", Duration::ZERO, Duration::from_millis(10));
+
+            // each tag sets a different channel, the others must be absent
+            builder
+                .add_reaction(source, move |ctx, _, c| {
+                    let i = (ctx.get_elapsed_logical_time().as_millis() / 10) as usize;
+                    ctx.set(c.port_mut(out.channel(i)), i as u32)
+                })
+                .triggered_by(t)
+                .effects(out);
+            builder
+                .add_reaction(sink, move |ctx, log, c| {
+                    let values: Vec<_> = c.multiport(inp).iter().map(|p| ctx.get(p)).collect();
+                    log.lock().unwrap().push(values)
+                })
+                .triggered_by(inp);
+        });
+
+        assert_eq!(
+            log,
+            vec![
+                vec![Some(0), None, None],
+                vec![None, Some(1), None],
+                vec![None, None, Some(2)]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_taken_multiport_channels_are_absent() {
+        let log = run_logged(SchedulerOptions::default(), |builder, main, log| {
+            let (source, out, sink, inp) = build_multiport_program(builder, main, log);
+            builder
+                .add_reaction(source, move |ctx, _, c| {
+                    let out = c.multiport_mut(out);
+                    for i in 0..3 {
+                        ctx.set(&mut out[i], i as u32)
+                    }
+                })
+                .on_startup()
+                .effects(out);
+            builder
+                .add_reaction(sink, move |ctx, log, c| {
+                    assert_eq!(ctx.take(c.port_mut(inp.channel(1))), Some(1));
+                    assert_eq!(ctx.use_mut(c.port_mut(inp.channel(2)), |v| v.map(|v| *v += 10)), Some(()));
+                    let present: Vec<_> = c.multiport(inp).enumerate_set().map(|(i, p)| (i, ctx.get(p))).collect();
+                    log.lock().unwrap().push(present)
+                })
+                .triggered_by(inp)
+                .mutates(inp.channel(1))
+                .mutates(inp.channel(2));
+        });
+
+        assert_eq!(log, vec![vec![(0, Some(0)), (2, Some(12))]]);
+    }
+
+    #[test]
+    fn test_present_channels_are_iterated_by_several_iterators_at_once() {
+        let log = run_logged(SchedulerOptions::default(), |builder, main, log| {
+            let (source, out, sink, inp) = build_multiport_program(builder, main, log);
+            builder
+                .add_reaction(source, move |ctx, _, c| {
+                    let out = c.multiport_mut(out);
+                    ctx.set(&mut out[2], 2);
+                    ctx.set(&mut out[0], 0);
+                })
+                .on_startup()
+                .effects(out);
+            builder
+                .add_reaction(sink, move |_, log, c| {
+                    let inp = c.multiport(inp);
+                    let mut log = log.lock().unwrap();
+                    for (i, _) in inp.enumerate_set() {
+                        for (j, _) in inp.enumerate_set() {
+                            log.push((i as u32, j as u32))
+                        }
+                    }
+                    log.extend(inp.iterate_values().zip(inp.enumerate_values().map(|(_, v)| v)));
+                })
+                .triggered_by(inp);
+        });
+
+        assert_eq!(log, vec![(0, 0), (0, 2), (2, 0), (2, 2), (0, 0), (2, 2)]);
+    }
+
+    /// Builds a program where `consumer` takes the vec sent
+    /// by `source`, which `other` reads too.
+    fn build_mutable_input_program(builder: &mut ProgramBuilder, main: ReactorHandle<Log<(u32, u32)>>, other_reads_first: bool) {
+        let source = builder.add_child(main, "source", ());
+        let consumer = builder.add_child(main, "consumer", ());
+        let other = builder.add_child(main, "other", ());
+        let source_out = builder.new_port::<Vec<u32>, _>(source, "out", PortKind::Output);
+        let consumer_in = builder.new_port::<Vec<u32>, _>(consumer, "in", PortKind::Input);
+        let consumer_go = builder.new_port::<u32, _>(consumer, "go", PortKind::Input);
+        let consumer_out = builder.new_port::<u32, _>(consumer, "out", PortKind::Output);
+        let other_in = builder.new_port::<Vec<u32>, _>(other, "in", PortKind::Input);
+        let other_out = builder.new_port::<u32, _>(other, "out", PortKind::Output);
+        builder.connect(source_out, consumer_in);
+        builder.connect(source_out, other_in);
+        if other_reads_first {
+            builder.connect(other_out, consumer_go);
+        }
+
+        builder
+            .add_reaction(source, move |ctx, _, c| ctx.set(c.port_mut(source_out), vec![1, 2]))
+            .on_startup()
+            .effects(source_out);
+        builder
+            .add_reaction(other, move |ctx, _, c| {
+                let len = ctx.use_ref_opt(c.port(other_in), Vec::len).unwrap();
+                ctx.set(c.port_mut(other_out), len as u32)
+            })
+            .triggered_by(other_in)
+            .effects(other_out);
+        builder
+            .add_reaction(consumer, move |ctx, _, c| {
+                let mut values = ctx.take(c.port_mut(consumer_in)).unwrap();
+                values.push(3);
+                assert!(!ctx.is_present(c.port(consumer_in)));
+                ctx.set(c.port_mut(consumer_out), values.len() as u32)
+            })
+            .triggered_by(consumer_in)
+            .triggered_by(consumer_go)
+            .mutates(consumer_in)
+            .effects(consumer_out);
+        builder
+            .add_reaction(main, move |ctx, log, c| {
+                let entry = (ctx.get(c.port(other_out)).unwrap(), ctx.get(c.port(consumer_out)).unwrap());
+                log.lock().unwrap().push(entry)
+            })
+            .triggered_by(other_out)
+            .triggered_by(consumer_out);
+    }
+
+    #[test]
+    fn test_mutable_input_is_taken_by_last_reader() {
+        let log = run_logged(SchedulerOptions::default(), |builder, main, _| {
+            build_mutable_input_program(builder, main, true)
+        });
+
+        assert_eq!(log, vec![(2, 3)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "may read it concurrently or afterwards")]
+    fn test_mutable_input_with_concurrent_reader_is_rejected() {
+        run_logged(SchedulerOptions::default(), |builder, main, _| {
+            build_mutable_input_program(builder, main, false)
+        });
+    }
+}
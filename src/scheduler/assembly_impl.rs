@@ -29,6 +29,7 @@ use index_vec::{Idx, IndexVec};
 
 use super::{ReactorBox, ReactorVec};
 use crate::assembly::*;
+use crate::codec::ValueCodec;
 use crate::scheduler::dependencies::DepGraph;
 use crate::*;
 
@@ -42,18 +43,32 @@ pub(super) struct RootAssembler {
     pub(super) debug_info: DebugInfoRegistry,
 
     /// Next reactor ID to assign
-    reactor_id: ReactorId,
+    pub(super) reactor_id: ReactorId,
     /// Next trigger ID to assign
-    cur_trigger: TriggerId,
+    pub(super) cur_trigger: TriggerId,
+
+    /// Whether bindings of mismatched widths are truncated
+    /// instead of failing assembly (see [SchedulerOptions::allow_width_mismatch]).
+    pub(super) allow_width_mismatch: bool,
+
+    /// Parameters of reactors given by a configuration file.
+    #[cfg(feature = "config")]
+    pub(super) config: std::sync::Arc<crate::config::Config>,
 }
 
 impl RootAssembler {
     /// Register a reactor into the global data structure that owns them during execution.
     fn register_reactor<R: ReactorInitializer + 'static>(&mut self, child: R) {
+        self.register_boxed(Box::new(child))
+    }
+
+    /// Register a reactor into the global data structure that owns them during execution.
+    pub(super) fn register_boxed(&mut self, child: ReactorBox<'static>) {
         if child.id().index() >= self.reactors.len() {
             self.reactors.resize_with(child.id().index() + 1, || None)
         }
-        let prev = self.reactors[child.id()].replace(Box::new(child));
+        let id = child.id();
+        let prev = self.reactors[id].replace(child);
         // this is impossible because we control how we allocate IDs entirely
         debug_assert!(prev.is_none(), "Overwrote a reactor during initialization")
     }
@@ -65,25 +80,117 @@ impl RootAssembler {
         }
     }
 
+    pub(super) fn new(options: &SchedulerOptions) -> Self {
+        Self {
+            allow_width_mismatch: options.allow_width_mismatch,
+            #[cfg(feature = "config")]
+            config: options.config.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Resume the assembly of a program that is executing,
+    /// to mutate it. New IDs are allocated after those of the
+    /// existing reactors and components.
+    pub(super) fn resume(
+        reactors: ReactorVec<'static>,
+        graph: DepGraph,
+        debug_info: DebugInfoRegistry,
+        options: &SchedulerOptions,
+    ) -> Self {
+        Self {
+            reactor_id: ReactorId::from_usize(reactors.len()),
+            cur_trigger: debug_info.next_trigger_id(),
+            reactors: reactors.into_iter().map(Some).collect(),
+            graph,
+            debug_info,
+            allow_width_mismatch: options.allow_width_mismatch,
+            #[cfg(feature = "config")]
+            config: options.config.clone(),
+        }
+    }
+
     /// Top level fun that assembles the main reactor
     pub fn assemble_tree<R: ReactorInitializer + 'static>(
         main_args: R::Params,
+        options: &SchedulerOptions,
     ) -> (ReactorVec<'static>, DepGraph, DebugInfoRegistry) {
-        let mut root = RootAssembler::default();
-        let assembler = AssemblyCtx::new(&mut root, ReactorDebugInfo::root::<R::Wrapped>());
-
-        let main_reactor = match R::assemble(main_args, assembler) {
-            Ok(main) => main.finish(),
-            Err(e) => std::panic::panic_any(e.lift(&root.debug_info)),
-        };
+        let mut root = RootAssembler::new(options);
+        let main_reactor = root.assemble_main::<R>(main_args);
         root.debug_info.record_main_reactor(main_reactor.id());
         root.register_reactor(main_reactor);
+        root.finish()
+    }
+
+    /// Assemble the given reactor at the root of the tree,
+    /// and return it. The reactor is not registered, nor
+    /// recorded as the main reactor.
+    pub(super) fn assemble_main<R: ReactorInitializer + 'static>(&mut self, args: R::Params) -> R {
+        let assembler = AssemblyCtx::new(self, ReactorDebugInfo::root::<R::Wrapped>());
+
+        match R::assemble(args, assembler) {
+            Ok(main) => main.finish(),
+            Err(e) => std::panic::panic_any(e.lift(&self.debug_info)),
+        }
+    }
 
-        let RootAssembler { graph, reactors, debug_info: id_registry, .. } = root;
+    /// Release the global data structures, once all reactors
+    /// have been registered.
+    pub(super) fn finish(self) -> (ReactorVec<'static>, DepGraph, DebugInfoRegistry) {
+        let RootAssembler { graph, reactors, debug_info: id_registry, .. } = self;
 
         let reactors = reactors.into_iter().map(|r| r.expect("Uninitialized reactor!")).collect();
         (reactors, graph, id_registry)
     }
+
+    /// Checks that both sides of a zipped binding have the
+    /// same width. If they don't, but mismatches are allowed
+    /// and both sides are non-empty, the binding is recorded
+    /// to be reported as a lint instead.
+    pub(super) fn check_zipped_widths(
+        &mut self,
+        upstream: Option<PortId>,
+        up_width: usize,
+        downstream: Option<PortId>,
+        down_width: usize,
+    ) -> AssemblyResult<()> {
+        if up_width == down_width {
+            return Ok(());
+        }
+        match (upstream, downstream) {
+            (Some(up), Some(down)) if self.allow_width_mismatch => {
+                self.graph.record_zipped_binding(up, up_width, down, down_width);
+                Ok(())
+            }
+            _ => Err(AssemblyError(AssemblyErrorImpl::WidthMismatch(
+                upstream, up_width, downstream, down_width,
+            ))),
+        }
+    }
+
+    /// Checks that the width of the downstream of an iterated
+    /// binding is a multiple of that of the upstream. The
+    /// upstream must not be empty, even if mismatches are allowed.
+    pub(super) fn check_iterated_widths(
+        &mut self,
+        upstream: Option<PortId>,
+        up_width: usize,
+        downstream: Option<PortId>,
+        down_width: usize,
+    ) -> AssemblyResult<()> {
+        if up_width != 0 && down_width % up_width == 0 {
+            return Ok(());
+        }
+        match (upstream, downstream) {
+            (Some(up), Some(down)) if self.allow_width_mismatch => {
+                self.graph.record_iterated_binding(up, up_width, down, down_width);
+                Ok(())
+            }
+            _ => Err(AssemblyError(AssemblyErrorImpl::IteratedWidthMismatch(
+                upstream, up_width, downstream, down_width,
+            ))),
+        }
+    }
 }
 
 impl Default for RootAssembler {
@@ -94,6 +201,9 @@ impl Default for RootAssembler {
             debug_info: DebugInfoRegistry::new(),
             reactors: Default::default(),
             cur_trigger: TriggerId::FIRST_REGULAR,
+            allow_width_mismatch: false,
+            #[cfg(feature = "config")]
+            config: Default::default(),
         }
     }
 }
@@ -153,6 +263,25 @@ where
         Ok(FinishedReactor(PhantomData, reactor))
     }
 
+    /// Returns the value of a parameter of this reactor given
+    /// by the configuration file (see [SchedulerOptions::config]),
+    /// or the given default. The parameter is looked up by
+    /// the path of this reactor, see [crate::config].
+    ///
+    /// # Panics
+    ///
+    /// If the value in the file does not have type `T`, or
+    /// if this is called after [Self::assemble_self].
+    #[cfg(feature = "config")]
+    pub fn param<T: crate::cli::ParamValue>(&self, name: &str, default: T) -> T {
+        let debug = self.debug.as_ref().expect("should read parameters before assemble_self");
+        let path = format!("{}.{}", crate::config::reactor_path(&debug.to_string()), name);
+        match self.globals.config.param(&path) {
+            Ok(value) => value.unwrap_or(default),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
     /// Innermost function.
     pub fn assemble_self<const N: usize>(
         mut self,
@@ -192,7 +321,11 @@ where
 
         // declare dependencies
         let reactions = self.new_reactions(id, num_non_synthetic_reactions, reaction_names);
-        declare_dependencies(&mut DependencyDeclarator { assembler: &mut self }, &mut ich, reactions)?;
+        let mut declarator = DependencyDeclarator {
+            assembler: &mut self,
+            num_non_synthetic: num_non_synthetic_reactions,
+        };
+        declare_dependencies(&mut declarator, &mut ich, reactions)?;
         Ok(AssemblyIntermediate(self, ich))
     }
 
@@ -200,7 +333,9 @@ where
     /// priority edges, as they are taken to be those declared
     /// in LF by the user.
     /// The rest do not have priority edges, and their
-    /// implementation must hence have no observable side-effect.
+    /// implementation must hence have no observable side-effect,
+    /// except for those triggered by a timer, see
+    /// [DependencyDeclarator::declare_triggers].
     fn new_reactions<const N: usize>(
         &mut self,
         my_id: ReactorId,
@@ -317,12 +452,29 @@ where
 /// Declares dependencies between components and reactions.
 pub struct DependencyDeclarator<'a, 'x, S: ReactorInitializer> {
     assembler: &'a mut AssemblyCtx<'x, S>,
+    /// Number of reactions declared by the user, which come
+    /// before the synthetic ones.
+    num_non_synthetic: usize,
 }
 
 impl<S: ReactorInitializer> DependencyDeclarator<'_, '_, S> {
+    /// Declares that the trigger triggers the reaction. A
+    /// synthetic reaction triggered by a timer reschedules it.
+    /// It executes after the user reactions of its reactor, which
+    /// may change the period of the timer (see [ReactionCtx::set_timer_period]).
+    /// It must have no effects, so that this cannot introduce
+    /// a cycle.
     #[inline]
     pub fn declare_triggers(&mut self, trigger: TriggerId, reaction: GlobalReactionId) -> AssemblyResult<()> {
         self.graph().triggers_reaction(trigger, reaction);
+        let local = reaction.0.local().index();
+        if local >= self.num_non_synthetic && self.num_non_synthetic > 0 && self.graph().is_timer(trigger) {
+            let last_user_reaction = GlobalReactionId::new(
+                reaction.0.container(),
+                LocalReactionId::from_usize(self.num_non_synthetic - 1),
+            );
+            self.graph().reaction_priority(last_user_reaction, reaction);
+        }
         Ok(())
     }
 
@@ -336,6 +488,30 @@ impl<S: ReactorInitializer> DependencyDeclarator<'_, '_, S> {
         self.effects_instantaneous(reaction, port.get_id())
     }
 
+    /// Declares that the reaction may schedule the action.
+    /// This does not constrain the execution order of reactions,
+    /// but is required by the `verify-dependencies` feature.
+    #[inline]
+    pub fn effects_logical_action<T: Sync>(
+        &mut self,
+        reaction: GlobalReactionId,
+        action: &LogicalAction<T>,
+    ) -> AssemblyResult<()> {
+        self.graph().reaction_schedules(reaction, action.get_id());
+        Ok(())
+    }
+
+    /// See [Self::effects_logical_action].
+    #[inline]
+    pub fn effects_physical_action<T: Sync>(
+        &mut self,
+        reaction: GlobalReactionId,
+        action: &PhysicalActionRef<T>,
+    ) -> AssemblyResult<()> {
+        self.graph().reaction_schedules(reaction, action.get_id());
+        Ok(())
+    }
+
     #[doc(hidden)] // used by synthesized timer reactions
     pub fn effects_timer(&mut self, reaction: GlobalReactionId, timer: &Timer) -> AssemblyResult<()> {
         self.effects_instantaneous(reaction, timer.get_id())
@@ -353,6 +529,43 @@ impl<S: ReactorInitializer> DependencyDeclarator<'_, '_, S> {
         Ok(())
     }
 
+    /// Declares that the reaction may take or mutate the value
+    /// of the port, see [ReactionCtx::take]. This implies
+    /// [Self::declare_uses]. Whether this is allowed is checked
+    /// when the dependency graph is complete.
+    #[inline]
+    pub fn declare_mutable_input(&mut self, reaction: GlobalReactionId, port: TriggerId) -> AssemblyResult<()> {
+        self.graph().reaction_mutates(reaction, port);
+        Ok(())
+    }
+
+    /// Register a codec for the values of a port or action,
+    /// see [crate::codec].
+    pub fn register_codec<T: Send + 'static>(
+        &mut self,
+        component: &impl ReactionTrigger<T>,
+        codec: impl ValueCodec<T> + 'static,
+    ) {
+        let codec = crate::codec::erase(codec);
+        self.assembler.globals.debug_info.record_codec(component.get_id(), codec);
+    }
+
+    /// Register a codec for the values of all the channels
+    /// of a multiport, see [crate::codec].
+    pub fn register_multiport_codec<T: Sync + Send + 'static>(
+        &mut self,
+        multiport: &Multiport<T>,
+        codec: impl ValueCodec<T> + 'static,
+    ) {
+        let codec = crate::codec::erase(codec);
+        for channel in multiport.iter() {
+            self.assembler
+                .globals
+                .debug_info
+                .record_codec(channel.get_id(), codec.clone());
+        }
+    }
+
     /// Bind two ports together.
     #[inline]
     pub fn bind_ports<T: Sync>(&mut self, upstream: &mut Port<T>, downstream: &mut Port<T>) -> AssemblyResult<()> {
@@ -362,39 +575,55 @@ impl<S: ReactorInitializer> DependencyDeclarator<'_, '_, S> {
     }
 
     /// Bind the ports of the upstream to those of the downstream,
-    /// as if zipping both iterators.
-    /// todo this will just throw away bindings if both iterators are not of the same size
-    ///  normally this should be reported by LFC as a warning, maybe we should implement the same thing here
+    /// as if zipping both iterators. Both iterators must have
+    /// the same length, unless the scheduler is configured with
+    /// [SchedulerOptions::allow_width_mismatch], in which case
+    /// the excess ports are left unbound.
     #[inline]
     pub fn bind_ports_zip<'a, T: Sync + 'a>(
         &mut self,
         upstream: impl Iterator<Item = &'a mut Port<T>>,
         downstream: impl Iterator<Item = &'a mut Port<T>>,
     ) -> AssemblyResult<()> {
-        for (upstream, downstream) in upstream.zip(downstream) {
+        let upstream = upstream.collect::<Vec<_>>();
+        let downstream = downstream.collect::<Vec<_>>();
+        self.assembler.globals.check_zipped_widths(
+            upstream.first().map(|p| p.get_id()),
+            upstream.len(),
+            downstream.first().map(|p| p.get_id()),
+            downstream.len(),
+        )?;
+        for (upstream, downstream) in upstream.into_iter().zip(downstream) {
             self.bind_ports(upstream, downstream)?;
         }
         Ok(())
     }
 
+    /// Bind the ports of the upstream to those of the downstream,
+    /// cycling through the upstream ports as many times as
+    /// necessary. The width of the downstream must be a multiple
+    /// of that of the upstream, unless the scheduler is configured
+    /// with [SchedulerOptions::allow_width_mismatch], in which
+    /// case the last cycle is incomplete.
     #[inline]
     pub fn bind_ports_iterated<'a, T: Sync + 'a>(
         &mut self,
         upstream: impl Iterator<Item = &'a mut Port<T>>,
-        mut downstream: impl Iterator<Item = &'a mut Port<T>>,
+        downstream: impl Iterator<Item = &'a mut Port<T>>,
     ) -> AssemblyResult<()> {
         let mut upstream = upstream.collect::<Vec<_>>();
-        assert!(!upstream.is_empty(), "Empty upstream!");
+        let downstream = downstream.collect::<Vec<_>>();
+        self.assembler.globals.check_iterated_widths(
+            upstream.first().map(|p| p.get_id()),
+            upstream.len(),
+            downstream.first().map(|p| p.get_id()),
+            downstream.len(),
+        )?;
         let up_len = upstream.len();
         // we have to implement this loop manually instead of with an iterator
         // because we can't clone mutable references in the upstream iterator
-        for i in 0.. {
-            let up = &mut upstream[i % up_len];
-            if let Some(down) = downstream.next() {
-                self.bind_ports(up, down)?;
-            } else {
-                break;
-            }
+        for (i, down) in downstream.into_iter().enumerate() {
+            self.bind_ports(upstream[i % up_len], down)?;
         }
         Ok(())
     }
@@ -417,7 +646,7 @@ impl<S: ReactorInitializer> ComponentCreator<'_, '_, S> {
 
     fn new_port_impl<T: Sync>(&mut self, lf_name: Cow<'static, str>, kind: PortKind) -> Port<T> {
         let id = self.next_comp_id(lf_name);
-        self.graph().record_port(id);
+        self.graph().record_port(id, kind);
         Port::new(id, kind)
     }
 
@@ -445,7 +674,7 @@ impl<S: ReactorInitializer> ComponentCreator<'_, '_, S> {
         index: usize,
     ) -> Port<T> {
         let channel_id = self.next_comp_id(Cow::Owned(format!("{}[{}]", lf_name, index)));
-        self.graph().record_port_bank_component(bank_id, channel_id);
+        self.graph().record_port_bank_component(bank_id, channel_id, kind);
         Port::new(channel_id, kind)
     }
 
@@ -485,6 +714,28 @@ impl<S: ReactorInitializer> ComponentCreator<'_, '_, S> {
     }
 }
 
+/// Returns the width shared by the multiports, which are the
+/// same field of every reactor of a bank. This is used by
+/// [unsafe_iter_bank] to interleave the multiports.
+#[doc(hidden)]
+pub fn interleaved_width<'a, T: Sync + 'a>(mut multiports: impl Iterator<Item = &'a Multiport<T>>) -> AssemblyResult<usize> {
+    let first = match multiports.next() {
+        Some(first) => first,
+        None => return Ok(0),
+    };
+    for other in multiports {
+        if other.len() != first.len() {
+            return Err(AssemblyError(AssemblyErrorImpl::InterleavedWidthMismatch(
+                first.get_id(),
+                first.len(),
+                other.get_id(),
+                other.len(),
+            )));
+        }
+    }
+    Ok(first.len())
+}
+
 /// Iterates a bank, produces an `Iterator<Item=&mut Port<_>>`.
 /// Does not explicitly borrow the bank, which is unsafe, but
 /// we trust the code generator to fail if a port is both on
@@ -517,29 +768,27 @@ macro_rules! unsafe_iter_bank {
             .map(move |i| unsafe { &mut (*__ptr.add(i)) })
             .flat_map(|a| a.$field_name.iter_mut())
     }};
-    // the field is a multiport, we interleave all of them
+    // the field is a multiport, we interleave all of them.
+    // This fails with `?` if the multiports don't all have the same width.
     ($bank:ident # interleaved($field_name:ident)) => {{
+        let __multiport_len = $crate::assembly::interleaved_width($bank.iter().map(|r| &r.$field_name))?;
         let __ptr = $bank.as_mut_ptr();
         let __bank_len = $bank.len();
-        // Assume that the contained multiports of the bank
-        // are all of the same length.
-        let __multiport_len = $bank[0].$field_name.len();
 
         // Build an iterator of tuples that get mapped to their
         // respective bank element and multiport.
         let mut bank_idx = 0;
         let mut multiport_idx = 0;
-        let mut iter = std::iter::from_fn(move || {
+        let iter = std::iter::from_fn(move || {
             // The inner loop iterates over bank_idx.
             if bank_idx >= __bank_len {
                 // When one iteration is done we reset the bank_idx
                 // and increment the outer loop over multiport_idx.
                 bank_idx = 0;
                 multiport_idx += 1;
-
-                if multiport_idx >= __multiport_len {
-                    return None;
-                }
+            }
+            if multiport_idx >= __multiport_len {
+                return None;
             }
 
             let bank_idx_copy = bank_idx;
@@ -0,0 +1,1854 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use std::any::{type_name, Any};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use index_vec::Idx;
+
+use super::assembly_impl::RootAssembler;
+use super::{ReactorVec, SchedulerOptions, SyncScheduler};
+use crate::assembly::*;
+use crate::codec::{erase, DynCodec, ValueCodec};
+use crate::scheduler::dependencies::{CutBinding, DepGraph};
+use crate::*;
+
+/// Builds a reactor program at runtime, without going
+/// through the code generator. Reactors, their components
+/// and reactions are declared on the builder, which hands
+/// out typed handles to refer to them. The program is then
+/// lowered onto the same data structures as generated code
+/// (see [ProgramBuilder::run]).
+///
+/// Reactions are closures that receive the [ReactionCtx],
+/// the state of their reactor, and its [Components], which
+/// resolve handles to the actual ports, actions and timers.
+/// Like in LF, reactions may only access the components
+/// of their own reactor, and the ports of its direct children.
+/// They must declare what they access with a [ReactionDeclarator].
+///
+/// Once the program runs, reactions may mutate it with the
+/// builder, to create reactors and connect their ports, or
+/// to destroy reactors (see [Components::mutate]).
+#[derive(Default)]
+pub struct ProgramBuilder {
+    reactors: Vec<ReactorDecl>,
+    /// Connections that were not lowered yet.
+    connections: Vec<Connection>,
+    /// Reactors to destroy when mutations are applied.
+    to_destroy: Vec<usize>,
+    /// Multiports of running reactors to resize when mutations
+    /// are applied, with their new width.
+    to_resize: Vec<(ComponentAddr, usize)>,
+    /// All connections declared with [Self::connect] and
+    /// [Self::connect_multiports], including those that were
+    /// lowered, so that connected multiports are resized
+    /// together (see [Self::resize_multiport]).
+    declared_connections: Vec<(Endpoint, Endpoint)>,
+    /// Connections of the ports of new bank members, which are
+    /// made once those ports are declared (see [Self::resize_bank]).
+    bank_connections: Vec<BankConnection>,
+}
+
+/// A mutation of a running program, see [Components::mutate].
+pub(crate) type Mutation = Box<dyn FnOnce(&mut ProgramBuilder) + Send>;
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare the main reactor. There must be exactly one.
+    pub fn add_main<S: Send + 'static>(&mut self, state: S) -> ReactorHandle<S> {
+        assert!(
+            self.reactors.iter().all(|r| r.parent.is_some()),
+            "Main reactor was already declared"
+        );
+        self.add_reactor_impl(None, "/", None, state)
+    }
+
+    /// Declare a child reactor of the given parent.
+    pub fn add_child<S: Send + 'static, P>(
+        &mut self,
+        parent: ReactorHandle<P>,
+        inst_name: &'static str,
+        state: S,
+    ) -> ReactorHandle<S> {
+        self.add_reactor_impl(Some(parent.ix), inst_name, None, state)
+    }
+
+    /// Declare a bank of child reactors of the given parent.
+    /// The state of each bank member is produced by the given
+    /// function from its bank index.
+    pub fn add_child_bank<S: Send + 'static, P>(
+        &mut self,
+        parent: ReactorHandle<P>,
+        inst_name: &'static str,
+        bank_width: usize,
+        mut state_maker: impl FnMut(/*bank_index:*/ usize) -> S,
+    ) -> Vec<ReactorHandle<S>> {
+        (0..bank_width)
+            .map(|i| self.add_reactor_impl(Some(parent.ix), inst_name, Some(i), state_maker(i)))
+            .collect()
+    }
+
+    fn add_reactor_impl<S: Send + 'static>(
+        &mut self,
+        parent: Option<usize>,
+        inst_name: &'static str,
+        bank_index: Option<usize>,
+        state: S,
+    ) -> ReactorHandle<S> {
+        let ix = self.reactors.len();
+        if let Some(parent) = parent {
+            self.reactors[parent].children.push(ix);
+        }
+        self.reactors.push(ReactorDecl {
+            type_name: type_name::<S>(),
+            inst_name,
+            bank_index,
+            parent,
+            children: Vec::new(),
+            state: Some(Box::new(state)),
+            components: Vec::new(),
+            references: HashMap::new(),
+            reactions: Vec::new(),
+            reaction_deps: Vec::new(),
+            codecs: Vec::new(),
+            lowered: None,
+            destroyed: false,
+        });
+        ReactorHandle { ix, _s: PhantomData }
+    }
+
+    /// Declare a new port on the given reactor. The kind
+    /// must be [PortKind::Input] or [PortKind::Output].
+    pub fn new_port<T: Sync + 'static, S>(
+        &mut self,
+        reactor: ReactorHandle<S>,
+        lf_name: &'static str,
+        kind: PortKind,
+    ) -> PortHandle<T> {
+        let addr = self.new_port_impl::<T>(reactor.ix, Cow::Borrowed(lf_name), kind, None);
+        PortHandle { addr, channel: None, _t: PhantomData }
+    }
+
+    /// Declare a new multiport on the given reactor. The kind
+    /// must be [PortKind::Input] or [PortKind::Output].
+    pub fn new_multiport<T: Sync + 'static, S>(
+        &mut self,
+        reactor: ReactorHandle<S>,
+        lf_name: &'static str,
+        kind: PortKind,
+        len: usize,
+    ) -> MultiportHandle<T> {
+        let addr = self.new_port_impl::<T>(reactor.ix, Cow::Borrowed(lf_name), kind, Some(len));
+        MultiportHandle { addr, len, _t: PhantomData }
+    }
+
+    fn new_port_impl<T: Sync + 'static>(
+        &mut self,
+        reactor: usize,
+        lf_name: Cow<'static, str>,
+        kind: PortKind,
+        width: Option<usize>,
+    ) -> ComponentAddr {
+        assert!(
+            matches!(kind, PortKind::Input | PortKind::Output),
+            "Port references are created automatically"
+        );
+        let ops: Arc<dyn PortOps> = Arc::new(TypedPortOps::<T>(PhantomData));
+        self.new_component(reactor, lf_name, ComponentKind::Port { kind, width, ops })
+    }
+
+    pub fn new_logical_action<T: Sync + 'static, S>(
+        &mut self,
+        reactor: ReactorHandle<S>,
+        lf_name: &'static str,
+        min_delay: Option<Duration>,
+    ) -> LogicalActionHandle<T> {
+        let make = Box::new(move |id| Box::new(LogicalAction::<T>::new(id, min_delay)) as Box<dyn DynComponent>);
+        let addr = self.new_component(
+            reactor.ix,
+            Cow::Borrowed(lf_name),
+            ComponentKind::Action { make, physical: false },
+        );
+        LogicalActionHandle { addr, _t: PhantomData }
+    }
+
+    pub fn new_physical_action<T: Sync + 'static, S>(
+        &mut self,
+        reactor: ReactorHandle<S>,
+        lf_name: &'static str,
+        min_delay: Option<Duration>,
+    ) -> PhysicalActionHandle<T> {
+        let make = Box::new(move |id| Box::new(PhysicalActionRef::<T>::new(id, min_delay)) as Box<dyn DynComponent>);
+        let addr = self.new_component(
+            reactor.ix,
+            Cow::Borrowed(lf_name),
+            ComponentKind::Action { make, physical: true },
+        );
+        PhysicalActionHandle { addr, _t: PhantomData }
+    }
+
+    /// Declare a timer. Like in generated code, the reactions
+    /// that (re)schedule the timer are synthesized.
+    pub fn new_timer<S>(
+        &mut self,
+        reactor: ReactorHandle<S>,
+        lf_name: &'static str,
+        offset: Duration,
+        period: Duration,
+    ) -> TimerHandle {
+        let addr = self.new_component(reactor.ix, Cow::Borrowed(lf_name), ComponentKind::Timer { offset, period });
+        TimerHandle { addr }
+    }
+
+    fn new_component(&mut self, reactor: usize, name: Cow<'static, str>, kind: ComponentKind) -> ComponentAddr {
+        self.reactors[reactor].assert_not_lowered();
+        let components = &mut self.reactors[reactor].components;
+        components.push(ComponentDecl { name, kind });
+        ComponentAddr { reactor, slot: components.len() - 1 }
+    }
+
+    /// Bind two ports together.
+    pub fn connect<T: Sync + 'static>(&mut self, upstream: PortHandle<T>, downstream: PortHandle<T>) {
+        self.connect_impl((upstream.addr, upstream.channel), (downstream.addr, downstream.channel));
+    }
+
+    /// Bind the channels of two multiports together, as if zipping them.
+    pub fn connect_multiports<T: Sync + 'static>(&mut self, upstream: MultiportHandle<T>, downstream: MultiportHandle<T>) {
+        self.connect_impl((upstream.addr, None), (downstream.addr, None));
+    }
+
+    /// Change the number of channels of a multiport, and return
+    /// a handle with the new width. Handles to the multiport that
+    /// were given out before still refer to it, but have the old
+    /// width. Channels that remain keep their bindings, and pending
+    /// connections to removed channels are discarded. Multiports
+    /// of running reactors may be resized in mutations (see
+    /// [Components::mutate]).
+    ///
+    /// Multiports connected to this one with [Self::connect_multiports]
+    /// are resized too, transitively, and their new channels are
+    /// connected to each other. Use [Self::refresh] to get handles
+    /// to them with the new width.
+    pub fn resize_multiport<T: Sync + 'static>(&mut self, multiport: MultiportHandle<T>, len: usize) -> MultiportHandle<T> {
+        self.resize_connected_multiports(multiport.addr, len);
+        MultiportHandle { addr: multiport.addr, len, _t: PhantomData }
+    }
+
+    /// Returns a handle to the multiport with its current width,
+    /// eg after it was resized along with a connected multiport.
+    pub fn refresh<T>(&self, multiport: MultiportHandle<T>) -> MultiportHandle<T> {
+        MultiportHandle {
+            addr: multiport.addr,
+            len: self.width(multiport.addr),
+            _t: PhantomData,
+        }
+    }
+
+    fn width(&self, addr: ComponentAddr) -> usize {
+        match &self.reactors[addr.reactor].components[addr.slot].kind {
+            ComponentKind::Port { width: Some(width), .. } => *width,
+            _ => panic!("Only multiports have a width"),
+        }
+    }
+
+    /// Resize the multiport, and those connected to it as a
+    /// whole, directly or transitively.
+    fn resize_connected_multiports(&mut self, addr: ComponentAddr, len: usize) {
+        assert!(len > 0, "Multiports cannot be empty");
+        let mut connected = vec![addr];
+        let mut next = 0;
+        while let Some(&addr) = connected.get(next) {
+            next += 1;
+            for &((up, up_channel), (down, down_channel)) in &self.declared_connections {
+                let other = match (up_channel, down_channel) {
+                    (None, None) if up == addr => down,
+                    (None, None) if down == addr => up,
+                    _ => continue,
+                };
+                if !connected.contains(&other) {
+                    connected.push(other);
+                }
+            }
+        }
+        let old_lens: HashMap<ComponentAddr, usize> = connected
+            .iter()
+            .map(|&addr| (addr, self.resize_one_multiport(addr, len)))
+            .collect();
+
+        // Multiports of running reactors were bound channel by
+        // channel, the new channels are bound the same way.
+        let bound: Vec<(ComponentAddr, ComponentAddr)> = self
+            .declared_connections
+            .iter()
+            .filter_map(|&((up, up_channel), (down, down_channel))| match (up_channel, down_channel) {
+                (None, None) if old_lens.contains_key(&up) => Some((up, down)),
+                _ => None,
+            })
+            .filter(|&(up, down)| {
+                !self
+                    .connections
+                    .iter()
+                    .any(|c| c.upstream == (up, None) && c.downstream == (down, None))
+            })
+            .collect();
+        for (up, down) in bound {
+            let ops = self.reactors[up.reactor].components[up.slot].kind.port_ops_arc();
+            for i in old_lens[&up].min(old_lens[&down])..len {
+                self.connections.push(Connection {
+                    upstream: (up, Some(i)),
+                    downstream: (down, Some(i)),
+                    ops: ops.clone(),
+                });
+            }
+        }
+    }
+
+    /// Resize a single multiport, and the reference port that
+    /// stands in for it in its parent. Returns the old width.
+    fn resize_one_multiport(&mut self, addr: ComponentAddr, len: usize) -> usize {
+        let old_len = self.set_width(addr, len);
+
+        // the reference port that stands in for the multiport in the parent
+        let reference = self.reactors[addr.reactor].parent.and_then(|parent| {
+            Some(ComponentAddr {
+                reactor: parent,
+                slot: *self.reactors[parent].references.get(&addr)?,
+            })
+        });
+        if let Some(reference) = reference {
+            self.set_width(reference, len);
+            if self.reactors[addr.reactor].lowered.is_some() {
+                // the new channels are bound channel by channel,
+                // as the multiports were bound before
+                let ops = self.reactors[addr.reactor].components[addr.slot].kind.port_ops_arc();
+                let is_input = matches!(
+                    self.reactors[addr.reactor].components[addr.slot].kind,
+                    ComponentKind::Port { kind: PortKind::Input, .. }
+                );
+                for i in old_len..len {
+                    let (upstream, downstream) = if is_input {
+                        ((reference, Some(i)), (addr, Some(i)))
+                    } else {
+                        ((addr, Some(i)), (reference, Some(i)))
+                    };
+                    self.connections.push(Connection { upstream, downstream, ops: ops.clone() });
+                }
+            }
+        }
+
+        let is_removed = |(a, channel): Endpoint| (a == addr || Some(a) == reference) && channel.map_or(false, |i| i >= len);
+        self.connections
+            .retain(|c| !is_removed(c.upstream) && !is_removed(c.downstream));
+        self.declared_connections
+            .retain(|&(up, down)| !is_removed(up) && !is_removed(down));
+        old_len
+    }
+
+    /// Register a codec for the values of a port or action,
+    /// which reactions can then use to encode and decode them
+    /// (see [crate::codec]). A codec registered for a multiport
+    /// applies to all its channels, including the channels
+    /// added when it is resized.
+    pub fn register_codec<T: Send + 'static>(&mut self, component: impl ValueHandle<T>, codec: impl ValueCodec<T> + 'static) {
+        let (addr, channel) = match component.into().0 {
+            DepTarget::Component(addr, channel) => (addr, channel),
+            _ => unreachable!("handle refers to a component"),
+        };
+        let decl = &mut self.reactors[addr.reactor];
+        decl.assert_not_lowered();
+        decl.codecs.push((addr.slot, channel, erase(codec)));
+    }
+
+    /// Set the width of a multiport declaration, and return
+    /// the previous one. If the reactor is running, the multiport
+    /// is resized when mutations are applied.
+    fn set_width(&mut self, addr: ComponentAddr, len: usize) -> usize {
+        let decl = &mut self.reactors[addr.reactor];
+        let old_len = match &mut decl.components[addr.slot].kind {
+            ComponentKind::Port { width: Some(width), .. } => std::mem::replace(width, len),
+            _ => panic!("Only multiports may be resized"),
+        };
+        if decl.lowered.is_some() {
+            self.to_resize.push((addr, len));
+        }
+        old_len
+    }
+
+    /// Change the number of members of a bank of child reactors,
+    /// given its current members (see [Self::add_child_bank]),
+    /// and return the members of the resized bank. New members
+    /// are appended, with the state produced by the given function
+    /// from their bank index. When the bank shrinks, its last
+    /// members are destroyed, which is only possible in mutations
+    /// (see [Self::destroy]).
+    ///
+    /// A multiport whose channel `i` is connected to a port of
+    /// member `i`, for every member, is resized along with the
+    /// bank (see [Self::resize_multiport]). Its new channels are
+    /// connected to the port with the same name of the new members,
+    /// which must be declared before the mutation is applied.
+    ///
+    /// # Panics
+    ///
+    /// If the bank shrinks to no members and a multiport must
+    /// be resized with it.
+    pub fn resize_bank<S: Send + 'static, P>(
+        &mut self,
+        parent: ReactorHandle<P>,
+        inst_name: &'static str,
+        bank: &[ReactorHandle<S>],
+        width: usize,
+        mut state_maker: impl FnMut(/*bank_index:*/ usize) -> S,
+    ) -> Vec<ReactorHandle<S>> {
+        for (i, member) in bank.iter().enumerate() {
+            let decl = &self.reactors[member.ix];
+            assert!(
+                decl.parent == Some(parent.ix) && decl.inst_name == inst_name && decl.bank_index == Some(i),
+                "Reactor is not member {} of bank {}",
+                i,
+                inst_name
+            );
+        }
+        let connected = self.multiports_connected_to_bank(bank);
+        let mut members = bank.to_vec();
+        while members.len() > width {
+            self.destroy(members.pop().unwrap());
+        }
+        while members.len() < width {
+            let i = members.len();
+            members.push(self.add_reactor_impl(Some(parent.ix), inst_name, Some(i), state_maker(i)));
+        }
+
+        for (port_name, multiport, member_is_upstream) in connected {
+            self.resize_connected_multiports(multiport, width);
+            for (channel, member) in members.iter().enumerate().skip(bank.len()) {
+                self.bank_connections.push(BankConnection {
+                    member: member.ix,
+                    port_name: port_name.clone(),
+                    multiport,
+                    channel,
+                    member_is_upstream,
+                });
+            }
+        }
+        members
+    }
+
+    /// Returns the multiports whose channel `i` is connected to
+    /// the port of member `i` of the bank, with the name of
+    /// that port, and whether it is upstream of the channel.
+    fn multiports_connected_to_bank<S>(&self, bank: &[ReactorHandle<S>]) -> Vec<(Cow<'static, str>, ComponentAddr, bool)> {
+        let first = match bank.first() {
+            Some(first) => first.ix,
+            None => return Vec::new(),
+        };
+        let port_named = |member: usize, name: &str| {
+            let slot = self.reactors[member].components.iter().position(|c| c.name == name)?;
+            Some((ComponentAddr { reactor: member, slot }, None))
+        };
+
+        let declared: HashSet<&(Endpoint, Endpoint)> = self.declared_connections.iter().collect();
+        let mut result = Vec::new();
+        for &((up, up_channel), (down, down_channel)) in &self.declared_connections {
+            let (port, multiport, member_is_upstream) = match (up_channel, down_channel) {
+                (None, Some(0)) if up.reactor == first => (up, down, true),
+                (Some(0), None) if down.reactor == first => (down, up, false),
+                _ => continue,
+            };
+            if self.width(multiport) != bank.len() {
+                continue;
+            }
+            let name = &self.reactors[first].components[port.slot].name;
+            let all_connected = bank.iter().enumerate().all(|(i, member)| {
+                let channel = (multiport, Some(i));
+                port_named(member.ix, name).map_or(false, |port| {
+                    let connection = if member_is_upstream {
+                        (port, channel)
+                    } else {
+                        (channel, port)
+                    };
+                    declared.contains(&connection)
+                })
+            });
+            if all_connected {
+                result.push((name.clone(), multiport, member_is_upstream));
+            }
+        }
+        result
+    }
+
+    /// Connect the ports of new bank members to the channels
+    /// of the multiports resized with the bank, see [Self::resize_bank].
+    fn connect_bank_members(&mut self) {
+        for connection in std::mem::take(&mut self.bank_connections) {
+            let BankConnection {
+                member,
+                port_name,
+                multiport,
+                channel,
+                member_is_upstream,
+            } = connection;
+            let decl = &self.reactors[member];
+            if decl.destroyed || self.reactors[multiport.reactor].destroyed || channel >= self.width(multiport) {
+                continue;
+            }
+            let slot = decl
+                .components
+                .iter()
+                .position(|c| c.name == port_name && matches!(c.kind, ComponentKind::Port { width: None, .. }))
+                .unwrap_or_else(|| {
+                    panic!(
+                        "Member {} of bank {} has no port named {} to connect to its multiport",
+                        channel, decl.inst_name, port_name
+                    )
+                });
+            let port = (ComponentAddr { reactor: member, slot }, None);
+            let channel = (multiport, Some(channel));
+            if member_is_upstream {
+                self.connect_impl(port, channel)
+            } else {
+                self.connect_impl(channel, port)
+            }
+        }
+    }
+
+    /// Returns the path of the reactor, as used to name
+    /// reactors in a configuration file (see [crate::config]),
+    /// eg `main.sensors[3]`.
+    #[cfg(feature = "config")]
+    pub fn path<S>(&self, reactor: ReactorHandle<S>) -> String {
+        let mut segments = Vec::new();
+        let mut ix = Some(reactor.ix);
+        while let Some(decl) = ix.map(|ix| &self.reactors[ix]) {
+            match (decl.parent, decl.bank_index) {
+                (None, _) => segments.push("main".to_owned()),
+                (Some(_), None) => segments.push(decl.inst_name.to_owned()),
+                (Some(_), Some(i)) => segments.push(format!("{}[{}]", decl.inst_name, i)),
+            }
+            ix = decl.parent;
+        }
+        segments.reverse();
+        segments.join(".")
+    }
+
+    fn connect_impl(&mut self, upstream: Endpoint, downstream: Endpoint) {
+        assert_ne!(upstream.0, downstream.0, "Cannot connect a port to itself");
+        let ops = match &self.reactors[upstream.0.reactor].components[upstream.0.slot].kind {
+            ComponentKind::Port { ops, .. } => ops.clone(),
+            _ => unreachable!("handle is a port"),
+        };
+        self.declared_connections.push((upstream, downstream));
+        self.connections.push(Connection { upstream, downstream, ops });
+    }
+
+    /// Declare a new reaction on the given reactor. Reactions
+    /// of a reactor are ordered by priority in the order they
+    /// are declared. The returned object is used to declare
+    /// the dependencies of the reaction.
+    pub fn add_reaction<S: 'static>(
+        &mut self,
+        reactor: ReactorHandle<S>,
+        mut body: impl FnMut(&mut ReactionCtx, &mut S, &mut Components) + Send + 'static,
+    ) -> ReactionDeclarator<'_> {
+        let body: ReactionBody = Box::new(move |ctx, state, components| {
+            let state = state.downcast_mut::<S>().expect("Mismatched reactor state type");
+            body(ctx, state, components)
+        });
+        self.reactors[reactor.ix].assert_not_lowered();
+        let reactions = &mut self.reactors[reactor.ix].reactions;
+        reactions.push(ReactionDecl { label: None, body, deps: Vec::new() });
+        ReactionDeclarator {
+            reaction: reactions.len() - 1,
+            reactor: reactor.ix,
+            builder: self,
+        }
+    }
+
+    /// Resolve a dependency of a reaction of the `owner` reactor.
+    /// Ports of children are accessed through a reference port
+    /// of the owner, which is created the first time.
+    fn resolve_dependency(&mut self, owner: usize, dep: Dependency) -> DepTarget {
+        match dep.0 {
+            DepTarget::Component(addr, _) if addr.reactor == owner => dep.0,
+            DepTarget::Component(addr, channel) => {
+                let target = &self.reactors[addr.reactor];
+                assert_eq!(
+                    target.parent,
+                    Some(owner),
+                    "A reaction may only depend on components of its reactor, or ports of its children"
+                );
+                if let Some(&slot) = self.reactors[owner].references.get(&addr) {
+                    return DepTarget::Component(ComponentAddr { reactor: owner, slot }, channel);
+                }
+
+                let decl = &target.components[addr.slot];
+                let (kind, width, ops) = match &decl.kind {
+                    ComponentKind::Port { kind: PortKind::Input, width, ops } => {
+                        (PortKind::ChildInputReference, *width, ops.clone())
+                    }
+                    ComponentKind::Port { kind: PortKind::Output, width, ops } => {
+                        (PortKind::ChildOutputReference, *width, ops.clone())
+                    }
+                    _ => panic!("Only ports of child reactors may be accessed"),
+                };
+                let name = Cow::Owned(format!("{}.{}", target.inst_name, decl.name));
+                let reference = self.new_component(owner, name, ComponentKind::Port { kind, width, ops: ops.clone() });
+                self.reactors[owner].references.insert(addr, reference.slot);
+
+                let (upstream, downstream) = match kind {
+                    PortKind::ChildInputReference => (reference, addr),
+                    _ => (addr, reference),
+                };
+                self.connections.push(Connection {
+                    upstream: (upstream, None),
+                    downstream: (downstream, None),
+                    ops,
+                });
+                DepTarget::Component(reference, channel)
+            }
+            special => special,
+        }
+    }
+
+    /// Destroy the given reactor and its descendants. This may
+    /// only be used in mutations (see [Components::mutate]),
+    /// on reactors that were created before. Ports of other
+    /// reactors that were bound to their ports are unbound, and
+    /// may be bound again. Their pending events are discarded.
+    ///
+    /// # Panics
+    ///
+    /// If the reactor is the main reactor, or is not running.
+    pub fn destroy<S>(&mut self, reactor: ReactorHandle<S>) {
+        let decl = &self.reactors[reactor.ix];
+        assert!(decl.parent.is_some(), "Cannot destroy the main reactor");
+        assert!(
+            decl.lowered.is_some() && !decl.destroyed,
+            "Only running reactors may be destroyed"
+        );
+        self.to_destroy.push(reactor.ix);
+    }
+
+    /// Assemble the program and run it until it shuts down.
+    ///
+    /// # Panics
+    ///
+    /// If the program is malformed, eg if it contains
+    /// a dependency cycle, or ports bound several times.
+    pub fn run(mut self, options: SchedulerOptions) {
+        let (reactors, graph, id_registry) = self.assemble(&options);
+        SyncScheduler::run_assembled(options, reactors, graph, id_registry, Some(self))
+    }
+
+    /// Lower the declarations onto the data structures used
+    /// by the scheduler. The builder keeps what it needs to
+    /// apply mutations later.
+    pub(super) fn assemble(&mut self, options: &SchedulerOptions) -> (ReactorVec<'static>, DepGraph, DebugInfoRegistry) {
+        self.connect_bank_members();
+        let main = self
+            .reactors
+            .iter()
+            .position(|r| r.parent.is_none())
+            .expect("No main reactor was declared");
+        let main_debug = ReactorDebugInfo::root_named(self.reactors[main].type_name);
+
+        let mut root = RootAssembler::new(options);
+        let mut lowered: Vec<Option<LoweredReactor>> = (0..self.reactors.len()).map(|_| None).collect();
+        let main_id = lower_reactor(&mut self.reactors, main, main_debug, &mut root, &mut lowered);
+        assert!(
+            self.reactors.iter().all(|r| r.lowered.is_some()),
+            "Reactor is not reachable from the main reactor"
+        );
+
+        self.finish_lowering(lowered, &mut root);
+        root.debug_info.record_main_reactor(main_id);
+        root.finish()
+    }
+
+    /// Apply mutations to the running program, see [Components::mutate].
+    /// Returns the IDs of the reactors that were created.
+    pub(super) fn apply_mutations(
+        &mut self,
+        mutations: Vec<Mutation>,
+        options: &SchedulerOptions,
+        reactors: &mut ReactorVec<'static>,
+        graph: &mut DepGraph,
+        debug_info: &mut DebugInfoRegistry,
+    ) -> Vec<ReactorId> {
+        for mutation in mutations {
+            mutation(self);
+        }
+        self.connect_bank_members();
+
+        let mut root = RootAssembler::resume(
+            std::mem::take(reactors),
+            std::mem::replace(graph, DepGraph::new()),
+            std::mem::replace(debug_info, DebugInfoRegistry::new()),
+            options,
+        );
+        self.destroy_pending(&mut root);
+        self.resize_pending(&mut root);
+
+        let first_created = root.reactor_id;
+        let mut lowered: Vec<Option<LoweredReactor>> = (0..self.reactors.len()).map(|_| None).collect();
+        for ix in 0..self.reactors.len() {
+            let decl = &self.reactors[ix];
+            if decl.lowered.is_some() {
+                continue;
+            }
+            // Parents are declared before their children, so
+            // the parent is lowered, and lowers its new children.
+            let parent = &self.reactors[decl.parent.unwrap()];
+            assert!(!parent.destroyed, "Cannot create children of a destroyed reactor");
+            let parent_id = parent.id();
+            let debug = root
+                .debug_info
+                .get_debug_info(parent_id)
+                .derive_named(decl.type_name, decl.inst_name, decl.bank_index);
+            let id = lower_reactor(&mut self.reactors, ix, debug, &mut root, &mut lowered);
+            root.debug_info.record_reactor_container(parent_id, id);
+        }
+        let created = (first_created.index()..root.reactor_id.index())
+            .map(ReactorId::from_usize)
+            .collect();
+
+        self.finish_lowering(lowered, &mut root);
+        let (new_reactors, new_graph, new_debug_info) = root.finish();
+        *reactors = new_reactors;
+        *graph = new_graph;
+        *debug_info = new_debug_info;
+        created
+    }
+
+    /// Register the lowered reactors, and bind the pending connections.
+    fn finish_lowering(&mut self, lowered: Vec<Option<LoweredReactor>>, root: &mut RootAssembler) {
+        for reactor in lowered.into_iter().flatten() {
+            reactor.declare_dependencies(&mut root.graph);
+            root.register_boxed(Box::new(reactor.finish()));
+        }
+
+        for connection in std::mem::take(&mut self.connections) {
+            if let Err(e) = connection.bind(&self.reactors, root) {
+                std::panic::panic_any(e.lift(&root.debug_info))
+            }
+        }
+    }
+
+    /// Destroy the reactors passed to [Self::destroy], and their
+    /// descendants. Their components and reactions are removed
+    /// from the graph, and the ports that were bound to theirs
+    /// are unbound.
+    fn destroy_pending(&mut self, root: &mut RootAssembler) {
+        let mut destroyed = HashSet::new();
+        let mut todo = std::mem::take(&mut self.to_destroy);
+        while let Some(ix) = todo.pop() {
+            if !self.reactors[ix].destroyed && destroyed.insert(ix) {
+                todo.extend_from_slice(&self.reactors[ix].children);
+            }
+        }
+        if destroyed.is_empty() {
+            return;
+        }
+
+        let mut reactor_ids = HashSet::new();
+        let mut trigger_ids = HashSet::new();
+        for &ix in &destroyed {
+            let (id, slot_ids) = self.reactors[ix].lowered.as_ref().unwrap();
+            reactor_ids.insert(*id);
+            for ids in slot_ids {
+                trigger_ids.insert(ids.id);
+                trigger_ids.extend(ids.channels.iter().copied());
+            }
+        }
+
+        self.remove_components(root, &trigger_ids, &reactor_ids);
+        for ix in destroyed {
+            let decl = &mut self.reactors[ix];
+            decl.destroyed = true;
+            let id = decl.id();
+            trace!("Destroying reactor {}", root.debug_info.get_debug_info(id));
+            root.reactors[id] = Some(Box::new(DestroyedReactor(id)));
+        }
+        let reactors = &self.reactors;
+        self.declared_connections
+            .retain(|((up, _), (down, _))| !reactors[up.reactor].destroyed && !reactors[down.reactor].destroyed);
+    }
+
+    /// Resize the multiports passed to [Self::resize_multiport]
+    /// that belong to running reactors. Channels are removed
+    /// or created, and the reactions that depend on the whole
+    /// multiport are made to depend on the new channels.
+    fn resize_pending(&mut self, root: &mut RootAssembler) {
+        for (addr, len) in std::mem::take(&mut self.to_resize) {
+            let decl = &self.reactors[addr.reactor];
+            if decl.destroyed {
+                continue;
+            }
+            let id = decl.id();
+            let channels = decl.lowered.as_ref().unwrap().1[addr.slot].channels.clone();
+            let (kind, ops) = match &decl.components[addr.slot].kind {
+                ComponentKind::Port { kind, ops, .. } => (*kind, ops.clone()),
+                _ => unreachable!("component is a multiport"),
+            };
+
+            let mut new_channels = Vec::new();
+            if len < channels.len() {
+                let removed = channels[len..].iter().copied().collect();
+                self.remove_components(root, &removed, &HashSet::new());
+            } else {
+                let bank_id = decl.lowered.as_ref().unwrap().1[addr.slot].id;
+                for i in channels.len()..len {
+                    let name = Cow::Owned(format!("{}[{}]", decl.components[addr.slot].name, i));
+                    let channel_id = root.cur_trigger.get_and_incr().expect("Overflow while allocating ID");
+                    root.debug_info.record_late_trigger(channel_id, id, name);
+                    root.graph.add_port_bank_component(bank_id, channel_id, kind);
+                    new_channels.push(channel_id);
+                }
+                // Effects on the multiport are forwarded to the new
+                // channels by the graph, but other dependencies are not.
+                for (local, deps) in decl.reaction_deps.iter().enumerate() {
+                    let rid = GlobalReactionId::new(id, LocalReactionId::from_usize(local));
+                    for &(dep_kind, target) in deps {
+                        if target != DepTarget::Component(addr, None) {
+                            continue;
+                        }
+                        for &channel_id in &new_channels {
+                            match dep_kind {
+                                DepKind::Trigger => root.graph.triggers_reaction(channel_id, rid),
+                                DepKind::Use => root.graph.reaction_uses(rid, channel_id),
+                                DepKind::Mutable => root.graph.reaction_mutates(rid, channel_id),
+                                DepKind::Effect => {}
+                            }
+                        }
+                    }
+                }
+            }
+
+            let component = dyn_reactor_mut(root, id).components.slots[addr.slot].as_mut();
+            ops.resize_multiport(component, len, &new_channels, kind);
+            // Codecs registered for the whole multiport apply to the new channels.
+            for (channel, codec) in codecs_of(&self.reactors, addr) {
+                if channel.is_none() {
+                    for &channel_id in &new_channels {
+                        root.debug_info.record_codec(channel_id, codec.clone());
+                    }
+                }
+            }
+            let slot_ids = &mut self.reactors[addr.reactor].lowered.as_mut().unwrap().1[addr.slot];
+            slot_ids.channels.truncate(len);
+            slot_ids.channels.extend(new_channels);
+        }
+    }
+
+    /// Remove the given components and the reactions of the
+    /// given reactors from the graph, and unbind the ports that
+    /// remain from the ports that are removed.
+    fn remove_components(&self, root: &mut RootAssembler, trigger_ids: &HashSet<TriggerId>, reactor_ids: &HashSet<ReactorId>) {
+        // Locate the running ports by their ID.
+        let mut port_addrs = HashMap::new();
+        for (ix, decl) in self.reactors.iter().enumerate() {
+            if let (Some((_, slot_ids)), false) = (&decl.lowered, decl.destroyed) {
+                for (slot, ids) in slot_ids.iter().enumerate() {
+                    port_addrs.insert(ids.id, (ComponentAddr { reactor: ix, slot }, None));
+                    for (i, channel) in ids.channels.iter().enumerate() {
+                        port_addrs.insert(*channel, (ComponentAddr { reactor: ix, slot }, Some(i)));
+                    }
+                }
+            }
+        }
+
+        for cut in root.graph.remove_components(trigger_ids, reactor_ids) {
+            let port = match &cut {
+                CutBinding::Upstream { port, .. } | CutBinding::Downstream { port, .. } => port,
+            };
+            let (addr, channel) = port_addrs[port];
+            let decl = &self.reactors[addr.reactor];
+            let ops = decl.components[addr.slot].kind.port_ops();
+            let component = dyn_reactor_mut(root, decl.id()).components.slots[addr.slot].as_mut();
+            match &cut {
+                CutBinding::Upstream { downstream, .. } => ops.unbind(component, channel, downstream),
+                CutBinding::Downstream { removed, .. } => ops.forget_downstreams(component, channel, removed),
+            }
+        }
+    }
+}
+
+/// Returns the codecs registered for the component at the given
+/// address, with the channel they apply to, if not the whole
+/// component. Reference ports share the codecs of the port of
+/// the child reactor they stand in for.
+fn codecs_of(decls: &[ReactorDecl], addr: ComponentAddr) -> Vec<(Option<usize>, Arc<dyn DynCodec>)> {
+    let port = decls[addr.reactor]
+        .references
+        .iter()
+        .find(|(_, &slot)| slot == addr.slot)
+        .map_or(addr, |(port, _)| *port);
+    decls[port.reactor]
+        .codecs
+        .iter()
+        .filter(|(slot, ..)| *slot == port.slot)
+        .map(|(_, channel, codec)| (*channel, codec.clone()))
+        .collect()
+}
+
+/// Returns the reactor with the given ID, which was built by a [ProgramBuilder].
+fn dyn_reactor_mut(root: &mut RootAssembler, id: ReactorId) -> &mut DynReactor {
+    root.reactors[id]
+        .as_mut()
+        .and_then(|reactor| reactor.as_mutable())
+        .and_then(|reactor| reactor.downcast_mut())
+        .expect("Reactor was not built by a ProgramBuilder")
+}
+
+/// Lower the reactor at index `ix` and its descendants.
+/// Like in [AssemblyCtx::assemble_self], IDs are assigned
+/// depth-first: children are lowered before their parent.
+fn lower_reactor(
+    decls: &mut [ReactorDecl],
+    ix: usize,
+    debug: ReactorDebugInfo,
+    root: &mut RootAssembler,
+    lowered: &mut [Option<LoweredReactor>],
+) -> ReactorId {
+    assert!(decls[ix].lowered.is_none(), "reactor was already lowered");
+
+    let mut children_ids = Vec::with_capacity(decls[ix].children.len());
+    for child in decls[ix].children.clone() {
+        let child_decl = &decls[child];
+        let child_debug = debug.derive_named(child_decl.type_name, child_decl.inst_name, child_decl.bank_index);
+        children_ids.push(lower_reactor(decls, child, child_debug, root, lowered));
+    }
+
+    let id = root.reactor_id.get_and_incr();
+    root.debug_info.record_reactor(id, debug);
+    for child in children_ids {
+        root.debug_info.record_reactor_container(id, child);
+    }
+
+    let codecs: Vec<_> = (0..decls[ix].components.len())
+        .flat_map(|slot| {
+            let addr = ComponentAddr { reactor: ix, slot };
+            codecs_of(decls, addr)
+                .into_iter()
+                .map(move |(channel, codec)| (slot, channel, codec))
+        })
+        .collect();
+
+    let decl = &mut decls[ix];
+    let state = decl.state.take().unwrap();
+    let reactions = std::mem::take(&mut decl.reactions);
+    let references = decl.references.clone();
+
+    let first_trigger_id = root.cur_trigger;
+    let mut slots = Vec::with_capacity(decl.components.len());
+    let mut slot_ids = Vec::with_capacity(decl.components.len());
+    let mut timers = Vec::new();
+    for (slot, ComponentDecl { name, kind }) in decl.components.iter().enumerate() {
+        let (component, ids) = kind.instantiate(root, name.clone());
+        if component.as_any().is::<Timer>() {
+            timers.push(slot);
+        }
+        slots.push(component);
+        slot_ids.push(ids);
+    }
+    root.debug_info.set_id_range(id, first_trigger_id..root.cur_trigger);
+    decl.lowered = Some((id, slot_ids.clone()));
+    for (slot, channel, codec) in codecs {
+        let ids = &slot_ids[slot];
+        match channel {
+            None if !ids.channels.is_empty() => {
+                for &channel_id in &ids.channels {
+                    root.debug_info.record_codec(channel_id, codec.clone());
+                }
+            }
+            _ => root.debug_info.record_codec(ids.get(channel), codec),
+        }
+    }
+
+    // user reactions come first, then the synthesized timer reactions
+    let num_non_synthetic = reactions.len();
+    let num_reactions = num_non_synthetic + 2 * timers.len();
+    let mut reaction_ids = Vec::with_capacity(num_reactions);
+    let mut bodies = Vec::with_capacity(num_reactions);
+    let mut deps = Vec::with_capacity(num_reactions);
+    let mut prev: Option<GlobalReactionId> = None;
+    for (i, ReactionDecl { label, body, deps: reaction_deps }) in reactions.into_iter().enumerate() {
+        let rid = GlobalReactionId::new(id, LocalReactionId::from_usize(i));
+        if let Some(label) = label {
+            root.debug_info.record_reaction(rid, Cow::Borrowed(label));
+        }
+        root.graph.record_reaction(rid);
+        if let Some(prev) = prev {
+            // Add an edge that represents that the
+            // previous reaction takes precedence
+            root.graph.reaction_priority(prev, rid);
+        }
+        prev = Some(rid);
+        reaction_ids.push(rid);
+        bodies.push(body);
+        deps.push(reaction_deps);
+    }
+
+    for slot in timers {
+        let timer = DepTarget::Component(ComponentAddr { reactor: ix, slot }, None);
+
+        // startup -> bootstrap -> timer
+        let rid = GlobalReactionId::new(id, LocalReactionId::from_usize(reaction_ids.len()));
+        root.graph.record_reaction(rid);
+        reaction_ids.push(rid);
+        deps.push(vec![(DepKind::Trigger, DepTarget::Startup), (DepKind::Effect, timer)]);
+        bodies.push(Box::new(move |ctx, _, components| {
+            ctx.bootstrap_timer(components.slot_mut::<Timer>(slot))
+        }));
+
+        // timer -> reschedule, after the user reactions,
+        // which may change the period of the timer
+        let rid = GlobalReactionId::new(id, LocalReactionId::from_usize(reaction_ids.len()));
+        root.graph.record_reaction(rid);
+        if let Some(prev) = prev {
+            root.graph.reaction_priority(prev, rid);
+        }
+        reaction_ids.push(rid);
+        deps.push(vec![(DepKind::Trigger, timer)]);
+        bodies.push(Box::new(move |ctx, _, components| {
+            ctx.reschedule_timer(components.slot_mut::<Timer>(slot))
+        }));
+    }
+
+    decls[ix].reaction_deps = deps.clone();
+    lowered[ix] = Some(LoweredReactor {
+        id,
+        state,
+        slots,
+        slot_ids,
+        references,
+        owner: ix,
+        reaction_ids,
+        bodies,
+        deps,
+    });
+    id
+}
+
+/// A reactor whose components and reactions have been
+/// assigned their IDs.
+struct LoweredReactor {
+    id: ReactorId,
+    owner: usize,
+    state: Box<dyn Any + Send>,
+    slots: Vec<Box<dyn DynComponent>>,
+    slot_ids: Vec<SlotIds>,
+    references: HashMap<ComponentAddr, usize>,
+    reaction_ids: Vec<GlobalReactionId>,
+    bodies: Vec<ReactionBody>,
+    deps: Vec<Vec<(DepKind, DepTarget)>>,
+}
+
+impl LoweredReactor {
+    fn trigger_id(&self, target: DepTarget) -> TriggerId {
+        match target {
+            DepTarget::Startup => TriggerId::STARTUP,
+            DepTarget::Shutdown => TriggerId::SHUTDOWN,
+            DepTarget::Component(addr, channel) => {
+                debug_assert_eq!(addr.reactor, self.owner);
+                self.slot_ids[addr.slot].get(channel)
+            }
+        }
+    }
+
+    fn declare_dependencies(&self, graph: &mut DepGraph) {
+        for (rid, deps) in self.reaction_ids.iter().zip(&self.deps) {
+            for &(kind, target) in deps {
+                let trigger = self.trigger_id(target);
+                match kind {
+                    DepKind::Trigger => graph.triggers_reaction(trigger, *rid),
+                    DepKind::Use => graph.reaction_uses(*rid, trigger),
+                    DepKind::Mutable => graph.reaction_mutates(*rid, trigger),
+                    // Edges from reactions to actions are not represented,
+                    // see the doc of DepGraph
+                    DepKind::Effect if self.is_action(target) => graph.reaction_schedules(*rid, trigger),
+                    DepKind::Effect => graph.reaction_effects(*rid, trigger),
+                }
+            }
+        }
+    }
+
+    fn is_action(&self, target: DepTarget) -> bool {
+        match target {
+            DepTarget::Component(addr, _) => self.slot_ids[addr.slot].is_action,
+            _ => false,
+        }
+    }
+
+    fn finish(self) -> DynReactor {
+        let LoweredReactor { id, owner, state, slots, references, bodies, .. } = self;
+        DynReactor {
+            id,
+            state,
+            components: Components { owner, slots, references, mutations: Vec::new() },
+            reactions: bodies,
+        }
+    }
+}
+
+/// IDs allocated for a component.
+#[derive(Clone)]
+struct SlotIds {
+    id: TriggerId,
+    channels: Vec<TriggerId>,
+    is_action: bool,
+}
+
+impl SlotIds {
+    fn get(&self, channel: Option<usize>) -> TriggerId {
+        match channel {
+            None => self.id,
+            Some(i) => self.channels[i],
+        }
+    }
+}
+
+struct ReactorDecl {
+    type_name: &'static str,
+    inst_name: &'static str,
+    bank_index: Option<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// Moved to the reactor when it is lowered.
+    state: Option<Box<dyn Any + Send>>,
+    components: Vec<ComponentDecl>,
+    /// Maps ports of children to the slot of the
+    /// reference port that stands in for them in this reactor.
+    references: HashMap<ComponentAddr, usize>,
+    /// Moved to the reactor when it is lowered.
+    reactions: Vec<ReactionDecl>,
+    /// The dependencies of each reaction, once the reactor
+    /// has been lowered. This includes synthesized reactions.
+    reaction_deps: Vec<Vec<(DepKind, DepTarget)>>,
+    /// Codecs registered for the components of this reactor,
+    /// by slot and channel, see [ProgramBuilder::register_codec].
+    codecs: Vec<(usize, Option<usize>, Arc<dyn DynCodec>)>,
+    /// The ID of the reactor and of its components,
+    /// once it has been lowered.
+    lowered: Option<(ReactorId, Vec<SlotIds>)>,
+    /// Whether the reactor was destroyed by a mutation.
+    destroyed: bool,
+}
+
+impl ReactorDecl {
+    fn id(&self) -> ReactorId {
+        self.lowered.as_ref().expect("Reactor is not running").0
+    }
+
+    fn assert_not_lowered(&self) {
+        assert!(
+            self.lowered.is_none(),
+            "Components and reactions cannot be added to a running reactor"
+        );
+    }
+}
+
+struct ComponentDecl {
+    name: Cow<'static, str>,
+    kind: ComponentKind,
+}
+
+enum ComponentKind {
+    Port {
+        kind: PortKind,
+        /// Some if this is a multiport
+        width: Option<usize>,
+        ops: Arc<dyn PortOps>,
+    },
+    Action {
+        make: Box<dyn Fn(TriggerId) -> Box<dyn DynComponent>>,
+        physical: bool,
+    },
+    Timer {
+        offset: Duration,
+        period: Duration,
+    },
+}
+
+impl ComponentKind {
+    fn port_ops(&self) -> &dyn PortOps {
+        match self {
+            ComponentKind::Port { ops, .. } => ops.as_ref(),
+            _ => unreachable!("component is a port"),
+        }
+    }
+
+    fn port_ops_arc(&self) -> Arc<dyn PortOps> {
+        match self {
+            ComponentKind::Port { ops, .. } => ops.clone(),
+            _ => unreachable!("component is a port"),
+        }
+    }
+
+    /// Allocate IDs for the component and create it.
+    fn instantiate(&self, root: &mut RootAssembler, name: Cow<'static, str>) -> (Box<dyn DynComponent>, SlotIds) {
+        match *self {
+            ComponentKind::Port { kind, width: None, ref ops } => {
+                let id = next_comp_id(root, name);
+                root.graph.record_port(id, kind);
+                (ops.new_port(id, kind), SlotIds { id, channels: vec![], is_action: false })
+            }
+            ComponentKind::Port { kind, width: Some(len), ref ops } => {
+                let bank_id = next_comp_id(root, name.clone());
+                if let Err(e) = root.graph.record_port_bank(bank_id, len) {
+                    std::panic::panic_any(e.lift(&root.debug_info))
+                }
+                let channels: Vec<TriggerId> = (0..len)
+                    .map(|i| {
+                        let channel_id = next_comp_id(root, Cow::Owned(format!("{}[{}]", name, i)));
+                        root.graph.record_port_bank_component(bank_id, channel_id, kind);
+                        channel_id
+                    })
+                    .collect();
+                (
+                    ops.new_multiport(bank_id, &channels, kind),
+                    SlotIds { id: bank_id, channels, is_action: false },
+                )
+            }
+            ComponentKind::Action { ref make, physical } => {
+                let id = next_comp_id(root, name);
+                if physical {
+                    root.graph.record_paction(id);
+                } else {
+                    root.graph.record_laction(id);
+                }
+                (make(id), SlotIds { id, channels: vec![], is_action: true })
+            }
+            ComponentKind::Timer { offset, period } => {
+                let id = next_comp_id(root, name);
+                root.graph.record_timer(id);
+                (
+                    Box::new(Timer::new(id, offset, period)),
+                    SlotIds { id, channels: vec![], is_action: false },
+                )
+            }
+        }
+    }
+}
+
+/// Create and return a new id for a trigger component.
+fn next_comp_id(root: &mut RootAssembler, debug_name: Cow<'static, str>) -> TriggerId {
+    let id = root.cur_trigger.get_and_incr().expect("Overflow while allocating ID");
+    root.debug_info.record_trigger(id, debug_name);
+    id
+}
+
+struct ReactionDecl {
+    label: Option<&'static str>,
+    body: ReactionBody,
+    deps: Vec<(DepKind, DepTarget)>,
+}
+
+type ReactionBody = Box<dyn FnMut(&mut ReactionCtx, &mut (dyn Any + Send), &mut Components) + Send>;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum DepKind {
+    Trigger,
+    Use,
+    /// A use that may take or mutate the value.
+    Mutable,
+    Effect,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum DepTarget {
+    Startup,
+    Shutdown,
+    /// A component, and optionally a channel of a multiport.
+    Component(ComponentAddr, Option<usize>),
+}
+
+/// Address of a component in a [ProgramBuilder]: the index of
+/// its reactor, and its index within that reactor.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+struct ComponentAddr {
+    reactor: usize,
+    slot: usize,
+}
+
+/// A port, or a channel of a multiport if the index is Some.
+/// A multiport without index stands for all its channels.
+type Endpoint = (ComponentAddr, Option<usize>);
+
+/// A binding between two ports (or all channels of two multiports).
+struct Connection {
+    upstream: Endpoint,
+    downstream: Endpoint,
+    ops: Arc<dyn PortOps>,
+}
+
+/// A connection to make between the port of a new bank member
+/// and a channel of a multiport, see [ProgramBuilder::resize_bank].
+struct BankConnection {
+    /// Index of the member in the builder.
+    member: usize,
+    port_name: Cow<'static, str>,
+    multiport: ComponentAddr,
+    channel: usize,
+    /// Whether the port of the member is upstream of the channel.
+    member_is_upstream: bool,
+}
+
+impl Connection {
+    fn bind(self, decls: &[ReactorDecl], root: &mut RootAssembler) -> AssemblyResult<()> {
+        let Connection {
+            upstream: (up, up_channel),
+            downstream: (down, down_channel),
+            ops,
+        } = self;
+        let (up_id, down_id) = (decls[up.reactor].id(), decls[down.reactor].id());
+        assert!(
+            !decls[up.reactor].destroyed && !decls[down.reactor].destroyed,
+            "Cannot connect ports of a destroyed reactor"
+        );
+
+        // Take the components out of their reactors, as binding
+        // the ports also needs the root assembler.
+        let mut up_slots = std::mem::take(&mut dyn_reactor_mut(root, up_id).components.slots);
+        let result = if up_id == down_id {
+            let (first, second) = up_slots.split_at_mut(up.slot.max(down.slot));
+            let (up_component, down_component) = if up.slot < down.slot {
+                (&mut first[up.slot], &mut second[0])
+            } else {
+                (&mut second[0], &mut first[down.slot])
+            };
+            ops.bind(root, up_component.as_mut(), up_channel, down_component.as_mut(), down_channel)
+        } else {
+            let mut down_slots = std::mem::take(&mut dyn_reactor_mut(root, down_id).components.slots);
+            let result = ops.bind(
+                root,
+                up_slots[up.slot].as_mut(),
+                up_channel,
+                down_slots[down.slot].as_mut(),
+                down_channel,
+            );
+            dyn_reactor_mut(root, down_id).components.slots = down_slots;
+            result
+        };
+        dyn_reactor_mut(root, up_id).components.slots = up_slots;
+        result
+    }
+}
+
+/// Typed operations on ports, which are stored without
+/// their value type in the builder.
+trait PortOps {
+    fn new_port(&self, id: TriggerId, kind: PortKind) -> Box<dyn DynComponent>;
+
+    fn new_multiport(&self, bank_id: TriggerId, channels: &[TriggerId], kind: PortKind) -> Box<dyn DynComponent>;
+
+    /// Bind the upstream to the downstream, and record the bindings in the graph.
+    fn bind(
+        &self,
+        root: &mut RootAssembler,
+        upstream: &mut dyn DynComponent,
+        up_channel: Option<usize>,
+        downstream: &mut dyn DynComponent,
+        down_channel: Option<usize>,
+    ) -> AssemblyResult<()>;
+
+    /// Unbind the port from its upstream, which was destroyed.
+    /// The given ports stay bound to it, see [Port::unbind].
+    fn unbind(&self, port: &mut dyn DynComponent, channel: Option<usize>, downstream: &[PortId]);
+
+    /// Forget the given ports, which were bound to the port
+    /// and were destroyed, see [Port::forget_downstreams].
+    fn forget_downstreams(&self, port: &mut dyn DynComponent, channel: Option<usize>, removed: &[PortId]);
+
+    /// Truncate the multiport to the given length, or append
+    /// channels with the given IDs.
+    fn resize_multiport(&self, multiport: &mut dyn DynComponent, len: usize, new_channels: &[TriggerId], kind: PortKind);
+}
+
+struct TypedPortOps<T>(PhantomData<fn() -> T>);
+
+impl<T: Sync + 'static> TypedPortOps<T> {
+    /// Select the given channel, or all of them if the
+    /// component is a multiport and no channel is specified.
+    fn select(component: &mut dyn DynComponent, channel: Option<usize>) -> Vec<&mut Port<T>> {
+        let component = component.as_any_mut();
+        if component.is::<Port<T>>() {
+            return vec![component.downcast_mut::<Port<T>>().unwrap()];
+        }
+        let multiport = component.downcast_mut::<Multiport<T>>().expect("Mismatched port type");
+        match channel {
+            Some(i) => vec![&mut multiport[i]],
+            None => multiport.iter_mut().collect(),
+        }
+    }
+}
+
+impl<T: Sync + 'static> PortOps for TypedPortOps<T> {
+    fn new_port(&self, id: TriggerId, kind: PortKind) -> Box<dyn DynComponent> {
+        Box::new(Port::<T>::new(id, kind))
+    }
+
+    fn new_multiport(&self, bank_id: TriggerId, channels: &[TriggerId], kind: PortKind) -> Box<dyn DynComponent> {
+        let ports = channels.iter().map(|id| Port::<T>::new(*id, kind)).collect();
+        Box::new(Multiport::<T>::new(ports, bank_id))
+    }
+
+    fn bind(
+        &self,
+        root: &mut RootAssembler,
+        upstream: &mut dyn DynComponent,
+        up_channel: Option<usize>,
+        downstream: &mut dyn DynComponent,
+        down_channel: Option<usize>,
+    ) -> AssemblyResult<()> {
+        let ups = Self::select(upstream, up_channel);
+        let downs = Self::select(downstream, down_channel);
+        root.check_zipped_widths(Some(ups[0].get_id()), ups.len(), Some(downs[0].get_id()), downs.len())?;
+        for (up, down) in ups.into_iter().zip(downs) {
+            up.forward_to(down)?;
+            root.graph.port_bind_untyped(up.get_id(), down.get_id());
+        }
+        Ok(())
+    }
+
+    fn unbind(&self, port: &mut dyn DynComponent, channel: Option<usize>, downstream: &[PortId]) {
+        Self::select(port, channel)[0].unbind(downstream)
+    }
+
+    fn forget_downstreams(&self, port: &mut dyn DynComponent, channel: Option<usize>, removed: &[PortId]) {
+        Self::select(port, channel)[0].forget_downstreams(removed)
+    }
+
+    fn resize_multiport(&self, multiport: &mut dyn DynComponent, len: usize, new_channels: &[TriggerId], kind: PortKind) {
+        let multiport = multiport
+            .as_any_mut()
+            .downcast_mut::<Multiport<T>>()
+            .expect("Mismatched port type");
+        multiport.truncate(len);
+        for id in new_channels {
+            multiport.push_channel(Port::new(*id, kind));
+        }
+    }
+}
+
+/// A component of a reactor built with a [ProgramBuilder].
+trait DynComponent: Any {
+    fn cleanup(&mut self, ctx: &CleanupCtx);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+macro_rules! impl_dyn_component {
+    ($t:ty, $cleanup:ident) => {
+        impl_dyn_component!($t, |ctx: &CleanupCtx, c: &mut $t| ctx.$cleanup(c));
+    };
+    ($t:ty, $cleanup:expr) => {
+        impl<T: Sync + 'static> DynComponent for $t {
+            fn cleanup(&mut self, ctx: &CleanupCtx) {
+                ($cleanup)(ctx, self)
+            }
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+            fn as_any_mut(&mut self) -> &mut dyn Any {
+                self
+            }
+        }
+    };
+}
+
+impl_dyn_component!(Port<T>, cleanup_port);
+impl_dyn_component!(Multiport<T>, cleanup_multiport);
+impl_dyn_component!(LogicalAction<T>, cleanup_logical_action);
+impl_dyn_component!(PhysicalActionRef<T>, cleanup_physical_action);
+
+impl DynComponent for Timer {
+    fn cleanup(&mut self, _ctx: &CleanupCtx) {}
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// The components of a reactor built with a [ProgramBuilder].
+/// This resolves handles given out by the builder to the
+/// actual components, so that they may be used with the
+/// API of [ReactionCtx].
+pub struct Components {
+    /// Index of the reactor in the builder.
+    owner: usize,
+    slots: Vec<Box<dyn DynComponent>>,
+    /// Maps ports of children to the slot of their reference port.
+    references: HashMap<ComponentAddr, usize>,
+    /// Mutations requested by the executing reaction, which
+    /// are forwarded to the [ReactionCtx] when it returns.
+    mutations: Vec<Mutation>,
+}
+
+impl Components {
+    fn slot_of(&self, addr: ComponentAddr) -> usize {
+        if addr.reactor == self.owner {
+            addr.slot
+        } else {
+            *self
+                .references
+                .get(&addr)
+                .expect("Component is not declared as a dependency of a reaction of this reactor")
+        }
+    }
+
+    fn slot_ref<C: 'static>(&self, slot: usize) -> &C {
+        self.slots[slot].as_any().downcast_ref().expect("Mismatched component type")
+    }
+
+    fn slot_mut<C: 'static>(&mut self, slot: usize) -> &mut C {
+        self.slots[slot]
+            .as_any_mut()
+            .downcast_mut()
+            .expect("Mismatched component type")
+    }
+
+    /// Returns the port for the given handle. If the handle
+    /// points to a channel of a multiport, returns that channel.
+    pub fn port<T: Sync + 'static>(&self, handle: PortHandle<T>) -> &Port<T> {
+        let slot = self.slot_of(handle.addr);
+        match handle.channel {
+            None => self.slot_ref::<Port<T>>(slot),
+            Some(i) => &self.slot_ref::<Multiport<T>>(slot)[i],
+        }
+    }
+
+    /// Returns the port for the given handle. See [Self::port].
+    pub fn port_mut<T: Sync + 'static>(&mut self, handle: PortHandle<T>) -> &mut Port<T> {
+        let slot = self.slot_of(handle.addr);
+        match handle.channel {
+            None => self.slot_mut::<Port<T>>(slot),
+            Some(i) => &mut self.slot_mut::<Multiport<T>>(slot)[i],
+        }
+    }
+
+    pub fn multiport<T: Sync + 'static>(&self, handle: MultiportHandle<T>) -> &Multiport<T> {
+        self.slot_ref(self.slot_of(handle.addr))
+    }
+
+    pub fn multiport_mut<T: Sync + 'static>(&mut self, handle: MultiportHandle<T>) -> &mut Multiport<T> {
+        self.slot_mut(self.slot_of(handle.addr))
+    }
+
+    pub fn logical_action<T: Sync + 'static>(&self, handle: LogicalActionHandle<T>) -> &LogicalAction<T> {
+        self.slot_ref(self.slot_of(handle.addr))
+    }
+
+    pub fn logical_action_mut<T: Sync + 'static>(&mut self, handle: LogicalActionHandle<T>) -> &mut LogicalAction<T> {
+        self.slot_mut(self.slot_of(handle.addr))
+    }
+
+    pub fn physical_action<T: Sync + 'static>(&self, handle: PhysicalActionHandle<T>) -> &PhysicalActionRef<T> {
+        self.slot_ref(self.slot_of(handle.addr))
+    }
+
+    pub fn physical_action_mut<T: Sync + 'static>(&mut self, handle: PhysicalActionHandle<T>) -> &mut PhysicalActionRef<T> {
+        self.slot_mut(self.slot_of(handle.addr))
+    }
+
+    pub fn timer(&self, handle: TimerHandle) -> &Timer {
+        self.slot_ref(self.slot_of(handle.addr))
+    }
+
+    /// Request a mutation of the program, which may create
+    /// reactors and connect their ports, or destroy reactors
+    /// (see [ProgramBuilder::destroy]). Mutations are applied
+    /// at the end of the current tag, in the order they were
+    /// requested. The startup reactions of the reactors they
+    /// create are executed at the next microstep.
+    ///
+    /// This is only offered to the reactions of programs built
+    /// with a [ProgramBuilder], as reactors assembled from
+    /// generated code cannot be mutated. Handles given out by
+    /// the builder before the program started remain valid in
+    /// mutations. Mutations requested at shutdown are ignored.
+    ///
+    /// The mutations of a tag are applied to the dependency graph
+    /// in place. Only the levels and execution plans that depend
+    /// on the nodes they changed are then recomputed, so applying
+    /// them takes time proportional to the size of the affected
+    /// part of the graph, and not to the size of the program.
+    ///
+    /// # Panics
+    ///
+    /// When the mutation is applied, if the mutation makes the
+    /// program malformed, eg by introducing a dependency cycle.
+    pub fn mutate(&mut self, mutation: impl FnOnce(&mut ProgramBuilder) + Send + 'static) {
+        self.mutations.push(Box::new(mutation));
+    }
+}
+
+/// The reactor type that programs built by a [ProgramBuilder]
+/// are made of. Reactions are dispatched dynamically.
+struct DynReactor {
+    id: ReactorId,
+    state: Box<dyn Any + Send>,
+    components: Components,
+    reactions: Vec<ReactionBody>,
+}
+
+impl ReactorBehavior for DynReactor {
+    fn id(&self) -> ReactorId {
+        self.id
+    }
+
+    fn react(&mut self, ctx: &mut ReactionCtx, local_rid: LocalReactionId) {
+        let body = &mut self.reactions[local_rid.index()];
+        body(ctx, self.state.as_mut(), &mut self.components);
+        ctx.insides.mutations.append(&mut self.components.mutations);
+    }
+
+    fn cleanup_tag(&mut self, ctx: &CleanupCtx) {
+        for component in &mut self.components.slots {
+            component.cleanup(ctx)
+        }
+    }
+
+    fn as_mutable(&mut self) -> Option<&mut dyn Any> {
+        Some(self)
+    }
+}
+
+/// Stands in for a reactor that was destroyed by a mutation,
+/// so that the IDs of other reactors stay valid.
+struct DestroyedReactor(ReactorId);
+
+impl ReactorBehavior for DestroyedReactor {
+    fn id(&self) -> ReactorId {
+        self.0
+    }
+
+    fn react(&mut self, _ctx: &mut ReactionCtx, _local_rid: LocalReactionId) {
+        unreachable!("Reactor {} was destroyed", self.0)
+    }
+
+    fn cleanup_tag(&mut self, _ctx: &CleanupCtx) {}
+}
+
+/// Declares the dependencies of a reaction, see [ProgramBuilder::add_reaction].
+/// Dependencies on ports of child reactors are allowed.
+pub struct ReactionDeclarator<'a> {
+    builder: &'a mut ProgramBuilder,
+    reactor: usize,
+    reaction: usize,
+}
+
+impl ReactionDeclarator<'_> {
+    /// Set the label of the reaction, used in debug messages.
+    pub fn with_label(self, label: &'static str) -> Self {
+        self.builder.reactors[self.reactor].reactions[self.reaction].label = Some(label);
+        self
+    }
+
+    /// Declare that the reaction is triggered by the given component.
+    pub fn triggered_by(self, trigger: impl Into<Dependency>) -> Self {
+        self.declare(DepKind::Trigger, trigger.into())
+    }
+
+    /// Declare that the reaction is triggered at startup.
+    pub fn on_startup(self) -> Self {
+        self.declare(DepKind::Trigger, Dependency(DepTarget::Startup))
+    }
+
+    /// Declare that the reaction is triggered at shutdown.
+    pub fn on_shutdown(self) -> Self {
+        self.declare(DepKind::Trigger, Dependency(DepTarget::Shutdown))
+    }
+
+    /// Declare that the reaction reads the given component,
+    /// without being triggered by it.
+    pub fn uses(self, trigger: impl Into<Dependency>) -> Self {
+        self.declare(DepKind::Use, trigger.into())
+    }
+
+    /// Declare that the reaction may take or mutate the value
+    /// of the given input port, with [ReactionCtx::take] and
+    /// similar methods. This implies [Self::uses].
+    ///
+    /// Running the program panics if another reaction may read
+    /// the value after this one, or concurrently.
+    pub fn mutates<T: Sync + 'static>(self, port: PortHandle<T>) -> Self {
+        self.declare(DepKind::Mutable, port.into())
+    }
+
+    /// Declare that the reaction sets the given port, or
+    /// schedules the given action.
+    pub fn effects(self, trigger: impl Into<Dependency>) -> Self {
+        self.declare(DepKind::Effect, trigger.into())
+    }
+
+    fn declare(self, kind: DepKind, dep: Dependency) -> Self {
+        let target = self.builder.resolve_dependency(self.reactor, dep);
+        self.builder.reactors[self.reactor].reactions[self.reaction]
+            .deps
+            .push((kind, target));
+        self
+    }
+}
+
+/// Something a reaction may declare a dependency on.
+/// This is created from the handles of a [ProgramBuilder].
+#[derive(Copy, Clone, Debug)]
+pub struct Dependency(DepTarget);
+
+/// Handle to a reactor declared on a [ProgramBuilder].
+/// The type parameter is the type of the reactor's state.
+pub struct ReactorHandle<S> {
+    ix: usize,
+    _s: PhantomData<fn() -> S>,
+}
+
+/// Handle to a port declared on a [ProgramBuilder].
+pub struct PortHandle<T> {
+    addr: ComponentAddr,
+    /// Some if this is a channel of a multiport.
+    channel: Option<usize>,
+    _t: PhantomData<fn() -> T>,
+}
+
+/// Handle to a multiport declared on a [ProgramBuilder].
+pub struct MultiportHandle<T> {
+    addr: ComponentAddr,
+    len: usize,
+    _t: PhantomData<fn() -> T>,
+}
+
+impl<T> MultiportHandle<T> {
+    /// Returns the number of channels.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the multiport has no channels.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a handle to the channel at the given index.
+    pub fn channel(&self, index: usize) -> PortHandle<T> {
+        assert!(index < self.len, "Channel index out of bounds: {} >= {}", index, self.len);
+        PortHandle {
+            addr: self.addr,
+            channel: Some(index),
+            _t: PhantomData,
+        }
+    }
+
+    /// Iterate over the handles of all channels.
+    pub fn channels(&self) -> impl Iterator<Item = PortHandle<T>> + '_ {
+        (0..self.len).map(|i| self.channel(i))
+    }
+}
+
+/// Handle to a logical action declared on a [ProgramBuilder].
+pub struct LogicalActionHandle<T> {
+    addr: ComponentAddr,
+    _t: PhantomData<fn() -> T>,
+}
+
+/// Handle to a physical action declared on a [ProgramBuilder].
+pub struct PhysicalActionHandle<T> {
+    addr: ComponentAddr,
+    _t: PhantomData<fn() -> T>,
+}
+
+/// Handle to a timer declared on a [ProgramBuilder].
+#[derive(Copy, Clone)]
+pub struct TimerHandle {
+    addr: ComponentAddr,
+}
+
+// Derives would require T: Copy.
+macro_rules! impl_handle_copy {
+    ($($handle:ident),*) => {$(
+        impl<T> Clone for $handle<T> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<T> Copy for $handle<T> {}
+    )*};
+}
+
+impl_handle_copy!(
+    ReactorHandle,
+    PortHandle,
+    MultiportHandle,
+    LogicalActionHandle,
+    PhysicalActionHandle
+);
+
+impl<T> From<PortHandle<T>> for Dependency {
+    fn from(handle: PortHandle<T>) -> Self {
+        Dependency(DepTarget::Component(handle.addr, handle.channel))
+    }
+}
+
+impl<T> From<MultiportHandle<T>> for Dependency {
+    fn from(handle: MultiportHandle<T>) -> Self {
+        Dependency(DepTarget::Component(handle.addr, None))
+    }
+}
+
+impl<T> From<LogicalActionHandle<T>> for Dependency {
+    fn from(handle: LogicalActionHandle<T>) -> Self {
+        Dependency(DepTarget::Component(handle.addr, None))
+    }
+}
+
+impl<T> From<PhysicalActionHandle<T>> for Dependency {
+    fn from(handle: PhysicalActionHandle<T>) -> Self {
+        Dependency(DepTarget::Component(handle.addr, None))
+    }
+}
+
+/// Handles to the components that hold values of type `T`,
+/// for which a codec may be registered (see [ProgramBuilder::register_codec]).
+pub trait ValueHandle<T>: Into<Dependency> {}
+
+impl<T> ValueHandle<T> for PortHandle<T> {}
+impl<T> ValueHandle<T> for MultiportHandle<T> {}
+impl<T> ValueHandle<T> for LogicalActionHandle<T> {}
+impl<T> ValueHandle<T> for PhysicalActionHandle<T> {}
+
+impl From<TimerHandle> for Dependency {
+    fn from(handle: TimerHandle) -> Self {
+        Dependency(DepTarget::Component(handle.addr, None))
+    }
+}
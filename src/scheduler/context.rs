@@ -1,14 +1,18 @@
 use std::borrow::Borrow;
+use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread::JoinHandle;
+use std::time::SystemTime;
 
 use crossbeam_channel::reconnectable::{Receiver, SendError, Sender};
 use smallvec::SmallVec;
 
+use super::builder_impl::Mutation;
 use super::*;
 use crate::assembly::*;
+use crate::codec::{check_type as check_codec_type, CodecError, DynCodec};
 use crate::scheduler::dependencies::{DataflowInfo, ExecutableReactions, LevelIx};
 use crate::*;
 
@@ -39,6 +43,8 @@ pub struct ReactionCtx<'a, 'x> {
 
     /// Start time of the program.
     initial_time: Instant,
+    /// Wall-clock time corresponding to [Self::initial_time].
+    initial_time_utc: SystemTime,
 
     // globals, also they might be copied and passed to AsyncCtx
     dataflow: &'x DataflowInfo,
@@ -49,6 +55,12 @@ pub struct ReactionCtx<'a, 'x> {
     /// It duplicates [Self::was_terminated_atomic], to avoid an atomic
     /// operation within [Self::is_shutdown].
     was_terminated: bool,
+    /// Tag of the earliest pending event when this tag started,
+    /// see [Self::get_next_event_tag].
+    pub(super) next_event_tag: Option<EventTag>,
+    /// Scheduled shutdown tag when this tag started,
+    /// see [Self::get_shutdown_tag].
+    pub(super) shutdown_tag: Option<EventTag>,
 }
 
 impl<'a, 'x> ReactionCtx<'a, 'x> {
@@ -60,6 +72,14 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
         self.initial_time
     }
 
+    /// Returns the wall-clock time at which the execution
+    /// of this program started, which corresponds to
+    /// [Self::get_start_time].
+    #[inline]
+    pub fn get_start_time_utc(&self) -> SystemTime {
+        self.initial_time_utc
+    }
+
     /// Returns the current physical time.
     ///
     /// Repeated invocation of this method may produce different
@@ -70,6 +90,16 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
         Instant::now()
     }
 
+    /// Returns the current wall-clock time.
+    ///
+    /// Unlike [Self::get_physical_time], this is not monotonic,
+    /// as the system clock may be adjusted while the program runs.
+    /// It may therefore drift from [Self::get_logical_time_utc].
+    #[inline]
+    pub fn get_physical_time_utc(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
     /// Returns the current logical time.
     ///
     /// Logical time is frozen during the execution of a reaction.
@@ -80,6 +110,17 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
         self.tag.to_logical_time(self.get_start_time())
     }
 
+    /// Returns the current logical time as a wall-clock time,
+    /// that is, the [start time](Self::get_start_time_utc)
+    /// plus the offset of the current tag.
+    ///
+    /// Like [Self::get_logical_time], this is frozen during
+    /// the execution of a reaction.
+    #[inline]
+    pub fn get_logical_time_utc(&self) -> SystemTime {
+        self.tag.to_utc_time(self.initial_time_utc)
+    }
+
     /// Returns the tag at which the reaction executes.
     ///
     /// Repeated invocation of this method will always produce
@@ -101,6 +142,41 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
         self.was_terminated
     }
 
+    /// Returns the tag of the earliest event that is pending
+    /// after the current tag, if any. This lets a reaction
+    /// know how long the program may stay idle, eg to put
+    /// hardware to sleep.
+    ///
+    /// This is the state of the event queue when the current
+    /// tag started. Events scheduled during the current tag,
+    /// including the next triggering of the timers that trigger
+    /// at this tag, are not taken into account, nor are the
+    /// physical actions that may be triggered at any time.
+    /// Events after the [shutdown tag](Self::get_shutdown_tag)
+    /// are not reported either, as they will not be processed.
+    ///
+    /// Repeated invocation of this method will always produce
+    /// the same value.
+    #[inline]
+    pub fn get_next_event_tag(&self) -> Option<EventTag> {
+        self.next_event_tag
+    }
+
+    /// Returns the tag at which the program will shut down,
+    /// if it is already known, eg because a timeout was given
+    /// or a stop was requested at an earlier tag. At the
+    /// shutdown tag, this is the current tag.
+    ///
+    /// A stop requested at the current tag (see [Self::request_stop])
+    /// is only known when its tag is processed.
+    ///
+    /// Repeated invocation of this method will always produce
+    /// the same value.
+    #[inline]
+    pub fn get_shutdown_tag(&self) -> Option<EventTag> {
+        self.shutdown_tag
+    }
+
     /// Returns the amount of logical time elapsed since the
     /// start of the program. This does not take microsteps
     /// into account.
@@ -159,6 +235,7 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
     /// ```
     #[inline]
     pub fn get<T: Copy>(&self, container: &impl ReactionTrigger<T>) -> Option<T> {
+        self.verify_access(container, Access::Read);
         container.borrow().get_value(&self.get_tag(), &self.get_start_time())
     }
 
@@ -185,6 +262,7 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
     #[inline]
     #[cfg(not(feature = "no-unsafe"))]
     pub fn get_ref<'q, T>(&self, container: &'q impl crate::triggers::ReactionTriggerWithRefAccess<T>) -> Option<&'q T> {
+        self.verify_access(container, Access::Read);
         container.get_value_ref(&self.get_tag(), &self.get_start_time())
     }
 
@@ -215,6 +293,7 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
     /// See also the similar [Self::use_ref_opt].
     #[inline]
     pub fn use_ref<T, O>(&self, container: &impl ReactionTrigger<T>, action: impl FnOnce(Option<&T>) -> O) -> O {
+        self.verify_access(container, Access::Read);
         container
             .borrow()
             .use_value_ref(&self.get_tag(), &self.get_start_time(), action)
@@ -229,6 +308,110 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
         self.use_ref(container, |c| c.map(action))
     }
 
+    /// Returns the codec registered for the values of the
+    /// component with the given ID, if any. See [crate::codec].
+    pub fn codec(&self, component: TriggerId) -> Option<&dyn DynCodec> {
+        self.debug_info.id_registry.get_codec(component)
+    }
+
+    /// Encodes the current value of the port or action with
+    /// its registered codec, see [crate::codec]. Returns None
+    /// if the value is absent.
+    ///
+    /// ### Examples
+    ///
+    /// ```no_run
+    /// # use reactor_rt::{ReactionCtx, Port};
+    /// # let ctx: &mut ReactionCtx = unimplemented!();
+    /// # let port: &Port<u32> = unimplemented!();
+    /// if let Some(bytes) = ctx.encode(port).unwrap() {
+    ///     // send the bytes to another process
+    /// }
+    /// ```
+    pub fn encode<T: 'static>(&self, container: &impl ReactionTrigger<T>) -> Result<Option<Vec<u8>>, CodecError> {
+        let codec = self.codec(container.get_id()).ok_or(CodecError::Unregistered)?;
+        check_codec_type::<T>(codec)?;
+        self.use_ref(container, |value| value.map(|v| codec.encode_any(v)).transpose())
+    }
+
+    /// Decodes a value of the port or action with its registered
+    /// codec, see [crate::codec]. The value is not set, this
+    /// may be done with [Self::set] or [Self::schedule_with_v].
+    pub fn decode<T: 'static>(&self, container: &impl ReactionTrigger<T>, bytes: &[u8]) -> Result<T, CodecError> {
+        let codec = self.codec(container.get_id()).ok_or(CodecError::Unregistered)?;
+        check_codec_type::<T>(codec)?;
+        let value = codec.decode_any(bytes)?;
+        Ok(*value.downcast::<T>().expect("type was checked"))
+    }
+
+    /// Returns a new reference to the token held by the
+    /// port or action, if it is present. The value of the
+    /// token is not copied. See [Token].
+    #[inline]
+    pub fn get_token<T>(&self, container: &impl ReactionTrigger<Token<T>>) -> Option<Token<T>> {
+        self.use_ref_opt(container, Token::clone)
+    }
+
+    /// Moves the value out of a port, which then appears absent
+    /// to the reaction for the rest of the tag. This avoids copying
+    /// values that are not Copy.
+    ///
+    /// The reaction must have declared the port as a mutable
+    /// input (see [crate::assembly::DependencyDeclarator::declare_mutable_input]).
+    /// This is checked at assembly time: every other reaction that
+    /// may read the value must be executed before this one.
+    /// Using this method on other ports panics in debug builds,
+    /// and has unspecified results otherwise.
+    ///
+    /// ### Examples
+    ///
+    /// ```no_run
+    /// # use reactor_rt::{ReactionCtx, Port};
+    /// # let ctx: &mut ReactionCtx = panic!();
+    /// # let input: &mut Port<Vec<u8>> = panic!();
+    /// if let Some(mut buf) = ctx.take(input) {
+    ///     buf.push(0);
+    ///     // ...
+    /// }
+    /// ```
+    #[inline]
+    pub fn take<T: Sync>(&self, port: &mut Port<T>) -> Option<T> {
+        if cfg!(debug_assertions) {
+            self.check_mutate_port_is_legal(port)
+        }
+        self.verify_access(port, Access::Mutate);
+        port.use_mut(Option::take)
+    }
+
+    /// Returns a mutable reference to the value of a port.
+    /// The same restrictions as for [Self::take] apply. The
+    /// reference cannot remove the value, so the port stays
+    /// present.
+    ///
+    /// This method is not offered when compiling with the `no-unsafe`
+    /// feature, see [Self::use_mut] instead.
+    #[inline]
+    #[cfg(not(feature = "no-unsafe"))]
+    pub fn get_mut<'q, T: Sync>(&self, port: &'q mut Port<T>) -> Option<&'q mut T> {
+        if cfg!(debug_assertions) {
+            self.check_mutate_port_is_legal(port)
+        }
+        self.verify_access(port, Access::Mutate);
+        port.get_mut().as_mut()
+    }
+
+    /// Executes the provided closure on a mutable reference
+    /// to the value of a port. The same restrictions as for
+    /// [Self::take] apply.
+    #[inline]
+    pub fn use_mut<T: Sync, O>(&self, port: &mut Port<T>, action: impl FnOnce(Option<&mut T>) -> O) -> O {
+        if cfg!(debug_assertions) {
+            self.check_mutate_port_is_legal(port)
+        }
+        self.verify_access(port, Access::Mutate);
+        port.use_mut(|opt| action(opt.as_mut()))
+    }
+
     /// Sets the value of the given port.
     ///
     /// The change is visible at the same logical time, i.e.
@@ -243,10 +426,58 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
         if cfg!(debug_assertions) {
             self.check_set_port_is_legal(port)
         }
+        self.verify_access(port, Access::Effect);
         port.set_impl(Some(value));
+        self.insides
+            .touched_reactors
+            .extend_from_slice(self.dataflow.reactors_to_clean_up(port.get_id()));
         self.enqueue_now(Cow::Borrowed(self.reactions_triggered_by(port.get_id())));
     }
 
+    /// Panics if the current reaction did not declare the
+    /// dependency on the component that the access requires.
+    #[cfg(feature = "verify-dependencies")]
+    fn verify_access(&self, component: &impl TriggerLike, access: Access) {
+        let reaction = match self.current_reaction {
+            Some(reaction) => reaction,
+            None => return,
+        };
+        let id = component.get_id();
+        let declared = self.dataflow.declared_dependencies(reaction);
+        let is_declared = declared.map_or(false, |deps| match access {
+            // reactions may read the ports they set
+            Access::Read => deps.reads.contains(&id) || deps.mutable.contains(&id) || deps.effects.contains(&id),
+            Access::Mutate => deps.mutable.contains(&id),
+            Access::Effect => deps.effects.contains(&id),
+        });
+        if !is_declared {
+            panic!(
+                "Reaction {} {} {}, which it did not declare as {}",
+                self.debug_info.display_reaction(reaction),
+                access.verb(),
+                self.debug_info.id_registry.fmt_component(id),
+                access.expected_declaration(),
+            )
+        }
+    }
+
+    #[cfg(not(feature = "verify-dependencies"))]
+    #[inline(always)]
+    fn verify_access(&self, _component: &impl TriggerLike, _access: Access) {}
+
+    fn check_mutate_port_is_legal<T: Sync>(&self, port: &Port<T>) {
+        let reaction = match self.current_reaction {
+            Some(reaction) => reaction,
+            None => return,
+        };
+        assert!(
+            self.dataflow.is_mutable_input(reaction, port.get_id()),
+            "Port {} can only be taken or mutated by reactions that declared it as a mutable input, got reaction {}",
+            self.debug_info.id_registry.fmt_component(port.get_id()),
+            self.debug_info.display_reaction(reaction),
+        );
+    }
+
     fn check_set_port_is_legal<T: Sync>(&self, port: &mut Port<T>) {
         let port_id = port.get_id();
         let port_container = self.debug_info.id_registry.get_trigger_container(port_id).unwrap();
@@ -314,6 +545,7 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
     /// If so, then it may, but must not, present a value ([Self::get]).
     #[inline]
     pub fn is_present<T>(&self, action: &impl ReactionTrigger<T>) -> bool {
+        self.verify_access(action, Access::Read);
         action.is_present(&self.get_tag(), &self.get_start_time())
     }
 
@@ -353,7 +585,9 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
     /// The action will trigger after its own implicit time delay,
     /// plus an optional additional time delay (see [Offset]). This
     /// delay is added to the current logical (resp. physical) time
-    /// for logical (resp. physical) actions.
+    /// for logical (resp. physical) actions. If the resulting tag
+    /// is too far in the future to be represented, it saturates
+    /// to [EventTag::FOREVER], and the action never triggers.
     ///
     /// ### Examples
     ///
@@ -370,17 +604,132 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
     /// ```
     #[inline]
     pub fn schedule_with_v<T: Sync>(&mut self, action: &mut impl SchedulableAsAction<T>, value: Option<T>, offset: Offset) {
+        self.verify_access(action, Access::Effect);
         action.schedule_with_v(self, value, offset)
     }
 
-    /// Add new reactions to execute later (at least 1 microstep later).
+    /// Schedule an action to trigger at the given tag, with
+    /// the given value. Contrary to [Self::schedule_with_v],
+    /// the tag is absolute and not relative to the current tag.
+    ///
+    /// The tag must be strictly after the current tag, and at
+    /// least the action's own minimum delay after it. Otherwise
+    /// the action is not scheduled and an error is returned.
+    /// An action scheduled at [EventTag::FOREVER] never triggers.
+    ///
+    /// ### Examples
+    ///
+    /// ```no_run
+    /// # use reactor_rt::prelude::*;
+    /// # let ctx: &mut ReactionCtx = panic!();
+    /// # let action: &mut LogicalAction<&'static str> = panic!();
+    /// // will be executed one second after startup with that value.
+    /// ctx.schedule_at(action, tag!(T0 + 1 sec), Some("value")).unwrap();
+    /// ```
+    pub fn schedule_at<T: Sync>(
+        &mut self,
+        action: &mut LogicalAction<T>,
+        tag: EventTag,
+        value: Option<T>,
+    ) -> Result<(), TagTooEarly> {
+        self.verify_access(action, Access::Effect);
+        let earliest = self.make_successor_tag(action.0.min_delay);
+        if tag < earliest {
+            return Err(TagTooEarly { requested: tag, earliest });
+        }
+        action.0.schedule_future_value(tag, value);
+        self.enqueue_later(action.get_id(), tag);
+        Ok(())
+    }
+
+    /// Returns the earliest tag strictly after the current tag
+    /// at which the given action is scheduled, if any. This
+    /// includes the events scheduled during the current tag.
+    ///
+    /// ### Examples
+    ///
+    /// ```no_run
+    /// # use reactor_rt::prelude::*;
+    /// # let ctx: &mut ReactionCtx = panic!();
+    /// # let action: &mut LogicalAction<u32> = panic!();
+    /// ctx.schedule_with_v(action, Some(2), after!(5 ms));
+    /// assert_eq!(ctx.next_scheduled_tag(action), Some(ctx.get_tag().successor(delay!(5 ms))));
+    /// ```
+    pub fn next_scheduled_tag<T: Sync>(&self, action: &impl SchedulableAsAction<T>) -> Option<EventTag> {
+        self.verify_access(action, Access::Read);
+        action.next_scheduled_tag(self.get_tag())
+    }
+
+    /// Returns whether the given action is scheduled at a tag
+    /// strictly after the current tag, see [Self::next_scheduled_tag].
+    #[inline]
+    pub fn is_scheduled<T: Sync>(&self, action: &impl SchedulableAsAction<T>) -> bool {
+        self.next_scheduled_tag(action).is_some()
+    }
+
+    /// Cancel the event of the given action at the given tag,
+    /// which must be strictly after the current tag. The value
+    /// scheduled for that tag is dropped, and the action is not
+    /// present at that tag anymore. Returns whether there was
+    /// an event to cancel.
     ///
-    /// This is used for actions.
+    /// The reactions triggered by the action are removed from
+    /// the event queue, unless another event was scheduled for
+    /// the same tag. In that case they still execute, but the
+    /// action is not [present](Self::is_present) then.
+    ///
+    /// To reschedule an action, cancel it and schedule it again.
+    ///
+    /// ### Examples
+    ///
+    /// ```no_run
+    /// # use reactor_rt::prelude::*;
+    /// # let ctx: &mut ReactionCtx = panic!();
+    /// # let action: &mut LogicalAction<u32> = panic!();
+    /// let tag = ctx.get_tag().successor(delay!(5 ms));
+    /// ctx.schedule_with_v(action, Some(2), after!(5 ms));
+    /// assert!(ctx.cancel(action, tag));
+    /// ```
+    pub fn cancel<T: Sync>(&mut self, action: &mut LogicalAction<T>, tag: EventTag) -> bool {
+        self.verify_access(action, Access::Effect);
+        if tag <= self.get_tag() || !action.0.cancel_value(&tag) {
+            return false;
+        }
+        self.retract_event(action.get_id(), tag);
+        true
+    }
+
+    /// Cancel all events of the given action that are scheduled
+    /// strictly after the current tag. See [Self::cancel].
+    pub fn cancel_all<T: Sync>(&mut self, action: &mut LogicalAction<T>) {
+        self.verify_access(action, Access::Effect);
+        let now = self.get_tag();
+        for tag in action.0.scheduled_tags() {
+            if tag > now && action.0.cancel_value(&tag) {
+                self.retract_event(action.get_id(), tag);
+            }
+        }
+    }
+
+    /// Remove the given trigger from its events at the given tag.
+    /// Events pushed during this tag are updated right away,
+    /// those already in the event queue are updated by the
+    /// scheduler at the end of the tag.
+    fn retract_event(&mut self, trigger: TriggerId, tag: EventTag) {
+        let dataflow = self.dataflow;
+        for evt in self.insides.future_events.iter_mut().filter(|evt| evt.tag == tag) {
+            evt.retract(trigger, dataflow);
+        }
+        self.insides.future_events.retain(|evt| !evt.is_empty());
+        self.insides.cancelled_events.push((tag, trigger));
+    }
+
+    /// Trigger the given action or timer later (at least 1 microstep later).
     #[inline]
-    pub(crate) fn enqueue_later(&mut self, downstream: &'x ExecutableReactions, tag: EventTag) {
+    pub(crate) fn enqueue_later(&mut self, trigger: TriggerId, tag: EventTag) {
         debug_assert!(tag > self.get_tag());
 
-        let evt = Event::execute(tag, Cow::Borrowed(downstream));
+        let evt = Event::trigger(tag, trigger, self.reactions_triggered_by(trigger));
         self.insides.future_events.push(evt);
     }
 
@@ -470,15 +819,83 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
         self.insides.future_events.push(evt);
     }
 
+    /// Stop the timer. It won't trigger after the current tag,
+    /// until it is [restarted](Self::restart_timer). If the timer
+    /// triggers at the current tag, it is still present for the
+    /// rest of the tag. Its next triggering is removed from the
+    /// event queue, so the reactions it triggers don't execute
+    /// then.
+    pub fn stop_timer(&mut self, timer: &Timer) {
+        let next = {
+            let mut state = timer.state();
+            state.settle(self.tag);
+            state.controlled = true;
+            std::mem::replace(&mut state.next, EventTag::NEVER)
+        };
+        self.retract_timer_event(timer, next);
+    }
+
+    /// Restart the timer, so that it triggers next after the
+    /// given offset from the current tag, then periodically. This
+    /// discards the next triggering of the timer, if any, like
+    /// [Self::stop_timer]. A zero offset triggers the timer at the
+    /// next microstep. When called at startup, this replaces the
+    /// offset the timer was declared with.
+    ///
+    /// ```no_run
+    /// # use reactor_rt::prelude::*;
+    /// # let ctx: &mut ReactionCtx = panic!();
+    /// # let timer: &Timer = panic!();
+    /// // trigger in 5 ms, then every 20 ms
+    /// ctx.set_timer_period(timer, delay!(20 ms));
+    /// ctx.restart_timer(timer, delay!(5 ms));
+    /// ```
+    pub fn restart_timer(&mut self, timer: &Timer, offset: Duration) {
+        let tag = self.make_successor_tag(offset);
+        let next = {
+            let mut state = timer.state();
+            state.settle(self.tag);
+            state.controlled = true;
+            std::mem::replace(&mut state.next, tag)
+        };
+        self.retract_timer_event(timer, next);
+        self.enqueue_later(timer.get_id(), tag);
+    }
+
+    /// Retract the event of the timer at the given tag, which
+    /// was its next triggering, if it's still to come.
+    fn retract_timer_event(&mut self, timer: &Timer, next: EventTag) {
+        if next > self.tag && next != EventTag::NEVER {
+            self.retract_event(timer.get_id(), next);
+        }
+    }
+
+    /// Change the period of the timer. The next triggering
+    /// of the timer is unchanged, the new period is used to
+    /// schedule the ones after it. If the timer triggers at the
+    /// current tag, its next triggering uses the new period.
+    /// A zero period means the timer stops after its next
+    /// triggering.
+    pub fn set_timer_period(&mut self, timer: &Timer, period: Duration) {
+        timer.state().period = period;
+    }
+
     /// Reschedule a periodic timer if need be.
     /// This is called by a reaction synthesized for each timer.
     // note: reactions can't call this as they're only passed a shared reference to a timer.
     #[doc(hidden)]
     #[inline]
     pub fn reschedule_timer(&mut self, timer: &mut Timer) {
-        if timer.is_periodic() {
-            let downstream = self.reactions_triggered_by(timer.get_id());
-            self.enqueue_later(downstream, self.make_successor_tag(timer.period));
+        let mut state = timer.state();
+        if state.next != self.tag {
+            // the timer was stopped or restarted earlier in this tag
+            return;
+        }
+        state.latest = std::mem::replace(&mut state.next, EventTag::NEVER);
+        if !state.period.is_zero() {
+            let tag = self.make_successor_tag(state.period);
+            state.next = tag;
+            self.enqueue_later(timer.get_id(), tag);
         }
     }
 
@@ -489,12 +906,19 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
     #[inline]
     pub fn bootstrap_timer(&mut self, timer: &mut Timer) {
         // we're in startup
-        let downstream = self.reactions_triggered_by(timer.get_id());
+        let mut state = timer.state();
+        if state.controlled {
+            // a startup reaction already stopped or restarted the timer
+            return;
+        }
         if timer.offset.is_zero() {
             // no offset
-            self.enqueue_now(Cow::Borrowed(downstream))
+            state.next = self.tag;
+            self.enqueue_now(Cow::Borrowed(self.reactions_triggered_by(timer.get_id())))
         } else {
-            self.enqueue_later(downstream, self.make_successor_tag(timer.offset))
+            let tag = self.make_successor_tag(timer.offset);
+            state.next = tag;
+            self.enqueue_later(timer.get_id(), tag)
         }
     }
 
@@ -516,6 +940,7 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
         rx: &'a Receiver<PhysicalEvent>,
         tag: EventTag,
         initial_time: Instant,
+        initial_time_utc: SystemTime,
         todo: ReactionPlan<'x>,
         dataflow: &'x DataflowInfo,
         debug_info: DebugInfoProvider<'a>,
@@ -523,16 +948,19 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
         was_terminated: bool,
     ) -> Self {
         Self {
-            insides: RContextForwardableStuff { todo_now: todo, future_events: Default::default() },
+            insides: RContextForwardableStuff { todo_now: todo, ..Default::default() },
             cur_level: Default::default(),
             tag,
             current_reaction: None,
             rx,
             initial_time,
+            initial_time_utc,
             dataflow,
             was_terminated_atomic,
             debug_info,
             was_terminated,
+            next_event_tag: None,
+            shutdown_tag: None,
         }
     }
 
@@ -548,11 +976,42 @@ impl<'a, 'x> ReactionCtx<'a, 'x> {
             rx: self.rx,
             cur_level: self.cur_level,
             initial_time: self.initial_time,
+            initial_time_utc: self.initial_time_utc,
             dataflow: self.dataflow,
             was_terminated: self.was_terminated,
             was_terminated_atomic: self.was_terminated_atomic,
             debug_info: self.debug_info.clone(),
             current_reaction: self.current_reaction,
+            next_event_tag: self.next_event_tag,
+            shutdown_tag: self.shutdown_tag,
+        }
+    }
+}
+
+/// Kind of access of a reaction to a component,
+/// see [ReactionCtx::verify_access].
+#[derive(Copy, Clone)]
+enum Access {
+    Read,
+    Mutate,
+    Effect,
+}
+
+#[cfg(feature = "verify-dependencies")]
+impl Access {
+    fn verb(self) -> &'static str {
+        match self {
+            Access::Read => "reads",
+            Access::Mutate => "takes or mutates",
+            Access::Effect => "sets or schedules",
+        }
+    }
+
+    fn expected_declaration(self) -> &'static str {
+        match self {
+            Access::Read => "a trigger, use or effect",
+            Access::Mutate => "a mutable input",
+            Access::Effect => "an effect",
         }
     }
 }
@@ -573,6 +1032,18 @@ pub(super) struct RContextForwardableStuff<'x> {
     /// Events that were produced for a strictly greater
     /// logical time than a current one.
     pub(super) future_events: SmallVec<[Event<'x>; 4]>,
+
+    /// Events of actions that were cancelled, and must be
+    /// removed from the event queue (see [ReactionCtx::cancel]).
+    pub(super) cancelled_events: SmallVec<[(EventTag, TriggerId); 2]>,
+
+    /// Reactors that need to be cleaned up at the end of the
+    /// tag, because some of their reactions were executed or
+    /// some of their ports were set. May contain duplicates.
+    pub(super) touched_reactors: SmallVec<[ReactorId; 16]>,
+
+    /// Mutations to apply at the end of the tag, see [crate::builder::Components::mutate].
+    pub(super) mutations: Vec<Mutation>,
 }
 
 #[cfg(feature = "parallel-runtime")]
@@ -585,6 +1056,9 @@ impl RContextForwardableStuff<'_> {
     pub(super) fn absorb(&mut self, mut other: Self) {
         self.todo_now = ExecutableReactions::merge_cows(self.todo_now.take(), other.todo_now);
         self.future_events.append(&mut other.future_events);
+        self.cancelled_events.append(&mut other.cancelled_events);
+        self.touched_reactors.append(&mut other.touched_reactors);
+        self.mutations.append(&mut other.mutations);
     }
 }
 
@@ -690,17 +1164,23 @@ impl AsyncCtx {
 
 /// Implemented by LogicalAction and PhysicalAction references
 /// to give access to [ReactionCtx::schedule] and variants.
-pub trait SchedulableAsAction<T: Sync> {
+pub trait SchedulableAsAction<T: Sync>: TriggerLike {
     #[doc(hidden)]
     fn schedule_with_v(&mut self, ctx: &mut ReactionCtx, value: Option<T>, offset: Offset);
+
+    #[doc(hidden)]
+    fn next_scheduled_tag(&self, after: EventTag) -> Option<EventTag>;
 }
 
 impl<T: Sync> SchedulableAsAction<T> for LogicalAction<T> {
     fn schedule_with_v(&mut self, ctx: &mut ReactionCtx, value: Option<T>, offset: Offset) {
         let eta = ctx.make_successor_tag(self.0.min_delay + offset.to_duration());
         self.0.schedule_future_value(eta, value);
-        let downstream = ctx.dataflow.reactions_triggered_by(&self.get_id());
-        ctx.enqueue_later(downstream, eta);
+        ctx.enqueue_later(self.get_id(), eta);
+    }
+
+    fn next_scheduled_tag(&self, after: EventTag) -> Option<EventTag> {
+        self.0.next_scheduled_tag(after)
     }
 }
 
@@ -709,13 +1189,38 @@ impl<T: Sync> SchedulableAsAction<T> for PhysicalActionRef<T> {
         self.use_mut_p(value, |action, value| {
             let tag = EventTag::absolute(ctx.initial_time, Instant::now() + offset.to_duration());
             action.0.schedule_future_value(tag, value);
-            let downstream = ctx.dataflow.reactions_triggered_by(&action.get_id());
-            ctx.enqueue_later(downstream, tag);
+            ctx.enqueue_later(action.get_id(), tag);
         })
         .ok();
     }
+
+    fn next_scheduled_tag(&self, after: EventTag) -> Option<EventTag> {
+        self.use_value(|action| action.0.next_scheduled_tag(after)).ok().flatten()
+    }
 }
 
+/// Error returned by [ReactionCtx::schedule_at] when the
+/// requested tag is not far enough in the future.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TagTooEarly {
+    /// The tag that was requested.
+    pub requested: EventTag,
+    /// The earliest tag at which the action may be scheduled.
+    pub earliest: EventTag,
+}
+
+impl Display for TagTooEarly {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Cannot schedule action at {}, the earliest possible tag is {}",
+            self.requested, self.earliest
+        )
+    }
+}
+
+impl std::error::Error for TagTooEarly {}
+
 /// An offset from the current event.
 ///
 /// This is to be used with [ReactionCtx::schedule].
@@ -786,10 +1291,7 @@ pub struct CleanupCtx {
 
 impl CleanupCtx {
     pub fn cleanup_multiport<T: Sync>(&self, port: &mut Multiport<T>) {
-        // todo bound ports don't need to be cleared
-        for channel in port {
-            channel.clear_value()
-        }
+        port.clear_present()
     }
 
     pub fn cleanup_port<T: Sync>(&self, port: &mut Port<T>) {
@@ -804,3 +1306,530 @@ impl CleanupCtx {
         action.use_mut(|a| a.0.forget_value(&self.tag)).ok();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use crate::assembly::PortKind;
+    use crate::builder::*;
+    use crate::prelude::*;
+    use crate::test::testutil::*;
+    use crate::SchedulerOptions;
+
+    #[test]
+    fn test_logical_action_and_request_stop() {
+        let log = run_logged(SchedulerOptions::default(), |builder, main, _| {
+            let act = builder.new_logical_action::<u32, _>(main, "act", None);
+            builder
+                .add_reaction(main, move |ctx, _, c| {
+                    ctx.schedule_with_v(c.logical_action_mut(act), Some(0), Asap)
+                })
+                .on_startup()
+                .effects(act);
+            builder
+                .add_reaction(main, move |ctx, log, c| {
+                    let v = ctx.get(c.logical_action(act)).unwrap();
+                    log.lock().unwrap().push(ctx.get_tag());
+                    if v < 2 {
+                        ctx.schedule_with_v(c.logical_action_mut(act), Some(v + 1), after!(1 ms));
+                    } else {
+                        ctx.request_stop(Asap);
+                    }
+                })
+                .triggered_by(act)
+                .effects(act)
+                .with_label("loop");
+        });
+
+        let tags: Vec<_> = log.iter().map(|t| t.duration_since_start()).collect();
+        assert_eq!(tags, vec![Duration::ZERO, Duration::from_millis(1), Duration::from_millis(2)]);
+    }
+
+    #[cfg(feature = "verify-dependencies")]
+    #[test]
+    #[should_panic(expected = "Reaction /0 reads /out, which it did not declare as a trigger, use or effect")]
+    fn test_undeclared_read_is_rejected() {
+        let mut builder = ProgramBuilder::new();
+        let main = builder.add_main(());
+        let out = builder.new_port::<u32, _>(main, "out", PortKind::Output);
+        builder
+            .add_reaction(main, move |ctx, _, c| {
+                ctx.get(c.port(out));
+            })
+            .on_startup();
+
+        builder.run(SchedulerOptions::default());
+    }
+
+    #[cfg(feature = "verify-dependencies")]
+    #[test]
+    #[should_panic(expected = "Reaction /0 sets or schedules /act, which it did not declare as an effect")]
+    fn test_undeclared_schedule_is_rejected() {
+        let mut builder = ProgramBuilder::new();
+        let main = builder.add_main(());
+        let act = builder.new_logical_action::<u32, _>(main, "act", None);
+        builder
+            .add_reaction(main, move |ctx, _, c| ctx.schedule(c.logical_action_mut(act), Asap))
+            .on_startup();
+        builder.add_reaction(main, |_, _, _| {}).triggered_by(act);
+
+        builder.run(SchedulerOptions::default());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(
+        expected = "Port /source.out can only be taken or mutated by reactions that declared it as a mutable input, got reaction /0"
+    )]
+    fn test_undeclared_take_is_rejected() {
+        let mut builder = ProgramBuilder::new();
+        let main = builder.add_main(());
+        let source = builder.add_child(main, "source", ());
+        let out = builder.new_port::<u32, _>(source, "out", PortKind::Output);
+        builder
+            .add_reaction(main, move |ctx, _, c| {
+                ctx.take(c.port_mut(out));
+            })
+            .triggered_by(out);
+        builder
+            .add_reaction(source, move |ctx, _, c| ctx.set(c.port_mut(out), 1))
+            .on_startup()
+            .effects(out);
+
+        builder.run(SchedulerOptions::default());
+    }
+
+    #[test]
+    fn test_utc_time_is_aligned_and_follows_logical_time() {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let options = SchedulerOptions {
+            align_start: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let log: Vec<(SystemTime, SystemTime)> = run_logged(options, |builder, main, _| {
+            let t = builder.new_timer(main, "t", Duration::from_millis(10), Duration::ZERO);
+            builder
+                .add_reaction(main, |ctx, log, _| {
+                    log.lock()
+                        .unwrap()
+                        .push((ctx.get_start_time_utc(), ctx.get_logical_time_utc()))
+                })
+                .triggered_by(t);
+        });
+
+        let (start, logical) = log[0];
+        assert_eq!(logical, start + Duration::from_millis(10));
+        assert_eq!(EventTag::from_utc_time(start, logical), Some(tag!(T0 + 10 ms)));
+        assert_eq!(start.duration_since(UNIX_EPOCH).unwrap().as_nanos() % 50_000_000, 0);
+    }
+
+    #[test]
+    fn test_cancelled_action_events_are_retracted() {
+        let log = run_logged(options_with_timeout(Duration::from_millis(100)), |builder, main, _| {
+            let timeout = builder.new_logical_action::<u32, _>(main, "timeout", None);
+            let reply = builder.new_logical_action::<(), _>(main, "reply", None);
+            builder
+                .add_reaction(main, move |ctx, _, c| {
+                    let timeout = c.logical_action_mut(timeout);
+                    for (v, ms) in [(1, 30), (2, 40), (4, 45)] {
+                        ctx.schedule_with_v(timeout, Some(v), after!(ms ms));
+                    }
+                    // cancelled before the event reaches the queue
+                    ctx.schedule_with_v(timeout, Some(9), after!(20 ms));
+                    let tag = ctx.get_tag().successor(Duration::from_millis(20));
+                    assert!(ctx.cancel(timeout, tag));
+
+                    ctx.schedule(c.logical_action_mut(reply), after!(10 ms));
+                })
+                .on_startup()
+                .effects(timeout)
+                .effects(reply);
+            builder
+                .add_reaction(main, move |ctx, _, c| {
+                    // reschedule the timeout earlier
+                    let timeout = c.logical_action_mut(timeout);
+                    let tag = ctx.get_tag().successor(Duration::from_millis(20));
+                    assert!(ctx.cancel(timeout, tag));
+                    assert!(!ctx.cancel(timeout, tag));
+                    ctx.schedule_with_v(timeout, Some(3), after!(5 ms));
+                })
+                .triggered_by(reply)
+                .effects(timeout);
+            builder
+                .add_reaction(main, move |ctx, log, c| {
+                    // no need to check whether the timeout is present
+                    let timeout = c.logical_action_mut(timeout);
+                    log.lock().unwrap().push((ctx.get_elapsed_logical_time(), ctx.get(&*timeout)));
+                    ctx.cancel_all(timeout);
+                })
+                .triggered_by(timeout)
+                .effects(timeout);
+        });
+
+        assert_eq!(log, vec![(Duration::from_millis(15), Some(3))]);
+    }
+
+    #[test]
+    fn test_cancelling_an_action_keeps_other_actions_at_the_same_tag() {
+        let log = run_logged(options_with_timeout(Duration::from_millis(100)), |builder, main, _| {
+            let a = builder.new_logical_action::<(), _>(main, "a", None);
+            let b = builder.new_logical_action::<(), _>(main, "b", None);
+            let reply = builder.new_logical_action::<(), _>(main, "reply", None);
+            builder
+                .add_reaction(main, move |ctx, _, c| {
+                    for ms in [10, 20] {
+                        ctx.schedule(c.logical_action_mut(a), after!(ms ms));
+                        ctx.schedule(c.logical_action_mut(b), after!(ms ms));
+                    }
+                    // merged with the event of `a` before reaching the queue
+                    let tag = ctx.get_tag().successor(Duration::from_millis(20));
+                    assert!(ctx.cancel(c.logical_action_mut(b), tag));
+                    ctx.schedule(c.logical_action_mut(reply), after!(5 ms));
+                })
+                .on_startup()
+                .effects(a)
+                .effects(b)
+                .effects(reply);
+            builder
+                .add_reaction(main, move |ctx, _, c| {
+                    // merged with the event of `a` in the queue
+                    let tag = ctx.get_tag().successor(Duration::from_millis(5));
+                    assert!(ctx.cancel(c.logical_action_mut(b), tag));
+                })
+                .triggered_by(reply)
+                .effects(b);
+            for (label, triggers) in [("a", vec![a]), ("b", vec![b]), ("a or b", vec![a, b])] {
+                let mut reaction = builder.add_reaction(main, move |ctx, log, _| {
+                    log.lock().unwrap().push((ctx.get_elapsed_logical_time(), label))
+                });
+                for trigger in triggers {
+                    reaction = reaction.triggered_by(trigger);
+                }
+            }
+        });
+
+        let expected: Vec<_> = [(10, "a"), (10, "a or b"), (20, "a"), (20, "a or b")]
+            .iter()
+            .map(|(ms, r)| (Duration::from_millis(*ms), *r))
+            .collect();
+        assert_eq!(log, expected);
+    }
+
+    #[test]
+    fn test_schedule_at_absolute_tag() {
+        let log = run_logged(options_with_timeout(Duration::from_millis(100)), |builder, main, _| {
+            let act = builder.new_logical_action::<u32, _>(main, "act", Some(Duration::from_millis(10)));
+            builder
+                .add_reaction(main, move |ctx, _, c| {
+                    let act = c.logical_action_mut(act);
+                    let err = ctx.schedule_at(act, tag!(T0 + 5 ms), Some(1)).unwrap_err();
+                    assert_eq!(err.earliest, tag!(T0 + 10 ms));
+                    assert!(ctx.schedule_at(act, EventTag::ORIGIN, Some(1)).is_err());
+
+                    ctx.schedule_at(act, tag!(T0 + 10 ms), Some(2)).unwrap();
+                    ctx.schedule_at(act, tag!(T0 + 25 ms, 3), Some(3)).unwrap();
+                })
+                .on_startup()
+                .effects(act);
+            builder
+                .add_reaction(main, move |ctx, log, c| {
+                    let act = c.logical_action(act);
+                    log.lock().unwrap().push((ctx.get_tag(), ctx.get(act)));
+                })
+                .triggered_by(act);
+        });
+
+        assert_eq!(log, vec![(tag!(T0 + 10 ms), Some(2)), (tag!(T0 + 25 ms, 3), Some(3))]);
+    }
+
+    #[test]
+    fn test_mutations_create_and_destroy_reactors() {
+        #[derive(Copy, Clone)]
+        enum Command {
+            Connect { channel: usize, client: u32 },
+            Disconnect { channel: usize },
+            Broadcast(u32),
+        }
+
+        let log = run_logged(options_with_timeout(Duration::from_millis(100)), |builder, main, log| {
+            let sessions: Arc<Mutex<Vec<Option<ReactorHandle<u32>>>>> = Arc::new(Mutex::new(vec![None, None]));
+            let server = builder.add_child(main, "server", sessions);
+            let broadcast = builder.new_port::<u32, _>(server, "broadcast", PortKind::Output);
+            let replies = builder.new_multiport::<u32, _>(server, "replies", PortKind::Input, 2);
+            let commands = builder.new_logical_action::<Command, _>(server, "commands", None);
+
+            builder
+                .add_reaction(server, move |ctx, _, c| {
+                    let commands = c.logical_action_mut(commands);
+                    for (ms, command) in [
+                        (10, Command::Connect { channel: 0, client: 1 }),
+                        (20, Command::Connect { channel: 1, client: 2 }),
+                        (30, Command::Broadcast(3)),
+                        (40, Command::Disconnect { channel: 0 }),
+                        (50, Command::Broadcast(5)),
+                        (60, Command::Connect { channel: 0, client: 3 }),
+                        (70, Command::Broadcast(7)),
+                    ] {
+                        ctx.schedule_with_v(commands, Some(command), after!(ms ms));
+                    }
+                })
+                .on_startup()
+                .effects(commands);
+
+            let session_log = log.clone();
+            builder
+                .add_reaction(server, move |ctx, sessions, c| {
+                    let sessions = sessions.clone();
+                    match ctx.get(c.logical_action(commands)).unwrap() {
+                        Command::Broadcast(v) => ctx.set(c.port_mut(broadcast), v),
+                        Command::Disconnect { channel } => {
+                            c.mutate(move |b| b.destroy(sessions.lock().unwrap()[channel].take().unwrap()))
+                        }
+                        Command::Connect { channel, client } => {
+                            let log = session_log.clone();
+                            c.mutate(move |b| {
+                                let session = b.add_child(main, "session", client);
+                                let input = b.new_port::<u32, _>(session, "in", PortKind::Input);
+                                let output = b.new_port::<u32, _>(session, "out", PortKind::Output);
+                                b.add_reaction(session, move |ctx, client, _| {
+                                    let msg = format!("client {} started", client);
+                                    log.lock().unwrap().push((ctx.get_tag(), msg));
+                                })
+                                .on_startup();
+                                b.add_reaction(session, move |ctx, client, c| {
+                                    let v = ctx.get(c.port(input)).unwrap();
+                                    ctx.set(c.port_mut(output), v * 10 + *client);
+                                })
+                                .triggered_by(input)
+                                .effects(output);
+                                b.connect(broadcast, input);
+                                b.connect(output, replies.channel(channel));
+                                sessions.lock().unwrap()[channel] = Some(session);
+                            })
+                        }
+                    }
+                })
+                .triggered_by(commands)
+                .effects(broadcast);
+
+            let replies_log = log.clone();
+            builder
+                .add_reaction(server, move |ctx, _, c| {
+                    let values: Vec<Option<u32>> = c.multiport(replies).iter().map(|p| ctx.get(p)).collect();
+                    replies_log.lock().unwrap().push((ctx.get_tag(), format!("{:?}", values)));
+                })
+                .triggered_by(replies);
+        });
+
+        let expected: Vec<(EventTag, String)> = vec![
+            (tag!(T0 + 10 ms, 1), "client 1 started".into()),
+            (tag!(T0 + 20 ms, 1), "client 2 started".into()),
+            (tag!(T0 + 30 ms), "[Some(31), Some(32)]".into()),
+            (tag!(T0 + 50 ms), "[None, Some(52)]".into()),
+            (tag!(T0 + 60 ms, 1), "client 3 started".into()),
+            (tag!(T0 + 70 ms), "[Some(73), Some(72)]".into()),
+        ];
+        assert_eq!(log, expected);
+    }
+
+    #[test]
+    fn test_mutations_resize_bank_and_multiports() {
+        fn add_worker(b: &mut ProgramBuilder, worker: ReactorHandle<usize>) -> (PortHandle<u32>, PortHandle<u32>) {
+            let input = b.new_port::<u32, _>(worker, "in", PortKind::Input);
+            let output = b.new_port::<u32, _>(worker, "out", PortKind::Output);
+            b.add_reaction(worker, move |ctx, index, c| {
+                let v = ctx.get(c.port(input)).unwrap();
+                ctx.set(c.port_mut(output), v * 10 + *index as u32);
+            })
+            .triggered_by(input)
+            .effects(output);
+            (input, output)
+        }
+
+        let log = run_logged(options_with_timeout(Duration::from_millis(40)), |builder, main, log| {
+            let dispatcher = builder.add_child(main, "dispatcher", ());
+            let jobs = builder.new_multiport::<u32, _>(dispatcher, "jobs", PortKind::Output, 2);
+            let results = builder.new_multiport::<u32, _>(dispatcher, "results", PortKind::Input, 2);
+            let workers = builder.add_child_bank(main, "worker", 2, |i| i);
+            for (i, &worker) in workers.iter().enumerate() {
+                let (input, output) = add_worker(builder, worker);
+                builder.connect(jobs.channel(i), input);
+                builder.connect(output, results.channel(i));
+            }
+            let workers = Arc::new(Mutex::new(workers));
+
+            let t = builder.new_timer(dispatcher, "t", Duration::from_millis(5), Duration::from_millis(10));
+            let load = builder.new_logical_action::<usize, _>(dispatcher, "load", None);
+
+            builder
+                .add_reaction(dispatcher, move |ctx, _, c| {
+                    ctx.schedule_with_v(c.logical_action_mut(load), Some(3), after!(10 ms));
+                    ctx.schedule_with_v(c.logical_action_mut(load), Some(1), after!(30 ms));
+                })
+                .on_startup()
+                .effects(load);
+            builder
+                .add_reaction(dispatcher, move |ctx, _, c| {
+                    // only the last worker is busy
+                    if let Some(port) = c.multiport_mut(jobs).iter_mut().last() {
+                        ctx.set(port, 1);
+                    }
+                })
+                .triggered_by(t)
+                .effects(jobs);
+            builder
+                .add_reaction(dispatcher, move |ctx, _, c| {
+                    let width = ctx.get(c.logical_action(load)).unwrap();
+                    let workers = workers.clone();
+                    c.mutate(move |b| {
+                        let mut workers = workers.lock().unwrap();
+                        let old_width = workers.len();
+                        // the jobs and results multiports are resized
+                        // with the bank, and connected to the new workers
+                        *workers = b.resize_bank(main, "worker", &workers, width, |i| i);
+                        for &worker in workers.iter().skip(old_width) {
+                            add_worker(b, worker);
+                        }
+                        assert_eq!(b.refresh(jobs).len(), width);
+                        assert_eq!(b.refresh(results).len(), width);
+                    })
+                })
+                .triggered_by(load);
+
+            let results_log = log.clone();
+            builder
+                .add_reaction(dispatcher, move |ctx, _, c| {
+                    let values = c.multiport(results).iter().map(|p| ctx.get(p)).collect();
+                    results_log.lock().unwrap().push((ctx.get_tag(), values));
+                })
+                .triggered_by(results);
+        });
+
+        let expected: Vec<(EventTag, Vec<Option<u32>>)> = vec![
+            (tag!(T0 + 5 ms), vec![None, Some(11)]),
+            (tag!(T0 + 15 ms), vec![None, None, Some(12)]),
+            (tag!(T0 + 25 ms), vec![None, None, Some(12)]),
+            (tag!(T0 + 35 ms), vec![Some(10)]),
+        ];
+        assert_eq!(log, expected);
+    }
+
+    #[test]
+    fn test_mutations_resize_connected_multiports() {
+        let log = run_logged(options_with_timeout(Duration::from_millis(15)), |builder, main, log| {
+            let source = builder.add_child(main, "source", ());
+            let sink = builder.add_child(main, "sink", log.clone());
+            let out = builder.new_multiport::<u32, _>(source, "out", PortKind::Output, 2);
+            let inp = builder.new_multiport::<u32, _>(sink, "in", PortKind::Input, 2);
+            builder.connect_multiports(out, inp);
+            let t = builder.new_timer(source, "t", Duration::ZERO, Duration::from_millis(10));
+
+            builder
+                .add_reaction(source, move |ctx, _, c| {
+                    for (i, port) in c.multiport_mut(out).iter_mut().enumerate() {
+                        ctx.set(port, i as u32);
+                    }
+                    if ctx.get_elapsed_logical_time().is_zero() {
+                        c.mutate(move |b| {
+                            b.resize_multiport(out, 3);
+                            assert_eq!(b.refresh(inp).len(), 3);
+                        });
+                    }
+                })
+                .triggered_by(t)
+                .effects(out);
+            builder
+                .add_reaction(sink, move |ctx, log, c| {
+                    let values = c.multiport(inp).iter().map(|p| ctx.get(p)).collect();
+                    log.lock().unwrap().push((ctx.get_tag(), values));
+                })
+                .triggered_by(inp);
+        });
+
+        let expected: Vec<(EventTag, Vec<Option<u32>>)> = vec![
+            (tag!(T0), vec![Some(0), Some(1)]),
+            (tag!(T0 + 10 ms), vec![Some(0), Some(1), Some(2)]),
+        ];
+        assert_eq!(log, expected);
+    }
+
+    #[test]
+    fn test_reactions_query_upcoming_events() {
+        let log = run_logged(options_with_timeout(Duration::from_millis(30)), |builder, main, _| {
+            let act = builder.new_logical_action::<u32, _>(main, "act", None);
+            let other = builder.new_logical_action::<(), _>(main, "other", None);
+            builder
+                .add_reaction(main, move |ctx, log, c| {
+                    assert!(!ctx.is_scheduled(c.logical_action(act)));
+                    ctx.schedule_with_v(c.logical_action_mut(act), Some(1), after!(10 ms));
+                    ctx.schedule_with_v(c.logical_action_mut(act), Some(2), after!(20 ms));
+                    ctx.schedule(c.logical_action_mut(other), after!(15 ms));
+                    ctx.schedule(c.logical_action_mut(other), after!(40 ms));
+                    let next = ctx.next_scheduled_tag(c.logical_action(act));
+                    log.lock()
+                        .unwrap()
+                        .push((ctx.get_tag(), ctx.get_next_event_tag(), next, ctx.get_shutdown_tag()));
+                })
+                .on_startup()
+                .effects(act)
+                .effects(other);
+            builder
+                .add_reaction(main, move |ctx, log, c| {
+                    let next = ctx.next_scheduled_tag(c.logical_action(act));
+                    log.lock()
+                        .unwrap()
+                        .push((ctx.get_tag(), ctx.get_next_event_tag(), next, ctx.get_shutdown_tag()));
+                })
+                .triggered_by(act)
+                .triggered_by(other)
+                .on_shutdown();
+        });
+
+        let shutdown = Some(tag!(T0 + 30 ms));
+        assert_eq!(
+            log,
+            vec![
+                // events scheduled at the current tag are not pending yet
+                (EventTag::ORIGIN, None, Some(tag!(T0 + 10 ms)), shutdown),
+                (tag!(T0 + 10 ms), Some(tag!(T0 + 15 ms)), Some(tag!(T0 + 20 ms)), shutdown),
+                (tag!(T0 + 15 ms), Some(tag!(T0 + 20 ms)), Some(tag!(T0 + 20 ms)), shutdown),
+                // the event at 40 ms is after the shutdown tag
+                (tag!(T0 + 20 ms), None, None, shutdown),
+                (tag!(T0 + 30 ms), None, None, shutdown),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_events_at_forever_never_happen() {
+        // the timeout saturates to FOREVER, and never expires
+        let options = SchedulerOptions {
+            timeout: Some(Duration::MAX),
+            fast: true,
+            ..Default::default()
+        };
+        let log = run_logged(options, |builder, main, _| {
+            let act = builder.new_logical_action::<u32, _>(main, "act", None);
+            builder
+                .add_reaction(main, move |ctx, _, c| {
+                    ctx.schedule_with_v(c.logical_action_mut(act), Some(1), After(Duration::MAX));
+                    assert!(!ctx.is_scheduled(c.logical_action(act)));
+                    assert_eq!(ctx.get_shutdown_tag(), None);
+                })
+                .on_startup()
+                .effects(act);
+            builder
+                .add_reaction(main, move |ctx, log, _| log.lock().unwrap().push(("act", ctx.get_tag())))
+                .triggered_by(act);
+            builder
+                .add_reaction(main, move |ctx, log, _| log.lock().unwrap().push(("shutdown", ctx.get_tag())))
+                .on_shutdown();
+        });
+
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].0, "shutdown");
+        assert!(log[0].1 < tag!(FOREVER));
+    }
+}
@@ -27,18 +27,21 @@ use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter, Result};
 use std::ops::Range;
+use std::sync::Arc;
 
 use crate::vecmap::VecMap;
 use index_vec::{Idx, IndexVec};
 
 use crate::assembly::{ReactorInitializer, TriggerId};
+use crate::codec::DynCodec;
 use crate::{GlobalReactionId, ReactorId};
 
 /// Maps IDs to debug information, stores all the debug info.
 /// This is built during assembly.
-/// At runtime, this is only used to format debug messages and
-/// perform debug assertions, so compactness is more important
-/// than speed of the methods.
+/// At runtime, this is only used to format debug messages,
+/// perform debug assertions, and look up the codecs of
+/// components, so compactness is more important than speed
+/// of the methods.
 pub(crate) struct DebugInfoRegistry {
     /// Maps reactor ids to their debug info.
     reactor_infos: IndexVec<ReactorId, ReactorDebugInfo>,
@@ -51,6 +54,14 @@ pub(crate) struct DebugInfoRegistry {
     /// is registered here.
     trigger_infos: IndexVec<TriggerId, Cow<'static, str>>,
 
+    /// Triggers that were added to a reactor after its ID range
+    /// was set, eg channels of a multiport resized at runtime,
+    /// mapped to their reactor and index within it.
+    late_triggers: HashMap<TriggerId, RawId>,
+
+    /// Codecs registered for the values of components.
+    codecs: HashMap<TriggerId, Arc<dyn DynCodec>>,
+
     /// Maps each reactor id to the id of its container.
     /// The main reactor is not registered.
     reactor_container: VecMap<ReactorId, ReactorId>,
@@ -76,6 +87,8 @@ impl DebugInfoRegistry {
             trigger_infos: Default::default(),
             reaction_labels: Default::default(),
             reactor_container: Default::default(),
+            late_triggers: Default::default(),
+            codecs: Default::default(),
             main_reactor: None,
         };
 
@@ -167,6 +180,8 @@ impl DebugInfoRegistry {
                 (last_reactor, max_local_idx + id.index())
             }
 
+            id if self.late_triggers.contains_key(&id) => self.late_triggers[&id],
+
             id => {
                 match self.reactor_bound.binary_search(&id) {
                     // we're the upper bound of some reactor `rid`,
@@ -191,11 +206,32 @@ impl DebugInfoRegistry {
             .unwrap_or(TriggerId::FIRST_REGULAR)
     }
 
+    /// Returns the ID the next recorded trigger will have.
+    pub(crate) fn next_trigger_id(&self) -> TriggerId {
+        self.trigger_infos.next_idx()
+    }
+
     pub(crate) fn record_trigger(&mut self, id: TriggerId, name: Cow<'static, str>) {
         let ix = self.trigger_infos.push(name);
         debug_assert_eq!(ix, id);
     }
 
+    /// Record a trigger of a reactor whose ID range was already
+    /// set (see [Self::set_id_range]).
+    pub(crate) fn record_late_trigger(&mut self, id: TriggerId, reactor: ReactorId, name: Cow<'static, str>) {
+        let local_idx = id.index() - self.get_reactor_lower_bound(reactor).index();
+        self.record_trigger(id, name);
+        self.late_triggers.insert(id, (reactor, local_idx));
+    }
+
+    pub(crate) fn record_codec(&mut self, id: TriggerId, codec: Arc<dyn DynCodec>) {
+        self.codecs.insert(id, codec);
+    }
+
+    pub(crate) fn get_codec(&self, id: TriggerId) -> Option<&dyn DynCodec> {
+        self.codecs.get(&id).map(|c| c.as_ref())
+    }
+
     pub(crate) fn record_reaction(&mut self, id: GlobalReactionId, name: Cow<'static, str>) {
         let existing = self.reaction_labels.insert(id, name);
         debug_assert!(existing.is_none())
@@ -255,27 +291,27 @@ impl ReactorDebugInfo {
     }
 
     pub(crate) fn root<R>() -> Self {
-        Self {
-            type_name: type_name::<R>(),
-            inst_name: "/",
-            inst_path: "/".into(),
-        }
+        Self::root_named(type_name::<R>())
+    }
+
+    pub(crate) fn root_named(type_name: &'static str) -> Self {
+        Self { type_name, inst_name: "/", inst_path: "/".into() }
     }
 
     pub(crate) fn derive<R: ReactorInitializer>(&self, inst_name: &'static str) -> Self {
-        Self {
-            type_name: type_name::<R::Wrapped>(),
-            inst_name,
-            inst_path: format!("{}{}/", self.inst_path, inst_name),
-        }
+        self.derive_named(type_name::<R::Wrapped>(), inst_name, None)
     }
 
     pub(crate) fn derive_bank_item<R: ReactorInitializer>(&self, inst_name: &'static str, bank_idx: usize) -> Self {
-        Self {
-            type_name: type_name::<R::Wrapped>(),
-            inst_name,
-            inst_path: format!("{}{}[{}]/", self.inst_path, inst_name, bank_idx),
-        }
+        self.derive_named(type_name::<R::Wrapped>(), inst_name, Some(bank_idx))
+    }
+
+    pub(crate) fn derive_named(&self, type_name: &'static str, inst_name: &'static str, bank_idx: Option<usize>) -> Self {
+        let inst_path = match bank_idx {
+            None => format!("{}{}/", self.inst_path, inst_name),
+            Some(i) => format!("{}{}[{}]/", self.inst_path, inst_name, i),
+        };
+        Self { type_name, inst_name, inst_path }
     }
 }
 
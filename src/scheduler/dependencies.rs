@@ -24,16 +24,17 @@
 
 use std::borrow::Cow;
 use std::collections::hash_map::Entry as HEntry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
 use std::fmt::{Debug, Display, Formatter};
-use std::ops::Range;
 use std::sync::Arc;
 
 use index_vec::{Idx, IndexVec};
 use petgraph::graph::{DiGraph, NodeIndex};
 use petgraph::visit::EdgeRef;
-use petgraph::Direction::Outgoing;
+use petgraph::Direction;
+use petgraph::Direction::{Incoming, Outgoing};
+use smallvec::SmallVec;
 use vecmap::{Entry as VEntry, KeyRef, VecMap};
 
 use super::ReactionPlan;
@@ -62,7 +63,7 @@ struct GraphNode {
 }
 
 #[derive(Debug, Hash, Eq, PartialEq, Copy, Clone)]
-enum GraphId {
+pub(crate) enum GraphId {
     Trigger(TriggerId),
     Reaction(GlobalReactionId),
 }
@@ -115,11 +116,43 @@ pub(super) struct DepGraph {
     /// Maps global IDs back to graph indices.
     ix_by_id: HashMap<GraphId, GraphIx>,
 
-    /// Map of multiport component ID -> multiport ID.
-    /// todo data structure is bad.
-    multiport_containment: HashMap<GraphId, TriggerId>,
-    /// Map of multiport ID -> range of IDs for its channels
-    multiport_ranges: VecMap<TriggerId, Range<TriggerId>>,
+    /// Map of multiport ID -> IDs of its channels. Those are
+    /// contiguous, unless the multiport was resized at runtime.
+    multiport_channels: VecMap<TriggerId, Vec<TriggerId>>,
+
+    /// Ports whose value may be taken or mutated by a reaction.
+    /// Those are checked by [Self::check_mutable_inputs].
+    mutable_inputs: Vec<(TriggerId, GlobalReactionId)>,
+
+    /// Actions scheduled by a reaction. Those are not
+    /// represented as edges (see [Self::dataflow]), but
+    /// are needed to verify the effects of reactions.
+    scheduled_actions: Vec<(GlobalReactionId, TriggerId)>,
+
+    /// Kind of each port, including multiport channels.
+    /// Only used by [Self::lint].
+    port_kinds: HashMap<TriggerId, PortKind>,
+
+    /// Widths of both sides of each zipped binding, given
+    /// as (first upstream port, upstream width, first
+    /// downstream port, downstream width). Only used by [Self::lint].
+    zipped_bindings: Vec<(PortId, usize, PortId, usize)>,
+
+    /// Same as [Self::zipped_bindings] for iterated bindings.
+    iterated_bindings: Vec<(PortId, usize, PortId, usize)>,
+
+    /// Changes made since the latest [DataflowInfo] was built
+    /// or updated, if they are tracked (see [Self::track_changes]).
+    changes: Option<GraphChanges>,
+}
+
+/// Changes made to a [DepGraph], see [DataflowInfo::update].
+#[derive(Default)]
+struct GraphChanges {
+    /// Nodes that were added, or whose edges changed.
+    touched: HashSet<GraphId>,
+    /// Nodes that were removed.
+    removed: HashSet<GraphId>,
 }
 
 impl Debug for GraphNode {
@@ -138,8 +171,13 @@ impl DepGraph {
         let mut ich = Self {
             dataflow: Default::default(),
             ix_by_id: Default::default(),
-            multiport_containment: Default::default(),
-            multiport_ranges: Default::default(),
+            multiport_channels: Default::default(),
+            mutable_inputs: Default::default(),
+            scheduled_actions: Default::default(),
+            port_kinds: Default::default(),
+            zipped_bindings: Default::default(),
+            iterated_bindings: Default::default(),
+            changes: None,
         };
         ich.record_special(TriggerId::STARTUP);
         ich.record_special(TriggerId::SHUTDOWN);
@@ -168,11 +206,12 @@ impl DepGraph {
         format!("{}", Dot::with_config(&labeled, &[Config::EdgeNoLabel]))
     }
 
-    pub(super) fn record_port(&mut self, id: TriggerId) {
-        self.record_port_impl(id);
+    pub(super) fn record_port(&mut self, id: TriggerId, kind: PortKind) {
+        self.record_port_impl(id, kind);
     }
 
-    fn record_port_impl(&mut self, id: TriggerId) -> GraphIx {
+    fn record_port_impl(&mut self, id: TriggerId, kind: PortKind) -> GraphIx {
+        self.port_kinds.insert(id, kind);
         self.record(GraphId::Trigger(id), NodeKind::Port)
     }
 
@@ -194,22 +233,25 @@ impl DepGraph {
         assert!(len > 0, "empty port bank");
         self.record(GraphId::Trigger(id), NodeKind::MultiportUpstream);
 
-        for channel_id in id
-            .iter_next_range(len)
-            .map_err(|_| AssemblyError(AssemblyErrorImpl::IdOverflow))?
-        {
-            self.multiport_containment.insert(GraphId::Trigger(channel_id), id);
-
-            // self.dataflow.add_edge(upstream_ix, channel_ix, EdgeWeight::Default);
-        }
-        self.multiport_ranges.insert(id, id.next_range(len).unwrap());
+        let channels = id.next_range(len).map_err(|_| AssemblyError(AssemblyErrorImpl::IdOverflow))?;
+        self.multiport_channels.insert(id, TriggerId::iter_range(&channels).collect());
         Ok(())
     }
 
-    pub(super) fn record_port_bank_component(&mut self, bank_id: TriggerId, channel_id: TriggerId) {
-        let channel_ix = self.record_port_impl(channel_id);
-        self.dataflow
-            .add_edge(self.get_ix(bank_id.into()), channel_ix, EdgeWeight::Default);
+    pub(super) fn record_port_bank_component(&mut self, bank_id: TriggerId, channel_id: TriggerId, kind: PortKind) {
+        let channel_ix = self.record_port_impl(channel_id, kind);
+        self.add_edge(self.get_ix(bank_id.into()), channel_ix, EdgeWeight::Default);
+    }
+
+    /// Records a channel that is added to a port bank at
+    /// runtime. Its ID needs not follow those of the other
+    /// channels. Reactions that are triggered by or use the
+    /// whole bank must be recorded again for this channel.
+    pub(super) fn add_port_bank_component(&mut self, bank_id: TriggerId, channel_id: TriggerId, kind: PortKind) {
+        self.record_port_bank_component(bank_id, channel_id, kind);
+        let mut channels = self.multiport_channels.remove(&bank_id).expect("not a port bank");
+        channels.push(channel_id);
+        self.multiport_channels.insert(bank_id, channels);
     }
 
     pub(super) fn record_laction(&mut self, id: TriggerId) {
@@ -224,30 +266,31 @@ impl DepGraph {
         self.record(GraphId::Trigger(id), NodeKind::Timer);
     }
 
+    pub(super) fn is_timer(&self, id: TriggerId) -> bool {
+        self.dataflow[self.get_ix(id.into())].kind == NodeKind::Timer
+    }
+
     pub(super) fn record_reaction(&mut self, id: GlobalReactionId) {
         self.record(GraphId::Reaction(id), NodeKind::Reaction);
     }
 
     /// Records that n > m, ie it will execute always before m.
     pub fn reaction_priority(&mut self, n: GlobalReactionId, m: GlobalReactionId) {
-        self.dataflow
-            .add_edge(self.get_ix(n.into()), self.get_ix(m.into()), EdgeWeight::Default);
+        self.add_edge(self.get_ix(n.into()), self.get_ix(m.into()), EdgeWeight::Default);
     }
 
     pub fn port_bind<T: Sync>(&mut self, p1: &Port<T>, p2: &Port<T>) {
         // upstream (settable) -> downstream (bound)
-        self.dataflow.add_edge(
+        self.add_edge(
             self.get_ix(p1.get_id().into()),
             self.get_ix(p2.get_id().into()),
             EdgeWeight::Default,
         );
     }
 
-    #[cfg(test)]
     pub fn port_bind_untyped(&mut self, p1: TriggerId, p2: TriggerId) {
         // upstream (settable) -> downstream (bound)
-        self.dataflow
-            .add_edge(self.get_ix(p1.into()), self.get_ix(p2.into()), EdgeWeight::Default);
+        self.add_edge(self.get_ix(p1.into()), self.get_ix(p2.into()), EdgeWeight::Default);
     }
 
     pub fn triggers_reaction(&mut self, trigger: TriggerId, reaction: GlobalReactionId) {
@@ -263,21 +306,53 @@ impl DepGraph {
         let reaction_ix = self.get_ix(reaction.into());
 
         if self.dataflow[trigger_ix].kind == MultiportUpstream {
-            for channel_id in TriggerId::iter_range(self.multiport_ranges.get(&trigger).unwrap()) {
+            let channels = self.multiport_channels.get(&trigger).unwrap().clone();
+            for channel_id in channels {
                 // trigger -> reaction
-                self.dataflow.add_edge(self.get_ix(channel_id.into()), reaction_ix, weight);
+                self.add_edge(self.get_ix(channel_id.into()), reaction_ix, weight);
             }
             return;
         }
 
         // trigger -> reaction
-        self.dataflow.add_edge(trigger_ix, reaction_ix, weight);
+        self.add_edge(trigger_ix, reaction_ix, weight);
+    }
+
+    /// Records that the reaction may take or mutate the value
+    /// of the port. The reaction also uses the port.
+    pub fn reaction_mutates(&mut self, reaction: GlobalReactionId, port: TriggerId) {
+        self.reaction_uses(reaction, port);
+        self.mutable_inputs.push((port, reaction));
+    }
+
+    /// Records that the reaction may schedule the action.
+    /// This does not add an edge to the graph.
+    pub fn reaction_schedules(&mut self, reaction: GlobalReactionId, action: TriggerId) {
+        self.scheduled_actions.push((reaction, action));
+        self.touch(reaction.into());
+    }
+
+    /// Records the widths of both sides of a zipped binding
+    /// (see [DependencyDeclarator::bind_ports_zip]).
+    pub fn record_zipped_binding(&mut self, upstream: PortId, up_width: usize, downstream: PortId, down_width: usize) {
+        self.zipped_bindings.push((upstream, up_width, downstream, down_width));
+    }
+
+    /// Records the widths of both sides of an iterated binding
+    /// (see [DependencyDeclarator::bind_ports_iterated]).
+    pub fn record_iterated_binding(&mut self, upstream: PortId, up_width: usize, downstream: PortId, down_width: usize) {
+        self.iterated_bindings.push((upstream, up_width, downstream, down_width));
     }
 
     pub fn reaction_effects(&mut self, reaction: GlobalReactionId, trigger: TriggerId) {
         // reaction -> trigger
-        self.dataflow
-            .add_edge(self.get_ix(reaction.into()), self.get_ix(trigger.into()), EdgeWeight::Default);
+        self.add_edge(self.get_ix(reaction.into()), self.get_ix(trigger.into()), EdgeWeight::Default);
+    }
+
+    fn add_edge(&mut self, source: GraphIx, target: GraphIx, weight: EdgeWeight) {
+        self.dataflow.add_edge(source, target, weight);
+        self.touch(self.dataflow[source].id);
+        self.touch(self.dataflow[target].id);
     }
 
     fn get_ix(&self, id: GraphId) -> GraphIx {
@@ -290,6 +365,7 @@ impl DepGraph {
             HEntry::Vacant(v) => {
                 let ix = self.dataflow.add_node(GraphNode { kind, id });
                 v.insert(ix);
+                self.touch(id);
                 ix
             }
         }
@@ -300,12 +376,148 @@ impl DepGraph {
         let node = GraphNode { kind: NodeKind::Special, id };
         self.ix_by_id.insert(id, self.dataflow.add_node(node));
     }
+
+    /// Starts recording the changes made to the graph, so that
+    /// a [DataflowInfo] can be updated instead of being rebuilt
+    /// (see [DataflowInfo::update]).
+    pub(super) fn track_changes(&mut self) {
+        self.changes = Some(GraphChanges::default());
+    }
+
+    /// Records that the node was added, or that its edges changed.
+    fn touch(&mut self, id: GraphId) {
+        if let Some(changes) = &mut self.changes {
+            changes.touched.insert(id);
+        }
+    }
+}
+
+/// A port binding that is cut when components are removed
+/// from the graph, see [DepGraph::remove_components].
+#[derive(Debug, Eq, PartialEq)]
+pub(super) enum CutBinding {
+    /// The upstream of the port was removed. The port must be
+    /// unbound, and the given ports, which are bound to it
+    /// directly or transitively, stay bound to it.
+    Upstream { port: PortId, downstream: Vec<PortId> },
+    /// The given ports, which are bound to the port directly
+    /// or transitively, were removed.
+    Downstream { port: PortId, removed: Vec<PortId> },
 }
 
 impl DepGraph {
-    pub(self) fn number_reactions_by_level(&self) -> AssemblyResult<HashMap<GlobalReactionId, LevelIx>> {
-        let toposorted = petgraph::algo::toposort(&self.dataflow, None)
-            .map_err(|_| AssemblyError(AssemblyErrorImpl::CyclicDependencyGraph))?;
+    /// Removes the given triggers, and the reactions of the
+    /// given reactors from the graph, eg because the reactors
+    /// were destroyed by a mutation. Returns the port bindings
+    /// between removed and remaining ports, all [CutBinding::Upstream]
+    /// first.
+    pub(super) fn remove_components(&mut self, triggers: &HashSet<TriggerId>, reactors: &HashSet<ReactorId>) -> Vec<CutBinding> {
+        let is_removed = |id: GraphId| match id {
+            GraphId::Trigger(id) => triggers.contains(&id),
+            GraphId::Reaction(id) => reactors.contains(&id.0.container()),
+        };
+
+        let mut upstream_cuts = Vec::new();
+        let mut downstream_cuts = Vec::new();
+        for edge in self.dataflow.edge_references() {
+            let (up, down) = (&self.dataflow[edge.source()], &self.dataflow[edge.target()]);
+            if up.kind != NodeKind::Port || down.kind != NodeKind::Port {
+                continue;
+            }
+            let (up_id, down_id) = match (up.id, down.id) {
+                (GraphId::Trigger(up_id), GraphId::Trigger(down_id)) => (up_id, down_id),
+                _ => unreachable!("ports are triggers"),
+            };
+            match (is_removed(up.id), is_removed(down.id)) {
+                (true, false) => {
+                    let mut downstream = Vec::new();
+                    self.collect_bound_ports_rec(edge.target(), &mut downstream);
+                    upstream_cuts.push(CutBinding::Upstream { port: down_id, downstream });
+                }
+                (false, true) => {
+                    let mut removed = vec![down_id];
+                    self.collect_bound_ports_rec(edge.target(), &mut removed);
+                    removed.retain(|id| triggers.contains(id));
+                    downstream_cuts.push(CutBinding::Downstream { port: up_id, removed });
+                }
+                _ => {}
+            }
+        }
+
+        let mut removed_ixs: Vec<GraphIx> = self
+            .ix_by_id
+            .iter()
+            .filter(|(id, _)| is_removed(**id))
+            .map(|(_, ix)| *ix)
+            .collect();
+        if self.changes.is_some() {
+            // the remaining neighbors lose their edges
+            let neighbors: Vec<GraphId> = removed_ixs
+                .iter()
+                .flat_map(|ix| self.dataflow.neighbors_undirected(*ix))
+                .map(|ix| self.dataflow[ix].id)
+                .filter(|id| !is_removed(*id))
+                .collect();
+            for id in neighbors {
+                self.touch(id);
+            }
+        }
+        // Removing a node moves the last node into its index,
+        // so remove them from the last one to keep indices valid.
+        removed_ixs.sort_unstable_by(|a, b| b.cmp(a));
+        for ix in removed_ixs {
+            let node = self.dataflow.remove_node(ix).unwrap();
+            self.ix_by_id.remove(&node.id);
+            if let Some(changes) = &mut self.changes {
+                changes.touched.remove(&node.id);
+                changes.removed.insert(node.id);
+            }
+            if let Some(moved) = self.dataflow.node_weight(ix) {
+                self.ix_by_id.insert(moved.id, ix);
+            }
+        }
+
+        for id in triggers {
+            self.multiport_channels.remove(id);
+            self.port_kinds.remove(id);
+        }
+        // channels may be removed from remaining port banks
+        let banks: Vec<TriggerId> = self.multiport_channels.iter().map(|(bank, _)| *bank).collect();
+        for bank in banks {
+            let mut channels = self.multiport_channels.remove(&bank).unwrap();
+            channels.retain(|channel| !triggers.contains(channel));
+            self.multiport_channels.insert(bank, channels);
+        }
+        self.mutable_inputs
+            .retain(|(port, reaction)| !triggers.contains(port) && !reactors.contains(&reaction.0.container()));
+        self.scheduled_actions
+            .retain(|(reaction, action)| !reactors.contains(&reaction.0.container()) && !triggers.contains(action));
+        self.zipped_bindings
+            .retain(|(up, _, down, _)| !triggers.contains(up) && !triggers.contains(down));
+        self.iterated_bindings
+            .retain(|(up, _, down, _)| !triggers.contains(up) && !triggers.contains(down));
+
+        upstream_cuts.extend(downstream_cuts);
+        upstream_cuts
+    }
+
+    /// Collects the ports bound to the port, directly or transitively.
+    fn collect_bound_ports_rec(&self, port: GraphIx, ports: &mut Vec<PortId>) {
+        for downstream in self.dataflow.neighbors_directed(port, Outgoing) {
+            if let GraphNode { kind: NodeKind::Port, id: GraphId::Trigger(id) } = self.dataflow[downstream] {
+                ports.push(id);
+                self.collect_bound_ports_rec(downstream, ports);
+            }
+        }
+    }
+}
+
+/// The level of each reaction, and of each trigger.
+type NodeLevels = (HashMap<GlobalReactionId, LevelIx>, HashMap<TriggerId, LevelIx>);
+
+impl DepGraph {
+    pub(self) fn number_nodes_by_level(&self) -> AssemblyResult<NodeLevels> {
+        let toposorted = petgraph::algo::toposort(&self.dataflow, None).map_err(|_| self.cycle_error())?;
 
         let mut levels = HashMap::<GraphIx, LevelIx>::with_capacity(self.dataflow.node_count());
 
@@ -321,16 +533,254 @@ impl DepGraph {
         }
 
         let mut reaction_levels = HashMap::<GlobalReactionId, LevelIx>::new();
+        let mut trigger_levels = HashMap::<TriggerId, LevelIx>::new();
 
         for ix in toposorted {
             let node = self.dataflow.node_weight(ix).unwrap();
+            let level = levels.get(&ix).cloned().unwrap();
+
+            match node.id {
+                GraphId::Reaction(id) => reaction_levels.insert(id, level),
+                GraphId::Trigger(id) => trigger_levels.insert(id, level),
+            };
+        }
+
+        Ok((reaction_levels, trigger_levels))
+    }
+
+    fn cycle_error(&self) -> AssemblyError {
+        AssemblyError(AssemblyErrorImpl::CyclicDependencyGraph(self.find_cycle()))
+    }
+
+    /// Returns the given nodes, and those reachable from
+    /// them in the given direction.
+    fn collect_reachable(&self, nodes: &[GraphIx], direction: Direction) -> HashSet<GraphIx> {
+        let mut result: HashSet<GraphIx> = nodes.iter().copied().collect();
+        let mut todo = nodes.to_vec();
+        while let Some(ix) = todo.pop() {
+            for next in self.dataflow.neighbors_directed(ix, direction) {
+                if result.insert(next) {
+                    todo.push(next);
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns the given triggers, and the ports and multiports
+    /// they are bound to, directly or transitively. Those are
+    /// the triggers whose plan goes through the given ones.
+    fn collect_upstream_triggers(&self, triggers: Vec<GraphIx>) -> HashSet<GraphIx> {
+        let mut result: HashSet<GraphIx> = triggers.iter().copied().collect();
+        let mut todo = triggers;
+        while let Some(ix) = todo.pop() {
+            if self.dataflow[ix].kind != NodeKind::Port {
+                continue;
+            }
+            for upstream in self.dataflow.neighbors_directed(ix, Incoming) {
+                if matches!(self.dataflow[upstream].id, GraphId::Trigger(_)) && result.insert(upstream) {
+                    todo.push(upstream);
+                }
+            }
+        }
+        result
+    }
+
+    /// Checks that reactions that take or mutate the value of
+    /// a port are the last ones to read it. All other reactions
+    /// that read the value must be executed before them. As bound
+    /// ports share their value, this includes the readers of all
+    /// the ports bound to the same upstream port.
+    ///
+    /// This assumes the graph is acyclic. If a region is given,
+    /// only ports that are read or mutated by reactions of that
+    /// region are checked.
+    pub(self) fn check_mutable_inputs(&self, region: Option<&HashSet<GraphIx>>) -> AssemblyResult<()> {
+        for &(port, reaction) in &self.mutable_inputs {
+            let reaction_ix = self.get_ix(reaction.into());
+            let channels = match self.multiport_channels.get(&port) {
+                Some(channels) => channels.clone(),
+                None => vec![port],
+            };
+
+            for channel in channels {
+                let mut root = self.get_ix(channel.into());
+                while let Some(upstream) = self.upstream_port(root) {
+                    root = upstream;
+                }
+
+                let mut readers = Vec::new();
+                self.collect_readers_rec(root, &mut readers);
+                if let Some(region) = region {
+                    if !region.contains(&reaction_ix) && readers.iter().all(|(ix, _)| !region.contains(ix)) {
+                        continue;
+                    }
+                }
+                for (reader_ix, reader) in readers {
+                    if reader != reaction && !petgraph::algo::has_path_connecting(&self.dataflow, reader_ix, reaction_ix, None) {
+                        return Err(AssemblyError(AssemblyErrorImpl::MutableInputConflict(port, reaction, reader)));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a cycle of the graph, as a list of nodes where
+    /// each has an edge to the next, and the last one has an
+    /// edge to the first one. If the cycle contains reactions,
+    /// it starts with the one that was recorded first. Must only be called if the graph is cyclic.
+    fn find_cycle(&self) -> Vec<GraphId> {
+        let scc = petgraph::algo::tarjan_scc(&self.dataflow)
+            .into_iter()
+            .find(|scc| scc.len() > 1 || self.dataflow.contains_edge(scc[0], scc[0]))
+            .expect("Graph is acyclic");
+        let start = scc
+            .iter()
+            .copied()
+            .filter(|ix| self.dataflow[*ix].kind == NodeKind::Reaction)
+            .min()
+            .unwrap_or(scc[0]);
+        let in_scc: std::collections::HashSet<GraphIx> = scc.into_iter().collect();
+
+        // Breadth-first search for the shortest path back to the start.
+        // All nodes of the component are reachable from each other.
+        let mut predecessors = HashMap::<GraphIx, GraphIx>::new();
+        let mut queue = std::collections::VecDeque::from(vec![start]);
+        'search: while let Some(ix) = queue.pop_front() {
+            for next in self.dataflow.neighbors_directed(ix, Outgoing) {
+                if !in_scc.contains(&next) || predecessors.contains_key(&next) {
+                    continue;
+                }
+                predecessors.insert(next, ix);
+                if next == start {
+                    break 'search;
+                }
+                queue.push_back(next);
+            }
+        }
 
-            if let GraphId::Reaction(id) = node.id {
-                reaction_levels.insert(id, levels.get(&ix).cloned().unwrap());
+        let mut cycle = Vec::new();
+        let mut ix = start;
+        loop {
+            ix = predecessors[&ix];
+            cycle.push(self.dataflow[ix].id);
+            if ix == start {
+                break;
             }
         }
+        cycle.reverse();
+        cycle
+    }
 
-        Ok(reaction_levels)
+    /// Returns the port this port is bound to, if any.
+    fn upstream_port(&self, port: GraphIx) -> Option<GraphIx> {
+        self.dataflow
+            .neighbors_directed(port, Incoming)
+            .find(|ix| self.dataflow[*ix].kind == NodeKind::Port)
+    }
+
+    /// Collects the reactions that read the port or one of its downstream ports.
+    fn collect_readers_rec(&self, port: GraphIx, readers: &mut Vec<(GraphIx, GlobalReactionId)>) {
+        for downstream in self.dataflow.neighbors_directed(port, Outgoing) {
+            match self.dataflow[downstream] {
+                GraphNode { kind: NodeKind::Port, .. } => self.collect_readers_rec(downstream, readers),
+                GraphNode { id: GraphId::Reaction(rid), .. } => readers.push((downstream, rid)),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl DepGraph {
+    /// Looks for dead wiring in the program, eg ports that are
+    /// never bound. None of it makes the program invalid, so
+    /// findings are reported as [Lint]s and not as errors.
+    /// Lints are grouped by kind.
+    pub(super) fn lint(&self) -> Vec<Lint> {
+        let scheduled: std::collections::HashSet<TriggerId> = self.scheduled_actions.iter().map(|&(_, a)| a).collect();
+        let mut unconnected_inputs = Vec::new();
+        let mut unused_outputs = Vec::new();
+        let mut untriggered_reactions = Vec::new();
+        let mut unscheduled_actions = Vec::new();
+
+        for ix in self.dataflow.node_indices() {
+            match self.dataflow[ix] {
+                GraphNode { kind: NodeKind::Port, id: GraphId::Trigger(id) } => match self.port_kinds.get(&id) {
+                    Some(PortKind::Input) if !self.has_upstream(ix) => unconnected_inputs.push(id),
+                    Some(PortKind::Output) if self.dataflow.edges_directed(ix, Outgoing).next().is_none() => {
+                        unused_outputs.push(id)
+                    }
+                    _ => {}
+                },
+                GraphNode {
+                    kind: NodeKind::Reaction,
+                    id: GraphId::Reaction(id),
+                } if !self.is_triggered(ix) => untriggered_reactions.push(id),
+                GraphNode { kind: NodeKind::Action, id: GraphId::Trigger(id) } if !scheduled.contains(&id) => {
+                    unscheduled_actions.push(id)
+                }
+                _ => {}
+            }
+        }
+
+        let mut lints = Vec::new();
+        lints.extend(
+            self.collapse_channels(unconnected_inputs)
+                .into_iter()
+                .map(Lint::UnconnectedInput),
+        );
+        lints.extend(self.collapse_channels(unused_outputs).into_iter().map(Lint::UnusedOutput));
+        lints.extend(untriggered_reactions.into_iter().map(Lint::UntriggeredReaction));
+        lints.extend(unscheduled_actions.into_iter().map(Lint::UnscheduledAction));
+        lints.extend(
+            self.zipped_bindings
+                .iter()
+                .filter(|(_, up_width, _, down_width)| up_width != down_width)
+                .map(|&(up, up_width, down, down_width)| Lint::ZipWidthMismatch(up, up_width, down, down_width)),
+        );
+        lints.extend(
+            self.iterated_bindings
+                .iter()
+                .filter(|(_, up_width, _, down_width)| down_width % up_width != 0)
+                .map(|&(up, up_width, down, down_width)| Lint::IteratedWidthMismatch(up, up_width, down, down_width)),
+        );
+        lints
+    }
+
+    /// Whether the port is bound to another port or set by
+    /// a reaction. A channel of a multiport is also set by
+    /// reactions that effect the whole multiport.
+    fn has_upstream(&self, port: GraphIx) -> bool {
+        self.dataflow
+            .neighbors_directed(port, Incoming)
+            .any(|upstream| self.dataflow[upstream].kind != MultiportUpstream || self.has_upstream(upstream))
+    }
+
+    /// Whether the reaction has a trigger. Use dependencies
+    /// and priority edges don't count.
+    fn is_triggered(&self, reaction: GraphIx) -> bool {
+        self.dataflow
+            .edges_directed(reaction, Incoming)
+            .any(|e| *e.weight() == EdgeWeight::Default && self.dataflow[e.source()].kind != NodeKind::Reaction)
+    }
+
+    /// Replaces the channels of a multiport with the multiport
+    /// itself if all of them are in the list, so that eg an
+    /// unused multiport is reported once and not per channel.
+    fn collapse_channels(&self, ports: Vec<TriggerId>) -> Vec<TriggerId> {
+        let flagged: std::collections::HashSet<TriggerId> = ports.iter().copied().collect();
+        let mut result = Vec::with_capacity(ports.len());
+        let mut collapsed = std::collections::HashSet::new();
+        for (bank, channels) in self.multiport_channels.iter() {
+            if channels.iter().all(|c| flagged.contains(c)) {
+                result.push(*bank);
+                collapsed.extend(channels.iter().copied());
+            }
+        }
+        result.extend(ports.into_iter().filter(|p| !collapsed.contains(p)));
+        result.sort_unstable();
+        result
     }
 }
 
@@ -346,8 +796,9 @@ enum EdgeWeight {
     Use,
 }
 
-/// Stores the level of each reaction. This is transient info
-/// that is used to build a [DataflowInfo] and discarded.
+/// Stores the level of each reaction. This is used to build
+/// a [DataflowInfo], which keeps it to plan the reactions of
+/// events that were pending when the program was mutated.
 ///
 pub struct ReactionLevelInfo {
     /// The level of each reaction.
@@ -377,40 +828,639 @@ pub(super) struct DataflowInfo {
     /// to be scheduled when it is triggered.
     /// Todo: many of those are never asked for, eg those of bound ports
     trigger_to_plan: IndexVec<TriggerId, Arc<ExecutableReactions<'static>>>,
+
+    /// Maps triggers to the reactors that need to be cleaned up
+    /// at the end of a tag where the trigger is present, sorted
+    /// and without duplicates:
+    /// - for a port, the reactor that contains it, and those that
+    ///   contain the ports bound downstream of it, transitively;
+    /// - for an action, the reactor that contains it;
+    /// - for timers, startup and shutdown, nothing. Triggers with
+    ///   an ID past the end of the vec have nothing either.
+    trigger_cleanup: IndexVec<TriggerId, SmallVec<[ReactorId; 2]>>,
+
+    /// Dependencies between reactions, only used by the
+    /// dependency-counting executor.
+    #[cfg(feature = "parallel-runtime")]
+    reaction_dependencies: ReactionDependencies,
+
+    /// The components each reaction declared a dependency on.
+    #[cfg(feature = "verify-dependencies")]
+    declared_dependencies: HashMap<GlobalReactionId, DeclaredDependencies>,
+
+    /// The ports each reaction may take or mutate, with the
+    /// channels of multiports. Only checked in debug builds,
+    /// see [crate::ReactionCtx::take].
+    mutable_inputs: HashSet<(GlobalReactionId, TriggerId)>,
+
+    /// The level of each reaction.
+    level_info: ReactionLevelInfo,
+
+    /// The level of each trigger. Those are only needed
+    /// to update the levels of reactions, see [Self::update].
+    trigger_levels: HashMap<TriggerId, LevelIx>,
+}
+
+/// The dependencies between the reactions that may execute
+/// at the same tag, for the dependency-counting executor.
+/// Reactions are numbered densely, so that the executor can
+/// keep its counters in vectors indexed by reaction.
+#[cfg(feature = "parallel-runtime")]
+pub(super) struct ReactionDependencies {
+    /// The reaction of each index.
+    pub ids: Vec<GlobalReactionId>,
+    /// The index of each reaction.
+    pub indices: HashMap<GlobalReactionId, usize>,
+    /// The indices of the reactions that directly depend on
+    /// each reaction, ie that must wait for it to complete if
+    /// both execute at the same tag. Ports in between are skipped.
+    pub successors: Vec<SmallVec<[usize; 4]>>,
+    /// The indices of reactions that were removed by a mutation,
+    /// which are reused for new reactions. No plan contains them,
+    /// and they have no successors.
+    free: Vec<usize>,
+    /// The dependency counts of the plan of each trigger, by
+    /// the address of the plan. Those plans are never moved,
+    /// and are the only ones that are borrowed by events.
+    plan_counts: HashMap<usize, DependencyCounts>,
+}
+
+/// The reactions that may execute when a plan is executed,
+/// with the number of those reactions each one depends on.
+#[cfg(feature = "parallel-runtime")]
+#[derive(Default)]
+pub(super) struct DependencyCounts {
+    /// The reactions of the plan, and those that depend on
+    /// them directly or transitively.
+    pub reachable: Vec<usize>,
+    /// The number of reachable reactions that each reachable
+    /// reaction depends on, in the same order.
+    pub in_degrees: Vec<usize>,
+}
+
+#[cfg(feature = "parallel-runtime")]
+impl ReactionDependencies {
+    /// Returns the precomputed counts of the given plan, if
+    /// it is the plan of a trigger.
+    #[inline]
+    pub fn precomputed_counts(&self, plan: &ExecutableReactions) -> Option<&DependencyCounts> {
+        self.plan_counts.get(&Self::plan_key(plan))
+    }
+
+    fn plan_key(plan: &ExecutableReactions) -> usize {
+        plan as *const ExecutableReactions as usize
+    }
+
+    /// Precomputes the counts of the plans of the given triggers.
+    fn count_plans(
+        &mut self,
+        trigger_to_plan: &IndexVec<TriggerId, Arc<ExecutableReactions<'static>>>,
+        triggers: impl Iterator<Item = TriggerId>,
+    ) {
+        let mut outstanding = vec![0; self.ids.len()];
+        let mut marks = vec![false; self.ids.len()];
+        for trigger in triggers {
+            let plan = &trigger_to_plan[trigger];
+            if plan.first_batch().is_none() {
+                continue;
+            }
+            let mut reachable = Vec::new();
+            let roots = plan.reactions().map(|rid| self.indices[&rid]);
+            self.count(roots, &mut reachable, &mut outstanding, &mut marks);
+            let in_degrees = reachable.iter().map(|ix| std::mem::take(&mut outstanding[*ix])).collect();
+            self.plan_counts
+                .insert(Self::plan_key(plan), DependencyCounts { reachable, in_degrees });
+        }
+    }
+
+    /// Updates the dependencies after the graph was mutated.
+    /// The successors of the reactions of `upstream`, which
+    /// contains the nodes upstream of those that changed, are
+    /// recomputed, and so are the counts of the plans of the
+    /// `recounted` triggers, which must have been forgotten
+    /// before their plan was replaced.
+    fn update(
+        &mut self,
+        dataflow: &DepGraphImpl,
+        upstream: &HashSet<GraphIx>,
+        removed: &HashSet<GraphId>,
+        recounted: &HashSet<TriggerId>,
+        trigger_to_plan: &IndexVec<TriggerId, Arc<ExecutableReactions<'static>>>,
+    ) {
+        for id in removed {
+            if let GraphId::Reaction(rid) = id {
+                // the reaction may have been added by the same mutations
+                if let Some(ix) = self.indices.remove(rid) {
+                    self.successors[ix].clear();
+                    self.free.push(ix);
+                }
+            }
+        }
+
+        let mut nodes = Vec::new();
+        for &node in upstream {
+            if let GraphId::Reaction(rid) = dataflow[node].id {
+                if !self.indices.contains_key(&rid) {
+                    let ix = match self.free.pop() {
+                        Some(ix) => {
+                            self.ids[ix] = rid;
+                            ix
+                        }
+                        None => {
+                            self.ids.push(rid);
+                            self.successors.push(SmallVec::new());
+                            self.ids.len() - 1
+                        }
+                    };
+                    self.indices.insert(rid, ix);
+                }
+                nodes.push((node, rid));
+            }
+        }
+
+        let mut port_successors = HashMap::new();
+        for (node, rid) in nodes {
+            let successors = DataflowInfo::collect_successors(dataflow, node, &self.indices, &mut port_successors);
+            self.successors[self.indices[&rid]] = successors;
+        }
+
+        self.count_plans(trigger_to_plan, recounted.iter().copied());
+    }
+
+    /// Collects the reactions reachable from the given ones
+    /// into `reachable`, and adds to `outstanding` the number
+    /// of those reactions that each one depends on. The `marks`
+    /// must be false for all reactions, they are reset afterwards.
+    pub fn count(
+        &self,
+        roots: impl IntoIterator<Item = usize>,
+        reachable: &mut Vec<usize>,
+        outstanding: &mut [usize],
+        marks: &mut [bool],
+    ) {
+        let start = reachable.len();
+        for root in roots {
+            if !std::mem::replace(&mut marks[root], true) {
+                reachable.push(root);
+            }
+        }
+        // the reachable reactions are also the work list
+        let mut next = start;
+        while let Some(&reaction) = reachable.get(next) {
+            next += 1;
+            for &successor in &self.successors[reaction] {
+                outstanding[successor] += 1;
+                if !std::mem::replace(&mut marks[successor], true) {
+                    reachable.push(successor);
+                }
+            }
+        }
+        for &reaction in &reachable[start..] {
+            marks[reaction] = false;
+        }
+    }
+}
+
+/// The components a reaction may access, as declared
+/// during assembly. Only used with the `verify-dependencies`
+/// feature.
+#[cfg(feature = "verify-dependencies")]
+#[derive(Default)]
+pub(super) struct DeclaredDependencies {
+    /// Triggers and uses.
+    pub reads: std::collections::HashSet<TriggerId>,
+    /// Ports that may be taken or mutated.
+    pub mutable: std::collections::HashSet<TriggerId>,
+    /// Ports that may be set and actions that may be scheduled.
+    pub effects: std::collections::HashSet<TriggerId>,
 }
 
 impl DataflowInfo {
-    pub fn new(mut graph: DepGraph) -> Result<Self, AssemblyError> {
-        let level_info = ReactionLevelInfo::new(graph.number_reactions_by_level()?);
-        let trigger_to_plan = Self::collect_trigger_to_plan(&mut graph, &level_info);
+    pub fn new(graph: &DepGraph, id_registry: &DebugInfoRegistry) -> Result<Self, AssemblyError> {
+        let (reaction_levels, trigger_levels) = graph.number_nodes_by_level()?;
+        let level_info = ReactionLevelInfo::new(reaction_levels);
+        graph.check_mutable_inputs(None)?;
+        let trigger_to_plan = Self::collect_trigger_to_plan(graph, &level_info, id_registry.next_trigger_id());
+        let trigger_cleanup = Self::collect_trigger_cleanup(&graph.dataflow, id_registry);
+        #[cfg(feature = "parallel-runtime")]
+        let reaction_dependencies = Self::collect_reaction_dependencies(&graph.dataflow, &trigger_to_plan);
+
+        Ok(DataflowInfo {
+            trigger_to_plan,
+            trigger_cleanup,
+            #[cfg(feature = "parallel-runtime")]
+            reaction_dependencies,
+            #[cfg(feature = "verify-dependencies")]
+            declared_dependencies: Self::collect_declared_dependencies(graph, graph.dataflow.node_indices()),
+            mutable_inputs: Self::collect_mutable_inputs(graph),
+            level_info,
+            trigger_levels,
+        })
+    }
+
+    /// Updates the info after the graph was mutated, so that
+    /// it is the same as if it was built again with [Self::new].
+    /// Only the levels, plans and dependencies that depend on
+    /// the nodes that changed since the info was built or last
+    /// updated are recomputed (see [DepGraph::track_changes]).
+    /// The dependency-counting executor may however number
+    /// reactions differently.
+    ///
+    /// If this fails, the info must not be used anymore.
+    pub fn update(&mut self, graph: &mut DepGraph, id_registry: &DebugInfoRegistry) -> Result<(), AssemblyError> {
+        let GraphChanges { touched, removed } = graph
+            .changes
+            .replace(GraphChanges::default())
+            .expect("changes to the graph are not tracked");
+        let graph = &*graph;
+        let dataflow = &graph.dataflow;
+        let touched_ixs: Vec<GraphIx> = touched.iter().map(|id| graph.get_ix(*id)).collect();
+        let is_trigger = |ix: &GraphIx| matches!(dataflow[*ix].id, GraphId::Trigger(_));
+        let removed_triggers: Vec<TriggerId> = removed
+            .iter()
+            .filter_map(|id| match id {
+                GraphId::Trigger(id) => Some(*id),
+                _ => None,
+            })
+            .collect();
+
+        for id in &removed {
+            match id {
+                GraphId::Trigger(id) => self.trigger_levels.remove(id),
+                GraphId::Reaction(id) => self.level_info.level_numbers.remove(id),
+            };
+        }
+        let downstream = graph.collect_reachable(&touched_ixs, Outgoing);
+        let releveled = self.update_levels(graph, &downstream)?;
+        graph.check_mutable_inputs(Some(&downstream))?;
+
+        // The plans that go through the changed triggers,
+        // or that contain reactions whose level changed.
+        let mut changed_triggers: Vec<GraphIx> = touched_ixs.iter().copied().filter(is_trigger).collect();
+        for reaction in releveled {
+            changed_triggers.extend(dataflow.neighbors_directed(reaction, Incoming).filter(is_trigger));
+        }
+        let replanned = graph.collect_upstream_triggers(changed_triggers);
+
+        #[cfg(feature = "parallel-runtime")]
+        let upstream = graph.collect_reachable(&touched_ixs, Incoming);
+        #[cfg(feature = "parallel-runtime")]
+        let recounted: HashSet<TriggerId> = replanned
+            .iter()
+            .chain(upstream.iter().filter(|ix| is_trigger(ix)))
+            .filter_map(|ix| match dataflow[*ix].id {
+                GraphId::Trigger(id) => Some(id),
+                _ => None,
+            })
+            .collect();
+        #[cfg(feature = "parallel-runtime")]
+        for trigger in recounted.iter().chain(&removed_triggers) {
+            if let Some(plan) = self.trigger_to_plan.get(*trigger) {
+                // the plan may be dropped, and its address reused
+                self.reaction_dependencies
+                    .plan_counts
+                    .remove(&ReactionDependencies::plan_key(plan));
+            }
+        }
+
+        while self.trigger_to_plan.len() < id_registry.next_trigger_id().index() {
+            self.trigger_to_plan.push(Default::default());
+        }
+        for trigger in removed_triggers {
+            self.trigger_to_plan[trigger] = Default::default();
+            if let Some(reactors) = self.trigger_cleanup.get_mut(trigger) {
+                reactors.clear();
+            }
+        }
+        for node in replanned {
+            let trigger = match dataflow[node].id {
+                GraphId::Trigger(id) => id,
+                _ => unreachable!("this is a trigger"),
+            };
+            let mut reactions = ExecutableReactions::new();
+            Self::collect_reactions_rec(dataflow, node, &self.level_info, &mut reactions);
+            self.trigger_to_plan[trigger] = Arc::new(reactions);
+
+            let reactors = Self::collect_trigger_cleanup_of(dataflow, node, id_registry).unwrap_or_default();
+            if self.trigger_cleanup.len() <= trigger.index() {
+                self.trigger_cleanup.resize(trigger.index() + 1, SmallVec::new());
+            }
+            self.trigger_cleanup[trigger] = reactors;
+        }
+
+        #[cfg(feature = "parallel-runtime")]
+        self.reaction_dependencies
+            .update(dataflow, &upstream, &removed, &recounted, &self.trigger_to_plan);
+
+        #[cfg(feature = "verify-dependencies")]
+        {
+            // Effects on a multiport also depend on its channels.
+            let mut reactions: HashSet<GraphIx> = HashSet::new();
+            for &ix in &touched_ixs {
+                match dataflow[ix].kind {
+                    NodeKind::Reaction => {
+                        reactions.insert(ix);
+                    }
+                    MultiportUpstream => reactions.extend(
+                        dataflow
+                            .neighbors_directed(ix, Incoming)
+                            .filter(|ix| dataflow[*ix].kind == NodeKind::Reaction),
+                    ),
+                    _ => {}
+                }
+            }
+            for id in &removed {
+                if let GraphId::Reaction(id) = id {
+                    self.declared_dependencies.remove(id);
+                }
+            }
+            self.declared_dependencies
+                .extend(Self::collect_declared_dependencies(graph, reactions.into_iter()));
+        }
+
+        self.mutable_inputs
+            .retain(|&(rid, port)| !removed.contains(&rid.into()) && !removed.contains(&port.into()));
+        for &(port, rid) in &graph.mutable_inputs {
+            if touched.contains(&rid.into()) || touched.contains(&port.into()) {
+                Self::insert_mutable_input(graph, &mut self.mutable_inputs, port, rid);
+            }
+        }
 
-        Ok(DataflowInfo { trigger_to_plan })
+        Ok(())
+    }
+
+    /// Recomputes the levels of the nodes of the region, which
+    /// must contain all the nodes downstream of those that changed.
+    /// The levels of other nodes cannot change. Returns the
+    /// reactions whose level changed, including new ones.
+    fn update_levels(&mut self, graph: &DepGraph, region: &HashSet<GraphIx>) -> AssemblyResult<Vec<GraphIx>> {
+        let dataflow = &graph.dataflow;
+        // Sort the region topologically with Kahn's algorithm.
+        let mut in_degrees: HashMap<GraphIx, usize> = region
+            .iter()
+            .map(|&ix| {
+                let in_region = dataflow.neighbors_directed(ix, Incoming).filter(|up| region.contains(up));
+                (ix, in_region.count())
+            })
+            .collect();
+        let mut ready: Vec<GraphIx> = in_degrees.iter().filter(|(_, d)| **d == 0).map(|(ix, _)| *ix).collect();
+
+        let mut releveled = Vec::new();
+        let mut sorted = 0;
+        while let Some(ix) = ready.pop() {
+            sorted += 1;
+            let level = dataflow
+                .neighbors_directed(ix, Incoming)
+                .map(|up| self.level_of(dataflow[up].id).next())
+                .max()
+                .unwrap_or(LevelIx::ZERO);
+            let old_level = match dataflow[ix].id {
+                GraphId::Trigger(id) => self.trigger_levels.insert(id, level),
+                GraphId::Reaction(id) => self.level_info.level_numbers.insert(id, level),
+            };
+            if dataflow[ix].kind == NodeKind::Reaction && old_level != Some(level) {
+                releveled.push(ix);
+            }
+
+            // the region contains all the successors
+            for down in dataflow.neighbors_directed(ix, Outgoing) {
+                let in_degree = in_degrees.get_mut(&down).unwrap();
+                *in_degree -= 1;
+                if *in_degree == 0 {
+                    ready.push(down);
+                }
+            }
+        }
+
+        if sorted < region.len() {
+            // a new cycle goes through a changed node, so it is in the region
+            return Err(graph.cycle_error());
+        }
+        Ok(releveled)
+    }
+
+    fn level_of(&self, id: GraphId) -> LevelIx {
+        match id {
+            GraphId::Trigger(id) => self.trigger_levels[&id],
+            GraphId::Reaction(id) => self.level_info.level_numbers[&id],
+        }
+    }
+
+    fn collect_mutable_inputs(graph: &DepGraph) -> HashSet<(GlobalReactionId, TriggerId)> {
+        let mut result = HashSet::new();
+        for &(port, rid) in &graph.mutable_inputs {
+            Self::insert_mutable_input(graph, &mut result, port, rid);
+        }
+        result
+    }
+
+    fn insert_mutable_input(
+        graph: &DepGraph,
+        mutable_inputs: &mut HashSet<(GlobalReactionId, TriggerId)>,
+        port: TriggerId,
+        rid: GlobalReactionId,
+    ) {
+        match graph.multiport_channels.get(&port) {
+            Some(channels) => mutable_inputs.extend(channels.iter().map(|&channel| (rid, channel))),
+            None => {
+                mutable_inputs.insert((rid, port));
+            }
+        }
+    }
+
+    /// Collects the declared dependencies of the reactions
+    /// among the given nodes.
+    #[cfg(feature = "verify-dependencies")]
+    fn collect_declared_dependencies(
+        graph: &DepGraph,
+        nodes: impl Iterator<Item = GraphIx>,
+    ) -> HashMap<GlobalReactionId, DeclaredDependencies> {
+        let dataflow = &graph.dataflow;
+        let mut result = HashMap::<GlobalReactionId, DeclaredDependencies>::new();
+        // Dependencies on a multiport apply to all its channels.
+        let with_channels = |id: TriggerId| -> Vec<TriggerId> {
+            match graph.multiport_channels.get(&id) {
+                Some(channels) => std::iter::once(id).chain(channels.iter().copied()).collect(),
+                None => vec![id],
+            }
+        };
+
+        for node in nodes {
+            if let GraphId::Reaction(rid) = dataflow[node].id {
+                let deps = result.entry(rid).or_default();
+                for upstream in dataflow.neighbors_directed(node, Incoming) {
+                    if let GraphId::Trigger(id) = dataflow[upstream].id {
+                        deps.reads.insert(id);
+                    }
+                }
+                for downstream in dataflow.neighbors_directed(node, Outgoing) {
+                    if let GraphId::Trigger(id) = dataflow[downstream].id {
+                        deps.effects.extend(with_channels(id));
+                    }
+                }
+            }
+        }
+        for &(port, rid) in &graph.mutable_inputs {
+            if let Some(deps) = result.get_mut(&rid) {
+                deps.mutable.extend(with_channels(port));
+            }
+        }
+        for &(rid, action) in &graph.scheduled_actions {
+            if let Some(deps) = result.get_mut(&rid) {
+                deps.effects.insert(action);
+            }
+        }
+
+        result
+    }
+
+    #[cfg(feature = "parallel-runtime")]
+    fn collect_reaction_dependencies(
+        dataflow: &DepGraphImpl,
+        trigger_to_plan: &IndexVec<TriggerId, Arc<ExecutableReactions<'static>>>,
+    ) -> ReactionDependencies {
+        let mut ids = Vec::new();
+        let mut nodes = Vec::new();
+        for node in dataflow.node_indices() {
+            if let GraphId::Reaction(rid) = dataflow[node].id {
+                ids.push(rid);
+                nodes.push(node);
+            }
+        }
+        let indices: HashMap<_, _> = ids.iter().enumerate().map(|(ix, rid)| (*rid, ix)).collect();
+
+        // the successors of ports are shared by all their upstream reactions
+        let mut port_successors = HashMap::new();
+        let successors = nodes
+            .iter()
+            .map(|node| Self::collect_successors(dataflow, *node, &indices, &mut port_successors))
+            .collect();
+
+        let mut deps = ReactionDependencies {
+            ids,
+            indices,
+            successors,
+            free: Vec::new(),
+            plan_counts: HashMap::new(),
+        };
+        deps.count_plans(trigger_to_plan, trigger_to_plan.indices());
+        deps
     }
 
+    /// Returns the reactions that directly depend on the given
+    /// node, looking through ports. The result for each port is
+    /// memoized in `port_successors`.
+    #[cfg(feature = "parallel-runtime")]
+    fn collect_successors(
+        dataflow: &DepGraphImpl,
+        node: GraphIx,
+        indices: &HashMap<GlobalReactionId, usize>,
+        port_successors: &mut HashMap<GraphIx, SmallVec<[usize; 4]>>,
+    ) -> SmallVec<[usize; 4]> {
+        let mut successors = SmallVec::new();
+        for downstream in dataflow.neighbors_directed(node, Outgoing) {
+            match dataflow[downstream].id {
+                // either a priority edge, or a trigger/use edge
+                GraphId::Reaction(rid) => successors.push(indices[&rid]),
+                // ports and multiports, look through them
+                GraphId::Trigger(_) => {
+                    if !port_successors.contains_key(&downstream) {
+                        let found = Self::collect_successors(dataflow, downstream, indices, port_successors);
+                        port_successors.insert(downstream, found);
+                    }
+                    successors.extend_from_slice(&port_successors[&downstream]);
+                }
+            }
+        }
+        successors.sort_unstable();
+        successors.dedup();
+        successors
+    }
+
+    fn collect_trigger_cleanup(
+        dataflow: &DepGraphImpl,
+        id_registry: &DebugInfoRegistry,
+    ) -> IndexVec<TriggerId, SmallVec<[ReactorId; 2]>> {
+        let mut result = IndexVec::new();
+
+        for node in dataflow.node_indices() {
+            let trigger_id = match dataflow[node].id {
+                GraphId::Trigger(id) => id,
+                _ => continue,
+            };
+            let reactors = match Self::collect_trigger_cleanup_of(dataflow, node, id_registry) {
+                Some(reactors) => reactors,
+                None => continue,
+            };
+
+            if result.len() <= trigger_id.index() {
+                result.resize(trigger_id.index() + 1, SmallVec::new());
+            }
+            result[trigger_id] = reactors;
+        }
+
+        result
+    }
+
+    /// Returns the reactors to clean up after the trigger, if
+    /// it is a port or an action, see [Self::trigger_cleanup].
+    fn collect_trigger_cleanup_of(
+        dataflow: &DepGraphImpl,
+        trigger: GraphIx,
+        id_registry: &DebugInfoRegistry,
+    ) -> Option<SmallVec<[ReactorId; 2]>> {
+        let trigger_id = match dataflow[trigger].id {
+            GraphId::Trigger(id) => id,
+            _ => return None,
+        };
+        let mut reactors = SmallVec::new();
+        match dataflow[trigger].kind {
+            NodeKind::Port => Self::collect_port_owners_rec(dataflow, trigger, id_registry, &mut reactors),
+            NodeKind::Action => reactors.extend(id_registry.get_trigger_container(trigger_id)),
+            _ => return None,
+        }
+        reactors.sort_unstable();
+        reactors.dedup();
+        Some(reactors)
+    }
+
+    fn collect_port_owners_rec(
+        dataflow: &DepGraphImpl,
+        port: GraphIx,
+        id_registry: &DebugInfoRegistry,
+        reactors: &mut SmallVec<[ReactorId; 2]>,
+    ) {
+        if let GraphId::Trigger(port_id) = dataflow[port].id {
+            reactors.extend(id_registry.get_trigger_container(port_id));
+        }
+        for downstream in dataflow.neighbors_directed(port, Outgoing) {
+            if dataflow[downstream].kind == NodeKind::Port {
+                // this is a port->port binding
+                Self::collect_port_owners_rec(dataflow, downstream, id_registry, reactors)
+            }
+        }
+    }
+
+    /// Collects the plan of every trigger. Triggers that are
+    /// not in the graph, eg because they were removed by a
+    /// mutation, have an empty plan.
     fn collect_trigger_to_plan(
-        DepGraph { dataflow, .. }: &mut DepGraph,
+        DepGraph { dataflow, .. }: &DepGraph,
         level_info: &ReactionLevelInfo,
+        end: TriggerId,
     ) -> IndexVec<TriggerId, Arc<ExecutableReactions<'static>>> {
-        let mut result = IndexVec::with_capacity(dataflow.node_count() / 2);
+        let mut result: IndexVec<TriggerId, _> = (0..end.index()).map(|_| Default::default()).collect();
 
         for trigger in dataflow.node_indices() {
             if let GraphId::Trigger(trigger_id) = dataflow[trigger].id {
-                // if let Some(_multiport_id) = multiport_containment.get(&dataflow[trigger].id) {
-                //     assert_eq!(dataflow[trigger].kind, NodeKind::Port);
-                //     todo!("multiports")
-                // todo this is a multiport channel:
-                //  1. if someone has declared a dependency on this individual channel, collect dependencies into DEPS
-                //  2. else add trigger to DELAY goto 4
-                //  3. merge DEPS into dependencies ALL for the whole multiport
-                //  4. goto next iteration while some channels of the multiport remain to be processed
-                //  5. assign all triggers in DELAY the dependencies ALL
-                //
-                //  This requires all components of a given multiport to be processed consecutively.
-                // }
-
+                // Channels of a multiport are nodes of their own.
+                // Dependencies on the whole multiport have been expanded
+                // into one edge per channel (see trigger_to_reaction_edge),
+                // so the plan of a channel only contains the reactions
+                // that depend on that channel, or on the whole multiport.
                 let mut reactions = ExecutableReactions::new();
                 Self::collect_reactions_rec(dataflow, trigger, level_info, &mut reactions);
-                result.insert(trigger_id, Arc::new(reactions));
+                result[trigger_id] = Arc::new(reactions);
             }
         }
 
@@ -458,6 +1508,53 @@ impl DataflowInfo {
     pub fn reactions_triggered_by(&self, trigger: &TriggerId) -> &ExecutableReactions<'static> {
         &self.trigger_to_plan[*trigger]
     }
+
+    /// Returns a plan to execute the given reactions. Reactions
+    /// that are not in the graph, eg because their reactor was
+    /// destroyed by a mutation, are ignored.
+    pub fn plan_reactions<'x>(&'x self, reactions: impl IntoIterator<Item = GlobalReactionId>) -> ReactionPlan<'x> {
+        let mut plan = ExecutableReactions::new();
+        for reaction in reactions {
+            if let Some(ix) = self.level_info.level_numbers.get(&reaction) {
+                plan.insert(reaction, *ix);
+            }
+        }
+        if plan.first_batch().is_none() {
+            None
+        } else {
+            Some(Cow::Owned(plan))
+        }
+    }
+
+    /// Returns the reactors that need to be cleaned up at the
+    /// end of a tag where the given port was set, or where the
+    /// given action is present.
+    #[inline]
+    pub fn reactors_to_clean_up(&self, trigger: TriggerId) -> &[ReactorId] {
+        self.trigger_cleanup.get(trigger).map_or(&[], |r| r.as_slice())
+    }
+
+    /// Returns true if the reaction declared the port, or the
+    /// multiport it belongs to, as a mutable input.
+    #[inline]
+    pub fn is_mutable_input(&self, reaction: GlobalReactionId, port: TriggerId) -> bool {
+        self.mutable_inputs.contains(&(reaction, port))
+    }
+
+    /// Returns the components the reaction declared a
+    /// dependency on.
+    #[cfg(feature = "verify-dependencies")]
+    pub fn declared_dependencies(&self, reaction: GlobalReactionId) -> Option<&DeclaredDependencies> {
+        self.declared_dependencies.get(&reaction)
+    }
+
+    /// Returns the dependencies between reactions, for the
+    /// dependency-counting executor.
+    #[cfg(feature = "parallel-runtime")]
+    #[inline]
+    pub fn reaction_dependencies(&self) -> &ReactionDependencies {
+        &self.reaction_dependencies
+    }
 }
 
 cfg_if! {
@@ -599,6 +1696,12 @@ impl<'x> ExecutableReactions<'x> {
         self.levels.iter()
     }
 
+    /// Returns an iterator over all reactions of this collection,
+    /// in the order of their level.
+    pub fn reactions(&self) -> impl Iterator<Item = GlobalReactionId> + '_ {
+        self.levels.iter().flat_map(|(_, level)| level.iter())
+    }
+
     pub fn first_batch(&self) -> Option<(KeyRef<&LevelIx>, &Level)> {
         self.levels.min_entry().map(|(ix, cow)| (ix, cow.as_ref()))
     }
@@ -731,7 +1834,7 @@ pub mod test {
             }
         }
 
-        fn new_reactor(&mut self, name: impl Into<String>) -> TestAssembler {
+        fn new_reactor(&mut self, name: impl Into<String>) -> TestAssembler<'_> {
             let reactor_id = self.next_reactor_id.get_and_incr();
             self.debug_info.record_reactor(reactor_id, ReactorDebugInfo::test_named(name));
             TestAssembler {
@@ -743,9 +1846,10 @@ pub mod test {
 
         fn number_reactions_by_level(&self) -> HashMap<GlobalReactionId, LevelIx> {
             self.graph
-                .number_reactions_by_level()
+                .number_nodes_by_level()
                 .map_err(|e| e.lift(&self.debug_info))
                 .unwrap()
+                .0
         }
 
         #[allow(unused)]
@@ -775,15 +1879,45 @@ pub mod test {
         }
 
         fn new_ports<const N: usize>(&mut self, names: [&'static str; N]) -> [TriggerId; N] {
+            self.new_ports_of_kind(PortKind::Output, names)
+        }
+
+        fn new_ports_of_kind<const N: usize>(&mut self, kind: PortKind, names: [&'static str; N]) -> [TriggerId; N] {
             let result = array![_ => self.fixture.next_trigger_id.get_and_incr().unwrap(); N];
             for (i, p) in result.iter().enumerate() {
-                self.fixture.graph.record_port(*p);
+                self.fixture.graph.record_port(*p, kind);
                 self.fixture.debug_info.record_trigger(*p, Cow::Borrowed(names[i]));
             }
             result
         }
     }
 
+    impl TestAssembler<'_> {
+        fn new_action(&mut self, name: &'static str) -> TriggerId {
+            let id = self.fixture.next_trigger_id.get_and_incr().unwrap();
+            self.fixture.graph.record_laction(id);
+            self.fixture.debug_info.record_trigger(id, Cow::Borrowed(name));
+            id
+        }
+
+        fn new_multiport<const N: usize>(&mut self, name: &'static str) -> (TriggerId, [TriggerId; N]) {
+            self.new_multiport_of_kind(PortKind::Input, name)
+        }
+
+        fn new_multiport_of_kind<const N: usize>(&mut self, kind: PortKind, name: &'static str) -> (TriggerId, [TriggerId; N]) {
+            let bank = self.fixture.next_trigger_id.get_and_incr().unwrap();
+            self.fixture.debug_info.record_trigger(bank, Cow::Borrowed(name));
+            self.fixture.graph.record_port_bank(bank, N).ok().unwrap();
+            let channels = array![i => {
+                let id = self.fixture.next_trigger_id.get_and_incr().unwrap();
+                self.fixture.debug_info.record_trigger(id, Cow::Owned(format!("{}[{}]", name, i)));
+                self.fixture.graph.record_port_bank_component(bank, id, kind);
+                id
+            }; N];
+            (bank, channels)
+        }
+    }
+
     impl Drop for TestAssembler<'_> {
         fn drop(&mut self) {
             let range = self.first_trigger_id..self.fixture.next_trigger_id;
@@ -976,4 +2110,376 @@ pub mod test {
 "#
         );
     }
+
+    #[test]
+    fn test_multiport_channels_trigger_separately() {
+        let mut test = TestGraphFixture::new();
+
+        let mut builder = test.new_reactor("main");
+        let [n0, n1, n_all] = builder.new_reactions();
+        let (bank, [c0, c1, c2]) = builder.new_multiport::<3>("in");
+        drop(builder);
+
+        test.graph.triggers_reaction(c0, n0);
+        test.graph.triggers_reaction(c1, n1);
+        test.graph.triggers_reaction(bank, n_all);
+
+        let dataflow = DataflowInfo::new(&test.graph, &test.debug_info).ok().unwrap();
+        let triggered = |c| {
+            dataflow
+                .reactions_triggered_by(&c)
+                .batches()
+                .flat_map(|(_, l)| l.iter())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(triggered(c0), vec![n0, n_all]);
+        assert_eq!(triggered(c1), vec![n1, n_all]);
+        assert_eq!(triggered(c2), vec![n_all]);
+    }
+
+    #[cfg(feature = "parallel-runtime")]
+    #[test]
+    fn test_dependency_counts_of_plans() {
+        let mut test = TestGraphFixture::new();
+
+        let mut builder = test.new_reactor("up");
+        let [u] = builder.new_reactions();
+        let [p] = builder.new_ports(["p"]);
+        drop(builder);
+        let mut builder = test.new_reactor("a");
+        let [a] = builder.new_reactions();
+        let [pa] = builder.new_ports(["pa"]);
+        drop(builder);
+        let mut builder = test.new_reactor("b");
+        let [b] = builder.new_reactions();
+        let [pb] = builder.new_ports(["pb"]);
+        drop(builder);
+        let mut builder = test.new_reactor("sink");
+        let [s0, s1] = builder.new_reactions();
+        drop(builder);
+
+        // u -> p -> a -> pa -> s0 -> s1 (by priority)
+        //        -> b -> pb ->
+        test.graph.reaction_effects(u, p);
+        test.graph.triggers_reaction(p, a);
+        test.graph.triggers_reaction(p, b);
+        test.graph.reaction_effects(a, pa);
+        test.graph.reaction_effects(b, pb);
+        test.graph.triggers_reaction(pa, s0);
+        test.graph.triggers_reaction(pb, s0);
+
+        let dataflow = DataflowInfo::new(&test.graph, &test.debug_info).ok().unwrap();
+        let deps = dataflow.reaction_dependencies();
+        let by_id = |reachable: &[usize], in_degree: &dyn Fn(usize) -> usize| -> HashMap<GlobalReactionId, usize> {
+            reachable
+                .iter()
+                .enumerate()
+                .map(|(i, ix)| (deps.ids[*ix], in_degree(i)))
+                .collect()
+        };
+
+        let counts = deps.precomputed_counts(dataflow.reactions_triggered_by(&p)).unwrap();
+        assert_eq!(
+            by_id(&counts.reachable, &|i| counts.in_degrees[i]),
+            HashMap::from([(a, 0), (b, 0), (s0, 2), (s1, 1)])
+        );
+
+        // plans that are not the plan of a trigger are counted on the fly
+        let mut reachable = Vec::new();
+        let mut outstanding = vec![0; deps.ids.len()];
+        let mut marks = vec![false; deps.ids.len()];
+        deps.count([deps.indices[&a]], &mut reachable, &mut outstanding, &mut marks);
+        assert_eq!(
+            by_id(&reachable, &|i| outstanding[reachable[i]]),
+            HashMap::from([(a, 0), (s0, 1), (s1, 1)])
+        );
+        assert!(marks.iter().all(|m| !m));
+    }
+
+    #[test]
+    fn test_multiport_channels_are_leveled_separately() {
+        let mut test = TestGraphFixture::new();
+
+        let mut builder = test.new_reactor("up");
+        let [u0, u1] = builder.new_reactions();
+        let [p0] = builder.new_ports(["p0"]);
+        drop(builder);
+        let mut builder = test.new_reactor("down0");
+        let [d0] = builder.new_reactions();
+        let (_, [in0, in1]) = builder.new_multiport::<2>("in");
+        drop(builder);
+        let mut builder = test.new_reactor("down1");
+        let [d1] = builder.new_reactions();
+        drop(builder);
+
+        // u0 -> p0 -> u1 -> in[0] -> d0
+        //                   in[1] -> d1
+        test.graph.reaction_effects(u0, p0);
+        test.graph.triggers_reaction(p0, u1);
+        test.graph.reaction_effects(u1, in0);
+        test.graph.triggers_reaction(in0, d0);
+        test.graph.triggers_reaction(in1, d1);
+
+        let levels = test.number_reactions_by_level();
+        assert!(levels[&u1] < levels[&d0]);
+        assert!(levels[&d1] < levels[&d0]);
+        assert_eq!(levels[&d1], LevelIx::from(2));
+    }
+
+    #[test]
+    fn test_mutable_input_must_be_read_last() {
+        let mut test = TestGraphFixture::new();
+
+        let mut builder = test.new_reactor("up");
+        let [u0] = builder.new_reactions();
+        let [out] = builder.new_ports(["out"]);
+        drop(builder);
+        let mut builder = test.new_reactor("down");
+        let [d0, d1] = builder.new_reactions();
+        let [inp] = builder.new_ports(["in"]);
+        drop(builder);
+        let mut builder = test.new_reactor("other");
+        let [o0] = builder.new_reactions();
+        let [other_in] = builder.new_ports(["in"]);
+        drop(builder);
+
+        // u0 -> out -> in -> d0 -> d1 (mutable)
+        //          \-> other_in -> o0
+        test.graph.reaction_effects(u0, out);
+        test.graph.port_bind_untyped(out, inp);
+        test.graph.port_bind_untyped(out, other_in);
+        test.graph.triggers_reaction(inp, d0);
+        test.graph.reaction_mutates(d1, inp);
+        assert!(test.graph.check_mutable_inputs(None).is_ok());
+
+        // o0 reads the same value and is not ordered with d1
+        test.graph.triggers_reaction(other_in, o0);
+        match test.graph.check_mutable_inputs(None) {
+            Err(AssemblyError(AssemblyErrorImpl::MutableInputConflict(p, r, o))) => assert_eq!((p, r, o), (inp, d1, o0)),
+            _ => panic!("expected a conflict"),
+        }
+    }
+
+    #[test]
+    fn test_lint_finds_dead_wiring() {
+        let mut test = TestGraphFixture::new();
+
+        let mut builder = test.new_reactor("up");
+        let [u0] = builder.new_reactions();
+        let [out, unused] = builder.new_ports_of_kind(PortKind::Output, ["out", "unused"]);
+        drop(builder);
+        let mut builder = test.new_reactor("down");
+        let [d0, d1] = builder.new_reactions();
+        let [inp, dangling] = builder.new_ports_of_kind(PortKind::Input, ["in", "dangling"]);
+        let (bank, [c0, c1]) = builder.new_multiport_of_kind(PortKind::Input, "bank");
+        let scheduled = builder.new_action("scheduled");
+        let act = builder.new_action("act");
+        drop(builder);
+
+        // startup -> u0 -> out -> in -> d0 -> scheduled
+        // d1 only has a priority edge from d0, and uses dangling
+        test.graph.triggers_reaction(TriggerId::STARTUP, u0);
+        test.graph.reaction_effects(u0, out);
+        test.graph.port_bind_untyped(out, inp);
+        test.graph.triggers_reaction(inp, d0);
+        test.graph.reaction_schedules(d0, scheduled);
+        test.graph.triggers_reaction(scheduled, d0);
+        test.graph.reaction_uses(d1, dangling);
+        test.graph.record_zipped_binding(out, 1, inp, 2);
+
+        assert_eq!(
+            test.graph.lint(),
+            vec![
+                Lint::UnconnectedInput(dangling),
+                Lint::UnconnectedInput(bank),
+                Lint::UnusedOutput(unused),
+                Lint::UntriggeredReaction(d1),
+                Lint::UnscheduledAction(act),
+                Lint::ZipWidthMismatch(out, 1, inp, 2),
+            ]
+        );
+
+        // setting one channel of the bank is enough to report channels separately
+        test.graph.reaction_effects(u0, c0);
+        assert_eq!(test.graph.lint()[1], Lint::UnconnectedInput(c1));
+    }
+
+    /// Asserts that the updated info is the same as the one
+    /// rebuilt from the whole graph.
+    fn assert_same_dataflow(test: &TestGraphFixture, updated: &DataflowInfo) {
+        let rebuilt = DataflowInfo::new(&test.graph, &test.debug_info).ok().unwrap();
+        let plan = |info: &DataflowInfo, trigger: TriggerId| -> Vec<(LevelIx, Vec<GlobalReactionId>)> {
+            info.trigger_to_plan
+                .get(trigger)
+                .iter()
+                .flat_map(|plan| plan.batches())
+                .map(|(ix, level)| {
+                    let mut reactions: Vec<_> = level.iter().collect();
+                    reactions.sort_unstable();
+                    (*ix, reactions)
+                })
+                .collect()
+        };
+        for trigger in (0..test.next_trigger_id.index()).map(TriggerId::from_usize) {
+            assert_eq!(plan(updated, trigger), plan(&rebuilt, trigger), "plan of {:?}", trigger);
+            assert_eq!(
+                updated.reactors_to_clean_up(trigger),
+                rebuilt.reactors_to_clean_up(trigger),
+                "cleanup of {:?}",
+                trigger
+            );
+        }
+        assert_eq!(updated.level_info.level_numbers, rebuilt.level_info.level_numbers);
+        assert_eq!(updated.trigger_levels, rebuilt.trigger_levels);
+        assert_eq!(updated.mutable_inputs, rebuilt.mutable_inputs);
+
+        #[cfg(feature = "parallel-runtime")]
+        {
+            // reactions may be numbered differently
+            let successors = |deps: &ReactionDependencies| -> HashMap<GlobalReactionId, Vec<GlobalReactionId>> {
+                deps.indices
+                    .iter()
+                    .map(|(rid, ix)| {
+                        let mut successors: Vec<_> = deps.successors[*ix].iter().map(|s| deps.ids[*s]).collect();
+                        successors.sort_unstable();
+                        (*rid, successors)
+                    })
+                    .collect()
+            };
+            let counts = |info: &DataflowInfo, trigger: TriggerId| -> Option<HashMap<GlobalReactionId, usize>> {
+                let deps = info.reaction_dependencies();
+                let counts = deps.precomputed_counts(info.reactions_triggered_by(&trigger))?;
+                Some(
+                    counts
+                        .reachable
+                        .iter()
+                        .map(|ix| deps.ids[*ix])
+                        .zip(counts.in_degrees.iter().copied())
+                        .collect(),
+                )
+            };
+            let (updated_deps, rebuilt_deps) = (updated.reaction_dependencies(), rebuilt.reaction_dependencies());
+            assert_eq!(successors(updated_deps), successors(rebuilt_deps));
+            for trigger in (0..test.next_trigger_id.index()).map(TriggerId::from_usize) {
+                assert_eq!(counts(updated, trigger), counts(&rebuilt, trigger), "counts of {:?}", trigger);
+            }
+            assert_eq!(updated_deps.plan_counts.len(), rebuilt_deps.plan_counts.len());
+        }
+
+        #[cfg(feature = "verify-dependencies")]
+        {
+            let declared = |info: &DataflowInfo| -> HashMap<GlobalReactionId, Vec<Vec<TriggerId>>> {
+                let sorted = |set: &std::collections::HashSet<TriggerId>| {
+                    let mut ids: Vec<_> = set.iter().copied().collect();
+                    ids.sort_unstable();
+                    ids
+                };
+                info.declared_dependencies
+                    .iter()
+                    .map(|(rid, deps)| (*rid, vec![sorted(&deps.reads), sorted(&deps.mutable), sorted(&deps.effects)]))
+                    .collect()
+            };
+            assert_eq!(declared(updated), declared(&rebuilt));
+        }
+    }
+
+    #[test]
+    fn test_dataflow_update_matches_rebuild() {
+        let mut test = TestGraphFixture::new();
+
+        let mut builder = test.new_reactor("up");
+        let [u] = builder.new_reactions();
+        let [p] = builder.new_ports(["p"]);
+        drop(builder);
+        let mut builder = test.new_reactor("a");
+        let [a] = builder.new_reactions();
+        let [pa] = builder.new_ports_of_kind(PortKind::Input, ["pa"]);
+        drop(builder);
+        let mut builder = test.new_reactor("gone");
+        let [g] = builder.new_reactions();
+        let [pg, q] = builder.new_ports(["pg", "q"]);
+        drop(builder);
+        let gone = ReactorId::new(2);
+        let mut builder = test.new_reactor("sink");
+        let [s0, s1] = builder.new_reactions();
+        let [si] = builder.new_ports_of_kind(PortKind::Input, ["si"]);
+        let (bank, [c0, c1]) = builder.new_multiport("bank");
+        drop(builder);
+
+        // u -> p -> pa -> a -> c0
+        //        -> pg -> g -> q -> si -> s0 -> s1
+        //   -> c1 ------------------------------^
+        test.graph.reaction_effects(u, p);
+        test.graph.port_bind_untyped(p, pa);
+        test.graph.port_bind_untyped(p, pg);
+        test.graph.triggers_reaction(pa, a);
+        test.graph.triggers_reaction(pg, g);
+        test.graph.reaction_effects(g, q);
+        test.graph.port_bind_untyped(q, si);
+        test.graph.triggers_reaction(si, s0);
+        test.graph.reaction_effects(a, c0);
+        test.graph.reaction_effects(u, c1);
+        test.graph.reaction_mutates(s1, bank);
+        test.graph.triggers_reaction(bank, s1);
+
+        let mut dataflow = DataflowInfo::new(&test.graph, &test.debug_info).ok().unwrap();
+        test.graph.track_changes();
+
+        // destroy the reactor in the middle
+        let removed = [pg, q].into_iter().collect();
+        test.graph.remove_components(&removed, &[gone].into_iter().collect());
+        // create a reactor that sets si, at a higher level than g
+        let mut builder = test.new_reactor("new");
+        let [n0, n1] = builder.new_reactions();
+        let [pn] = builder.new_ports_of_kind(PortKind::Input, ["pn"]);
+        drop(builder);
+        test.graph.port_bind_untyped(pa, pn);
+        test.graph.triggers_reaction(pn, n0);
+        test.graph.reaction_effects(n1, si);
+        // add a channel to the bank
+        let c2 = test.next_trigger_id.get_and_incr().unwrap();
+        test.debug_info.record_trigger(c2, Cow::Borrowed("bank[2]"));
+        test.graph.add_port_bank_component(bank, c2, PortKind::Input);
+        test.graph.triggers_reaction(c2, s1);
+        test.graph.reaction_effects(n1, c2);
+
+        dataflow.update(&mut test.graph, &test.debug_info).ok().unwrap();
+        assert_same_dataflow(&test, &dataflow);
+        assert_eq!(dataflow.level_info.level_numbers[&s1], LevelIx::from(8));
+        assert!(dataflow.is_mutable_input(s1, c2));
+
+        // a second batch of mutations, which closes a cycle
+        test.graph.triggers_reaction(si, n1);
+        test.graph.reaction_effects(s0, pn);
+        match dataflow.update(&mut test.graph, &test.debug_info) {
+            Err(e) => assert_eq!(
+                e.lift(&test.debug_info),
+                "Cyclic dependency graph: sink/0 -> new/pn -> new/0 -> new/1 -> sink/si -> sink/0"
+            ),
+            Ok(_) => panic!("expected a cycle"),
+        }
+    }
+
+    #[test]
+    fn test_cycle_is_reported() {
+        let mut test = TestGraphFixture::new();
+
+        let mut builder = test.new_reactor("main");
+        let [n0, n1] = builder.new_reactions();
+        let [p, q] = builder.new_ports(["p", "q"]);
+        drop(builder);
+
+        // n0 -> n1 -> p -> n0, q is not part of the cycle
+        test.graph.reaction_effects(n1, p);
+        test.graph.triggers_reaction(p, n0);
+        test.graph.reaction_effects(n0, q);
+
+        match test.graph.number_nodes_by_level() {
+            Err(e) => assert_eq!(
+                e.lift(&test.debug_info),
+                "Cyclic dependency graph: main/0 -> main/1 -> main/p -> main/0"
+            ),
+            Ok(_) => panic!("expected a cycle"),
+        }
+    }
 }
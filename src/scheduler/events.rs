@@ -1,7 +1,9 @@
 use std::borrow::Cow;
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 use std::fmt::{Display, Formatter};
-use std::time::Instant;
+use std::time::{Instant, SystemTime};
+
+use smallvec::{smallvec, SmallVec};
 
 use super::ReactionPlan;
 use crate::scheduler::dependencies::{DataflowInfo, ExecutableReactions};
@@ -16,14 +18,19 @@ use crate::*;
 /// instant. The label on this sequence is called the *microstep*
 /// of the tag.
 ///
+/// Like in LF, the tags [Self::NEVER] and [Self::FOREVER] are
+/// before, resp. after, any other tag. Events are never processed
+/// at those tags.
+///
 /// Use the [tag!](crate::tag) macro to create this struct with
 /// convenient syntax.
 #[derive(Copy, Clone, Hash, Eq, PartialEq, Debug, Ord, PartialOrd)]
 pub struct EventTag {
-    /// The time offset from the origin of the logical timeline.
+    /// The time offset from the origin of the logical timeline,
+    /// which is negative for tags before the start of the program.
     /// Knowing the start time of the application is necessary to
     /// convert this to an absolute [Instant] (see [Self::to_logical_time]).
-    pub offset_from_t0: Duration,
+    pub offset_from_t0: Interval,
     /// The microstep of this tag.
     pub microstep: MicroStep,
 }
@@ -31,19 +38,93 @@ pub struct EventTag {
 impl EventTag {
     /// The tag of the startup event.
     pub const ORIGIN: EventTag = EventTag {
-        offset_from_t0: Duration::from_millis(0),
+        offset_from_t0: Interval::ZERO,
+        microstep: MicroStep::ZERO,
+    };
+
+    /// A tag before any other.
+    pub const NEVER: EventTag = EventTag {
+        offset_from_t0: Interval::NEVER,
         microstep: MicroStep::ZERO,
     };
 
+    /// A tag after any other.
+    pub const FOREVER: EventTag = EventTag {
+        offset_from_t0: Interval::FOREVER,
+        microstep: MicroStep::MAX,
+    };
+
+    #[inline]
+    pub fn is_never(&self) -> bool {
+        self.offset_from_t0.is_never()
+    }
+
+    #[inline]
+    pub fn is_forever(&self) -> bool {
+        self.offset_from_t0.is_forever()
+    }
+
     /// Returns the logical instant for this tag, using the
     /// initial time `t0`.
+    ///
+    /// # Panics
+    ///
+    /// If the tag is [NEVER](Self::NEVER) or [FOREVER](Self::FOREVER),
+    /// or is too far from `t0` to be represented (see [Self::checked_logical_time]).
     #[inline]
     pub fn to_logical_time(&self, t0: Instant) -> Instant {
-        t0 + self.offset_from_t0
+        self.checked_logical_time(t0)
+            .unwrap_or_else(|| panic!("Tag {} has no logical time", self))
+    }
+
+    /// Returns the logical instant for this tag, using the
+    /// initial time `t0`, or None if the tag is [NEVER](Self::NEVER)
+    /// or [FOREVER](Self::FOREVER), or if the instant cannot
+    /// be represented.
+    pub fn checked_logical_time(&self, t0: Instant) -> Option<Instant> {
+        match self.offset_from_t0.to_duration() {
+            Some(offset) => t0.checked_add(offset),
+            None if self.offset_from_t0.is_finite() => t0.checked_sub((-self.offset_from_t0).to_duration()?),
+            None => None,
+        }
+    }
+
+    /// Returns the wall-clock time for this tag, using the
+    /// wall-clock time of the initial instant `t0_utc`
+    /// (see [ReactionCtx::get_start_time_utc]).
+    ///
+    /// # Panics
+    ///
+    /// Like [Self::to_logical_time].
+    #[inline]
+    pub fn to_utc_time(&self, t0_utc: SystemTime) -> SystemTime {
+        let time = match self.offset_from_t0.to_duration() {
+            Some(offset) => t0_utc.checked_add(offset),
+            None if self.offset_from_t0.is_finite() => (-self.offset_from_t0).to_duration().and_then(|d| t0_utc.checked_sub(d)),
+            None => None,
+        };
+        time.unwrap_or_else(|| panic!("Tag {} has no wall-clock time", self))
+    }
+
+    /// Returns the tag for the zeroth microstep of the given
+    /// wall-clock time, using the wall-clock time of the
+    /// initial instant `t0_utc`. The tag is before [Self::ORIGIN]
+    /// if the time is before `t0_utc`. Returns None if the
+    /// time is too far from `t0_utc` to be represented.
+    #[inline]
+    pub fn from_utc_time(t0_utc: SystemTime, time: SystemTime) -> Option<Self> {
+        let offset_from_t0 = match time.duration_since(t0_utc) {
+            Ok(elapsed) => Interval::from_duration(elapsed),
+            Err(e) => -Interval::from_duration(e.duration()),
+        };
+        Some(Self { offset_from_t0, microstep: MicroStep::ZERO }).filter(|tag| tag.offset_from_t0.is_finite())
     }
 
     /// Returns the amount of time elapsed since the start
-    /// of the app.
+    /// of the app. This saturates to zero for tags before
+    /// the start of the app, and to [Duration::MAX] for
+    /// [FOREVER](Self::FOREVER). See [Self::offset_from_t0]
+    /// for the signed offset.
     ///
     /// ```no_run
     /// # use std::time::Instant;
@@ -54,7 +135,19 @@ impl EventTag {
     /// ```
     #[inline]
     pub fn duration_since_start(&self) -> Duration {
-        self.offset_from_t0
+        match self.checked_duration_since_start() {
+            Some(elapsed) => elapsed,
+            None if self.is_forever() => Duration::MAX,
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Returns the amount of time elapsed since the start
+    /// of the app, or None if the tag is before the start
+    /// of the app, or is [FOREVER](Self::FOREVER).
+    #[inline]
+    pub fn checked_duration_since_start(&self) -> Option<Duration> {
+        self.offset_from_t0.to_duration()
     }
 
     /// Returns the microstep of this tag.
@@ -63,11 +156,12 @@ impl EventTag {
         self.microstep
     }
 
-    /// Create a tag for the zeroth microstep of the given instant.
+    /// Create a tag for the zeroth microstep of the given instant,
+    /// which is after `t0`.
     #[inline]
     pub(crate) fn absolute(t0: Instant, instant: Instant) -> Self {
         Self {
-            offset_from_t0: instant - t0,
+            offset_from_t0: instant.saturating_duration_since(t0).into(),
             microstep: MicroStep::ZERO,
         }
     }
@@ -77,54 +171,99 @@ impl EventTag {
     #[inline]
     pub fn offset(offset_from_t0: Duration, microstep: crate::time::MS) -> Self {
         Self {
-            offset_from_t0,
+            offset_from_t0: offset_from_t0.into(),
             microstep: MicroStep::new(microstep),
         }
     }
 
     /// Returns a tag that is strictly greater than this one.
+    /// This is the tag at which an action scheduled with the
+    /// given delay from this tag triggers (see [ReactionCtx::schedule]).
+    /// Tags that would be too far in the future to be represented
+    /// saturate to [FOREVER](Self::FOREVER). [NEVER](Self::NEVER)
+    /// and [FOREVER](Self::FOREVER) are their own successors.
+    ///
+    /// # Panics
+    ///
+    /// If the offset is zero and the microstep overflows,
+    /// see [Self::checked_next_microstep].
     #[inline]
-    pub(crate) fn successor(self, offset: Duration) -> Self {
-        if offset.is_zero() {
+    pub fn successor(self, offset: Duration) -> Self {
+        if !self.offset_from_t0.is_finite() {
+            self
+        } else if offset.is_zero() {
             self.next_microstep()
         } else {
-            Self {
-                offset_from_t0: self.offset_from_t0 + offset,
-                microstep: MicroStep::ZERO,
+            let offset_from_t0 = self.offset_from_t0 + offset.into();
+            if offset_from_t0.is_forever() {
+                Self::FOREVER
+            } else {
+                Self { offset_from_t0, microstep: MicroStep::ZERO }
             }
         }
     }
 
+    /// Returns the tag at the next microstep of the same time.
+    /// This fails if the microstep is already [MicroStep::MAX],
+    /// which happens when a program processes too many tags at
+    /// the same logical time, eg because of an endless loop
+    /// through zero-delay actions.
     #[inline]
-    pub(crate) fn next_microstep(&self) -> Self {
-        Self {
-            offset_from_t0: self.offset_from_t0,
-            microstep: self.microstep + 1,
+    pub fn checked_next_microstep(&self) -> Result<Self, MicroStepOverflow> {
+        match self.microstep.checked_next() {
+            Some(microstep) => Ok(Self { offset_from_t0: self.offset_from_t0, microstep }),
+            None => Err(MicroStepOverflow { tag: *self }),
         }
     }
 
+    /// Like [Self::checked_next_microstep], but panics on overflow.
+    #[inline]
+    pub(crate) fn next_microstep(&self) -> Self {
+        self.checked_next_microstep().unwrap_or_else(|e| panic!("{}", e))
+    }
+
     #[inline]
     pub(crate) fn now(t0: Instant) -> Self {
-        Self {
-            offset_from_t0: Instant::now() - t0,
-            microstep: MicroStep::ZERO,
-        }
+        Self::absolute(t0, Instant::now())
     }
 }
 
 impl Display for EventTag {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let elapsed = self.offset_from_t0;
+        let nanos = self.offset_from_t0.as_nanos();
+        let (sign, abs) = if nanos < 0 {
+            ('-', nanos.unsigned_abs())
+        } else {
+            ('+', nanos as u64)
+        };
+        match self.offset_from_t0 {
+            Interval::NEVER => write!(f, "(NEVER, {})", self.microstep),
+            Interval::FOREVER => write!(f, "(FOREVER, {})", self.microstep),
+            _ => write!(f, "(T0 {} {} ns = {} ms, {})", sign, abs, abs / 1_000_000, self.microstep),
+        }
+    }
+}
+
+/// Error returned by [EventTag::checked_next_microstep] when
+/// the microstep of the tag is already [MicroStep::MAX].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MicroStepOverflow {
+    /// The tag that has no next microstep.
+    pub tag: EventTag,
+}
+
+impl Display for MicroStepOverflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "(T0 + {} ns = {} ms, {})",
-            elapsed.as_nanos(),
-            elapsed.as_millis(),
-            self.microstep
+            "Microstep overflow after tag {}: too many tags at the same logical time, is there a zero-delay loop?",
+            self.tag
         )
     }
 }
 
+impl std::error::Error for MicroStepOverflow {}
+
 /// A tagged event of the reactor program. Events are tagged
 /// with the logical instant at which they must be processed.
 /// They are queued and processed in order. See [self::EventQueue].
@@ -139,6 +278,9 @@ pub(super) struct Event<'x> {
     pub(super) tag: EventTag,
     /// A set of reactions to execute.
     pub reactions: ReactionPlan<'x>,
+    /// The actions and timers that triggered this event. They
+    /// are present at its tag even if they trigger no reaction.
+    pub triggers: SmallVec<[TriggerId; 1]>,
     /// Whether we should terminate the application at
     /// the tag of this event (after processing the tag).
     pub terminate: bool,
@@ -148,14 +290,68 @@ impl<'x> Event<'x> {
     pub fn absorb(&mut self, other: Event<'x>) {
         debug_assert_eq!(self.tag, other.tag);
         self.reactions = ExecutableReactions::merge_cows(self.reactions.take(), other.reactions);
+        self.triggers.extend(other.triggers);
         self.terminate |= other.terminate;
     }
 
     pub fn execute(tag: EventTag, reactions: Cow<'x, ExecutableReactions<'x>>) -> Self {
-        Self { tag, reactions: Some(reactions), terminate: false }
+        Self {
+            tag,
+            reactions: Some(reactions),
+            triggers: SmallVec::new(),
+            terminate: false,
+        }
+    }
+    /// An event that triggers the given action or timer.
+    pub fn trigger(tag: EventTag, trigger: TriggerId, reactions: &'x ExecutableReactions<'x>) -> Self {
+        Self {
+            tag,
+            reactions: Some(Cow::Borrowed(reactions)),
+            triggers: smallvec![trigger],
+            terminate: false,
+        }
     }
     pub fn terminate_at(tag: EventTag) -> Self {
-        Self { tag, reactions: None, terminate: true }
+        Self {
+            tag,
+            reactions: None,
+            triggers: SmallVec::new(),
+            terminate: true,
+        }
+    }
+
+    /// Retract the given action or timer from this event, so
+    /// that it is not present at the tag of this event anymore.
+    /// Of the reactions it triggers, only those that are not
+    /// also triggered by another trigger of this event are removed.
+    pub(super) fn retract(&mut self, trigger: TriggerId, dataflow: &'x DataflowInfo) {
+        if !self.triggers.contains(&trigger) {
+            return;
+        }
+        self.triggers.retain(|t| *t != trigger);
+        let cancelled = dataflow.reactions_triggered_by(&trigger);
+        self.reactions = match self.reactions.take() {
+            Some(Cow::Borrowed(plan)) if self.triggers.is_empty() && std::ptr::eq(plan, cancelled) => None,
+            Some(plan) => {
+                let kept: HashSet<GlobalReactionId> = self
+                    .triggers
+                    .iter()
+                    .flat_map(|t| dataflow.reactions_triggered_by(t).reactions())
+                    .collect();
+                let retracted: HashSet<GlobalReactionId> = cancelled.reactions().filter(|r| !kept.contains(r)).collect();
+                if retracted.is_empty() {
+                    Some(plan)
+                } else {
+                    dataflow.plan_reactions(plan.reactions().filter(|r| !retracted.contains(r)))
+                }
+            }
+            None => None,
+        };
+    }
+
+    /// Whether this event has no effect, and may be dropped.
+    pub(super) fn is_empty(&self) -> bool {
+        self.reactions.is_none() && self.triggers.is_empty() && !self.terminate
     }
 }
 
@@ -173,12 +369,13 @@ pub(super) struct PhysicalEvent {
 
 impl PhysicalEvent {
     /// Turn a [PhysicalEvent] into an [Event] within the scheduler.
-    pub(super) fn make_executable(self, dataflow: &DataflowInfo) -> Event {
+    pub(super) fn make_executable(self, dataflow: &DataflowInfo) -> Event<'_> {
         let PhysicalEvent { tag, trigger_id, terminate } = self;
         Event {
             tag,
             terminate,
             reactions: trigger_id.map(|id| Cow::Borrowed(dataflow.reactions_triggered_by(&id))),
+            triggers: trigger_id.into_iter().collect(),
         }
     }
 
@@ -212,6 +409,11 @@ impl<'x> EventQueue<'x> {
         self.value_list.pop_front()
     }
 
+    /// Returns the tag of the earliest event, without removing it.
+    pub fn peek_earliest_tag(&self) -> Option<EventTag> {
+        self.value_list.front().map(|e| e.tag)
+    }
+
     // todo perf: we could make a more optimal function to push a
     //  lot of events at once. Consider the following algorithm:
     //  - start with a sorted `self.value_list` and a (non-sorted) `new_evts: Vec<Event>`
@@ -223,8 +425,24 @@ impl<'x> EventQueue<'x> {
     //  portion of `self.value_list`. Basically the routine of an insertion
     //  sort.
 
-    /// Push an event into the heap.
+    /// Retract the given trigger from the event at the given
+    /// tag. The event is removed if nothing else is left in it.
+    pub(super) fn retract(&mut self, tag: EventTag, trigger: TriggerId, dataflow: &'x DataflowInfo) {
+        if let Ok(idx) = self.value_list.binary_search_by_key(&tag, |e| e.tag) {
+            self.value_list[idx].retract(trigger, dataflow);
+            if self.value_list[idx].is_empty() {
+                self.value_list.remove(idx);
+            }
+        }
+    }
+
+    /// Push an event into the heap. Events at [EventTag::FOREVER]
+    /// are dropped, as they would never be processed.
     pub(super) fn push(&mut self, evt: Event<'x>) {
+        if evt.tag.is_forever() {
+            trace!("Dropping event at {}", evt.tag);
+            return;
+        }
         match self.value_list.binary_search_by_key(&evt.tag, |e| e.tag) {
             Ok(idx) => self.value_list[idx].absorb(evt),
             Err(idx) => self.value_list.insert(idx, evt),
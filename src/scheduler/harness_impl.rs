@@ -0,0 +1,396 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use index_vec::Idx;
+
+use super::assembly_impl::RootAssembler;
+use super::{SchedulerOptions, SchedulerState, SyncScheduler};
+use crate::assembly::*;
+use crate::scheduler::dependencies::DataflowInfo;
+use crate::*;
+
+/// Wraps a single reactor to test it in isolation. Its
+/// input ports are fed by synthetic upstream ports, and
+/// its output ports are recorded by synthetic downstream
+/// ports. Those are declared with [Self::input] and
+/// [Self::output], before running a [Simulation] with
+/// [Self::run].
+///
+/// The simulation does not follow physical time: tags
+/// are processed as fast as possible, in order, when
+/// the test asks for it. Reactions are executed on the
+/// thread of the test, even with feature `parallel-runtime`.
+pub struct TestHarness<R: ReactorInitializer> {
+    root: RootAssembler,
+    options: SchedulerOptions,
+    reactor: R,
+    /// ID of the synthetic reactor that contains the
+    /// upstream and downstream ports. It's the container
+    /// of the tested reactor.
+    driver_id: ReactorId,
+    first_driver_trigger: TriggerId,
+    driver_reactions: Vec<Box<dyn DriverReaction>>,
+}
+
+impl<R: ReactorInitializer + 'static> TestHarness<R> {
+    /// Assemble the reactor with the given parameters.
+    ///
+    /// # Panics
+    ///
+    /// If the assembly of the reactor fails.
+    pub fn new(params: R::Params) -> Self {
+        Self::with_options(params, SchedulerOptions::default())
+    }
+
+    /// Assemble the reactor with the given parameters and
+    /// options. The options that affect assembly, such as
+    /// [SchedulerOptions::allow_width_mismatch], are used here,
+    /// and the others by [Self::run]. Those that relate to
+    /// physical time, threads or signals are ignored.
+    ///
+    /// # Panics
+    ///
+    /// If the assembly of the reactor fails.
+    pub fn with_options(params: R::Params, options: SchedulerOptions) -> Self {
+        let mut root = RootAssembler::new(&options);
+        let reactor = root.assemble_main::<R>(params);
+
+        let driver_id = root.reactor_id.get_and_incr();
+        root.debug_info
+            .record_reactor(driver_id, ReactorDebugInfo::root_named("TestHarness"));
+        root.debug_info.record_reactor_container(driver_id, reactor.id());
+        let first_driver_trigger = root.cur_trigger;
+
+        Self {
+            root,
+            options,
+            reactor,
+            driver_id,
+            first_driver_trigger,
+            driver_reactions: Vec::new(),
+        }
+    }
+
+    /// Bind an input port of the reactor to a synthetic
+    /// upstream port, to which values can be fed with
+    /// [Simulation::feed].
+    ///
+    /// # Panics
+    ///
+    /// If the port is already bound.
+    pub fn input<T: Sync + 'static>(&mut self, port: impl FnOnce(&mut R) -> &mut Port<T>) -> HarnessInput<T> {
+        let i = self.driver_reactions.len();
+        let trigger = self.next_comp_id(Cow::Owned(format!("feed{}", i)));
+        self.root.graph.record_laction(trigger);
+        let up_id = self.next_comp_id(Cow::Owned(format!("in{}", i)));
+        self.root.graph.record_port(up_id, PortKind::Output);
+
+        let mut upstream = Port::<T>::new(up_id, PortKind::Output);
+        let downstream = port(&mut self.reactor);
+        if let Err(e) = upstream.forward_to(downstream) {
+            std::panic::panic_any(e.lift(&self.root.debug_info))
+        }
+        self.root.graph.port_bind(&upstream, downstream);
+
+        let rid = self.new_driver_reaction("feed");
+        self.root.graph.triggers_reaction(trigger, rid);
+        self.root.graph.reaction_effects(rid, up_id);
+
+        let pending = Rc::new(RefCell::new(BTreeMap::new()));
+        self.driver_reactions
+            .push(Box::new(Feed { port: upstream, pending: pending.clone() }));
+        HarnessInput { trigger, pending }
+    }
+
+    /// Bind an output port of the reactor to a synthetic
+    /// downstream port, which records all values it receives.
+    /// See [Simulation::outputs].
+    pub fn output<T: Sync + Clone + 'static>(&mut self, port: impl FnOnce(&mut R) -> &mut Port<T>) -> HarnessOutput<T> {
+        let i = self.driver_reactions.len();
+        let down_id = self.next_comp_id(Cow::Owned(format!("out{}", i)));
+        self.root.graph.record_port(down_id, PortKind::Input);
+
+        let mut downstream = Port::<T>::new(down_id, PortKind::Input);
+        let upstream = port(&mut self.reactor);
+        if let Err(e) = upstream.forward_to(&mut downstream) {
+            std::panic::panic_any(e.lift(&self.root.debug_info))
+        }
+        self.root.graph.port_bind(upstream, &downstream);
+
+        let rid = self.new_driver_reaction("record");
+        self.root.graph.triggers_reaction(down_id, rid);
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        self.driver_reactions
+            .push(Box::new(Record { port: downstream, log: log.clone() }));
+        HarnessOutput { log }
+    }
+
+    /// Run a simulation. Startup reactions are pending at
+    /// [EventTag::ORIGIN] when the closure is called. When
+    /// it returns, the program is shut down if it's not
+    /// already, so that shutdown reactions are run.
+    ///
+    /// # Panics
+    ///
+    /// If the dependency graph is malformed, eg if there is
+    /// a causality cycle, or if it has lints and
+    /// [SchedulerOptions::deny_lints] is set.
+    pub fn run<O>(self, test: impl FnOnce(&mut Simulation<R>) -> O) -> O {
+        let TestHarness {
+            mut root,
+            options,
+            reactor,
+            driver_id,
+            first_driver_trigger,
+            driver_reactions,
+        } = self;
+
+        root.debug_info
+            .set_id_range(driver_id, first_driver_trigger..root.cur_trigger);
+        root.debug_info.record_main_reactor(driver_id);
+
+        let reactor = Rc::new(RefCell::new(reactor));
+        root.register_boxed(Box::new(Driver { id: driver_id, reactions: driver_reactions }));
+        root.register_boxed(Box::new(SharedReactor(reactor.clone())));
+        let (reactors, graph, id_registry) = root.finish();
+
+        SyncScheduler::check_lints(&graph, &id_registry, &options);
+        let dataflow = DataflowInfo::new(&graph, &id_registry)
+            .map_err(|e| e.lift(&id_registry))
+            .unwrap();
+        let mut state = SchedulerState::new(&options, id_registry, reactors, Instant::now(), SystemTime::now());
+        // the reactors are shared with the test through Rc
+        state.run_sequentially();
+        let mut scheduler = SyncScheduler::new(state, &dataflow);
+        scheduler.push_startup_event();
+
+        let mut sim = Simulation { scheduler, reactor };
+        let result = test(&mut sim);
+        sim.scheduler.shutdown_now();
+        result
+    }
+
+    fn new_driver_reaction(&mut self, label: &'static str) -> GlobalReactionId {
+        let rid = GlobalReactionId::new(self.driver_id, LocalReactionId::from_usize(self.driver_reactions.len()));
+        self.root.graph.record_reaction(rid);
+        self.root.debug_info.record_reaction(rid, Cow::Borrowed(label));
+        rid
+    }
+
+    fn next_comp_id(&mut self, debug_name: Cow<'static, str>) -> TriggerId {
+        let id = self.root.cur_trigger.get_and_incr().expect("Overflow while allocating ID");
+        self.root.debug_info.record_trigger(id, debug_name);
+        id
+    }
+}
+
+/// A running test of a reactor, see [TestHarness::run].
+pub struct Simulation<'x, R> {
+    scheduler: SyncScheduler<'x>,
+    reactor: Rc<RefCell<R>>,
+}
+
+impl<R> Simulation<'_, R> {
+    /// Feed a value to an input port of the reactor at the
+    /// given tag. The tag must be later than the latest
+    /// processed tag.
+    pub fn feed<T: Sync>(&mut self, input: &HarnessInput<T>, tag: EventTag, value: T) {
+        if let Some(latest) = self.scheduler.latest_processed_tag() {
+            assert!(
+                tag > latest,
+                "Cannot feed a value at {}, tag {} was already processed",
+                tag,
+                latest
+            );
+        }
+        input.pending.borrow_mut().insert(tag, value);
+        self.scheduler.push_trigger_event(tag, input.trigger);
+    }
+
+    /// Process the next pending tag, and return it. Returns
+    /// None if there is no pending event or the program
+    /// has been shut down.
+    pub fn step(&mut self) -> Option<EventTag> {
+        self.scheduler.step()
+    }
+
+    /// Process all pending tags up to the given one (inclusive).
+    /// Returns the last processed tag.
+    pub fn advance_to(&mut self, tag: EventTag) -> Option<EventTag> {
+        while !self.scheduler.is_terminated() {
+            match self.scheduler.peek_next_tag() {
+                Some(next) if next <= tag => {
+                    self.step();
+                }
+                _ => break,
+            }
+        }
+        self.current_tag()
+    }
+
+    /// Shut down the program at the earliest possible tag.
+    /// Shutdown reactions are run.
+    pub fn shutdown(&mut self) {
+        self.scheduler.shutdown_now()
+    }
+
+    /// Whether the program has been shut down, eg by a call
+    /// to [ReactionCtx::request_stop].
+    pub fn is_shut_down(&self) -> bool {
+        self.scheduler.is_terminated()
+    }
+
+    /// Returns the latest processed tag.
+    pub fn current_tag(&self) -> Option<EventTag> {
+        self.scheduler.latest_processed_tag()
+    }
+
+    /// Returns the tag of the next event to process.
+    pub fn next_tag(&mut self) -> Option<EventTag> {
+        self.scheduler.peek_next_tag()
+    }
+
+    /// Returns all values received by the output port, with
+    /// the tag at which they were set, in order.
+    pub fn outputs<T: Clone>(&self, output: &HarnessOutput<T>) -> Vec<(EventTag, T)> {
+        output.log.borrow().clone()
+    }
+
+    /// Returns the values received by the output port since
+    /// the last call, with the tag at which they were set.
+    pub fn take_outputs<T>(&mut self, output: &HarnessOutput<T>) -> Vec<(EventTag, T)> {
+        output.log.borrow_mut().drain(..).collect()
+    }
+
+    /// Returns the tags for which the given logical action
+    /// of the reactor is scheduled, in ascending order.
+    pub fn scheduled_tags<T: Sync>(&self, action: impl FnOnce(&R) -> &LogicalAction<T>) -> Vec<EventTag> {
+        action(&self.reactor.borrow()).0.scheduled_tags()
+    }
+
+    /// Inspect the reactor between two tags.
+    pub fn with_reactor<O>(&self, f: impl FnOnce(&R) -> O) -> O {
+        f(&self.reactor.borrow())
+    }
+}
+
+/// Handle to the synthetic upstream port of an input of
+/// the tested reactor, see [TestHarness::input].
+pub struct HarnessInput<T> {
+    trigger: TriggerId,
+    pending: Rc<RefCell<BTreeMap<EventTag, T>>>,
+}
+
+/// Handle to the synthetic downstream port of an output of
+/// the tested reactor, see [TestHarness::output].
+pub struct HarnessOutput<T> {
+    log: Rc<RefCell<Vec<(EventTag, T)>>>,
+}
+
+/// The container of the tested reactor, which owns the
+/// synthetic ports. It has one reaction per port.
+struct Driver {
+    id: ReactorId,
+    reactions: Vec<Box<dyn DriverReaction>>,
+}
+
+impl ReactorBehavior for Driver {
+    fn id(&self) -> ReactorId {
+        self.id
+    }
+
+    fn react(&mut self, ctx: &mut ReactionCtx, local_rid: LocalReactionId) {
+        self.reactions[local_rid.index()].react(ctx)
+    }
+
+    fn cleanup_tag(&mut self, ctx: &CleanupCtx) {
+        for reaction in &mut self.reactions {
+            reaction.cleanup(ctx)
+        }
+    }
+}
+
+trait DriverReaction {
+    fn react(&mut self, ctx: &mut ReactionCtx);
+    fn cleanup(&mut self, ctx: &CleanupCtx);
+}
+
+/// Sets an upstream port with the value fed for the current tag.
+struct Feed<T: Sync> {
+    port: Port<T>,
+    pending: Rc<RefCell<BTreeMap<EventTag, T>>>,
+}
+
+impl<T: Sync> DriverReaction for Feed<T> {
+    fn react(&mut self, ctx: &mut ReactionCtx) {
+        let value = self.pending.borrow_mut().remove(&ctx.get_tag());
+        ctx.set_opt(&mut self.port, value)
+    }
+
+    fn cleanup(&mut self, ctx: &CleanupCtx) {
+        ctx.cleanup_port(&mut self.port)
+    }
+}
+
+/// Records the values of a downstream port.
+struct Record<T: Sync> {
+    port: Port<T>,
+    log: Rc<RefCell<Vec<(EventTag, T)>>>,
+}
+
+impl<T: Sync + Clone> DriverReaction for Record<T> {
+    fn react(&mut self, ctx: &mut ReactionCtx) {
+        if let Some(value) = ctx.use_ref_opt(&self.port, T::clone) {
+            self.log.borrow_mut().push((ctx.get_tag(), value))
+        }
+    }
+
+    fn cleanup(&mut self, ctx: &CleanupCtx) {
+        ctx.cleanup_port(&mut self.port)
+    }
+}
+
+/// The tested reactor, which is shared with the [Simulation].
+struct SharedReactor<R>(Rc<RefCell<R>>);
+
+impl<R: ReactorBehavior> ReactorBehavior for SharedReactor<R> {
+    fn id(&self) -> ReactorId {
+        self.0.borrow().id()
+    }
+
+    fn react(&mut self, ctx: &mut ReactionCtx, local_rid: LocalReactionId) {
+        self.0.borrow_mut().react(ctx, local_rid)
+    }
+
+    fn cleanup_tag(&mut self, ctx: &CleanupCtx) {
+        self.0.borrow_mut().cleanup_tag(ctx)
+    }
+}
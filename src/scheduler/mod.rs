@@ -31,14 +31,19 @@ use index_vec::IndexVec;
 pub use scheduler_impl::*;
 
 use self::dependencies::ExecutableReactions;
+pub(crate) use self::dependencies::GraphId;
 use crate::*;
 
 pub(crate) mod assembly_impl;
+pub(crate) mod builder_impl;
 mod context;
 pub(crate) mod debug;
 mod dependencies;
 mod events;
+pub(crate) mod harness_impl;
 mod scheduler_impl;
+#[cfg(feature = "signals")]
+mod signals;
 
 #[cfg(feature = "public-internals")]
 pub mod internals {
@@ -57,7 +62,7 @@ struct DebugInfoProvider<'a> {
 
 impl DebugInfoProvider<'_> {
     pub(self) fn display_event(&self, evt: &Event) -> String {
-        let Event { tag, reactions, terminate } = evt;
+        let Event { tag, reactions, terminate, .. } = evt;
         let mut str = format!("at {}: run {}", tag, self.display_reactions(reactions));
 
         if *terminate {
@@ -26,20 +26,26 @@
 
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crossbeam_channel::reconnectable::*;
+use smallvec::SmallVec;
 
 use super::assembly_impl::RootAssembler;
+use super::builder_impl::Mutation;
 use super::*;
 use crate::assembly::*;
-use crate::scheduler::dependencies::DataflowInfo;
+use crate::builder::ProgramBuilder;
+use crate::scheduler::dependencies::{DataflowInfo, DepGraph};
 use crate::*;
 
 /// Construction parameters for the scheduler.
 ///
 /// LFC uses target properties to set them. With the "cli"
 /// feature, generated programs also feature CLI options to
-/// override the defaults at runtime.
+/// override the defaults at runtime. Other programs may parse
+/// them from the command line and the environment with the
+/// `cli` module of this crate, enabled by its feature `cli`.
 #[derive(Default)]
 pub struct SchedulerOptions {
     /// If true, we won't shut down the scheduler as soon as
@@ -58,9 +64,53 @@ pub struct SchedulerOptions {
     /// building with feature `parallel-runtime`.
     pub threads: usize,
 
+    /// If true, reactions are not executed level by level, but
+    /// as soon as all the reactions they depend on have completed.
+    /// This avoids waiting for an entire level to complete when
+    /// levels are unbalanced, but has a bookkeeping overhead on
+    /// each tag. Ignored unless building with feature `parallel-runtime`.
+    pub dependency_counting: bool,
+
     /// If true, dump the dependency graph to a file before
     /// starting execution.
     pub dump_graph: bool,
+
+    /// If true, the lints found in the program (eg unbound
+    /// input ports) make it fail to start. Otherwise they are
+    /// only logged as warnings.
+    pub deny_lints: bool,
+
+    /// If true, bindings between ports of different widths
+    /// are truncated, like LFC does, and reported as lints.
+    /// Otherwise they make the program fail to start.
+    pub allow_width_mismatch: bool,
+
+    /// If set, the start of the program (T0) is delayed to the
+    /// next wall-clock time that is a multiple of this duration
+    /// since the UNIX epoch. For instance, with one second, the
+    /// program starts on the next full second.
+    pub align_start: Option<Duration>,
+
+    /// If true, the scheduler does not wait for physical time
+    /// to reach the tag of an event before processing it, so
+    /// that logical time may advance faster than physical time.
+    pub fast: bool,
+
+    /// If true, the first SIGINT or SIGTERM received by the
+    /// process shuts the program down gracefully at the next
+    /// tag, so that shutdown reactions are executed. Another
+    /// signal then exits the process immediately. Ignored
+    /// unless building with feature `signals`.
+    ///
+    /// The process then exits with the conventional status for
+    /// the second signal, 130 for SIGINT and 143 for SIGTERM.
+    /// Outside of UNIX, only Ctrl-C is handled.
+    pub handle_signals: bool,
+
+    /// Parameters of reactors loaded from a configuration
+    /// file, which reactors read while they are assembled.
+    #[cfg(feature = "config")]
+    pub config: Arc<crate::config::Config>,
 }
 
 // Macros are placed a bit out of order to avoid exporting them
@@ -96,7 +146,7 @@ pub struct SyncScheduler<'x> {
     dataflow: &'x DataflowInfo,
 
     /// All reactors.
-    reactors: ReactorVec<'x>,
+    reactors: ReactorVec<'static>,
 
     /// Pending events/ tags to process.
     event_queue: EventQueue<'x>,
@@ -104,12 +154,15 @@ pub struct SyncScheduler<'x> {
     /// Receiver through which asynchronous events are
     /// communicated to the scheduler. We only block when
     /// no events are ready to be processed.
-    rx: Receiver<PhysicalEvent>,
+    rx: Arc<Receiver<PhysicalEvent>>,
 
     /// Initial time of the logical system.
     #[allow(unused)] // might be useful someday
     initial_time: Instant,
 
+    /// Wall-clock time corresponding to [Self::initial_time].
+    initial_time_utc: SystemTime,
+
     /// Scheduled shutdown time. If Some, shutdown will be
     /// initiated at that logical time.
     ///
@@ -125,16 +178,163 @@ pub struct SyncScheduler<'x> {
 
     /// Debug information.
     id_registry: DebugInfoRegistry,
+
+    /// Whether reactions may be executed by several threads.
+    /// See [SchedulerState::run_sequentially].
+    parallel: bool,
+
+    /// Whether to use the dependency-counting executor
+    /// instead of executing reactions level by level.
+    #[cfg(feature = "parallel-runtime")]
+    dependency_counting: bool,
+
+    /// Buffers of the dependency-counting executor.
+    #[cfg(feature = "parallel-runtime")]
+    dependency_counters: parallel_rt_impl::DependencyCounters,
+
+    /// Whether to process events without waiting for
+    /// physical time, see [SchedulerOptions::fast].
+    fast: bool,
+
+    /// Mutations requested at the latest processed tag,
+    /// see [crate::builder::Components::mutate]. If not empty, the event
+    /// loop is suspended to apply them.
+    mutations: Vec<Mutation>,
+}
+
+/// An [Event] of a suspended scheduler. Its plan borrows
+/// the [DataflowInfo], so the reactions it executes are
+/// kept instead.
+struct SuspendedEvent {
+    tag: EventTag,
+    reactions: Vec<GlobalReactionId>,
+    triggers: SmallVec<[TriggerId; 1]>,
+    terminate: bool,
+}
+
+/// The state of a [SyncScheduler], without the [DataflowInfo]
+/// it borrows. The event loop is started from this, and is
+/// suspended to it to mutate the program, after which the
+/// dataflow info is updated and the event loop resumed.
+pub(super) struct SchedulerState {
+    latest_processed_tag: Option<EventTag>,
+    reactors: ReactorVec<'static>,
+    /// Pending events.
+    events: Vec<SuspendedEvent>,
+    rx: Arc<Receiver<PhysicalEvent>>,
+    initial_time: Instant,
+    initial_time_utc: SystemTime,
+    shutdown_time: Option<EventTag>,
+    was_terminated: Arc<AtomicBool>,
+    id_registry: DebugInfoRegistry,
+    parallel: bool,
+    #[cfg(feature = "parallel-runtime")]
+    dependency_counting: bool,
+    fast: bool,
+    /// Mutations to apply before resuming the event loop.
+    mutations: Vec<Mutation>,
+    /// Reactors created by the latest mutations, whose
+    /// startup reactions have not been executed yet.
+    created: Vec<ReactorId>,
+}
+
+impl SchedulerState {
+    /// Creates the state of a scheduler that has not
+    /// processed any tag yet.
+    pub(super) fn new(
+        options: &SchedulerOptions,
+        id_registry: DebugInfoRegistry,
+        reactors: ReactorVec<'static>,
+        initial_time: Instant,
+        initial_time_utc: SystemTime,
+    ) -> Self {
+        if !cfg!(feature = "parallel-runtime") && options.threads != 0 {
+            warn!("'workers' runtime parameter has no effect unless feature 'parallel-runtime' is enabled")
+        }
+        if !cfg!(feature = "parallel-runtime") && options.dependency_counting {
+            warn!("'dependency_counting' runtime parameter has no effect unless feature 'parallel-runtime' is enabled")
+        }
+
+        if options.keep_alive {
+            warn!("'keepalive' runtime parameter has no effect in the Rust target")
+        }
+        if !cfg!(feature = "signals") && options.handle_signals {
+            warn!("'handle_signals' runtime parameter has no effect unless feature 'signals' is enabled")
+        }
+
+        let (_, rx) = unbounded::<PhysicalEvent>();
+        Self {
+            latest_processed_tag: None,
+            reactors,
+            events: Vec::new(),
+            rx: Arc::new(rx),
+            initial_time,
+            initial_time_utc,
+            shutdown_time: options
+                .timeout
+                .map(|timeout| EventTag::ORIGIN.successor(timeout))
+                // a timeout too long to be represented never expires
+                .filter(|shutdown_tag| !shutdown_tag.is_forever())
+                .map(|shutdown_tag| {
+                    trace!("Timeout specified, will shut down at most at tag {}", shutdown_tag);
+                    shutdown_tag
+                }),
+            was_terminated: Default::default(),
+            id_registry,
+            parallel: cfg!(feature = "parallel-runtime"),
+            #[cfg(feature = "parallel-runtime")]
+            dependency_counting: options.dependency_counting,
+            fast: options.fast,
+            mutations: Vec::new(),
+            created: Vec::new(),
+        }
+    }
+
+    /// Execute all reactions on the thread that runs the
+    /// scheduler, even with feature `parallel-runtime`. The
+    /// test harness does this, as its reactors share state
+    /// with the test, which is not thread-safe.
+    pub(super) fn run_sequentially(&mut self) {
+        self.parallel = false;
+    }
 }
 
 impl<'x> SyncScheduler<'x> {
     pub fn run_main<R: ReactorInitializer + 'static>(options: SchedulerOptions, args: R::Params) {
         let start = Instant::now();
         info!("Starting assembly...");
-        let (reactors, graph, id_registry) = RootAssembler::assemble_tree::<R>(args);
+        let (reactors, graph, id_registry) = RootAssembler::assemble_tree::<R>(args, &options);
         let time = Instant::now() - start;
         info!("Assembly done in {} µs...", time.as_micros());
 
+        Self::run_assembled(options, reactors, graph, id_registry, None)
+    }
+
+    /// Report the lints of the dependency graph as warnings.
+    ///
+    /// # Panics
+    ///
+    /// If there are lints and [SchedulerOptions::deny_lints] is set.
+    pub(super) fn check_lints(graph: &DepGraph, id_registry: &DebugInfoRegistry, options: &SchedulerOptions) {
+        let lints = graph.lint();
+        if options.deny_lints && !lints.is_empty() {
+            panic!("{}", AssemblyError(AssemblyErrorImpl::DeniedLints(lints)).lift(id_registry));
+        }
+        for lint in lints {
+            warn!("{}", lint.display(id_registry));
+        }
+    }
+
+    /// Run a program whose reactors have already been assembled.
+    /// If the program was built by a [ProgramBuilder], it may be
+    /// mutated by reactions (see [crate::builder::Components::mutate]).
+    pub(super) fn run_assembled(
+        options: SchedulerOptions,
+        reactors: ReactorVec<'static>,
+        mut graph: DepGraph,
+        id_registry: DebugInfoRegistry,
+        mut builder: Option<ProgramBuilder>,
+    ) {
         if options.dump_graph {
             use std::fs::File;
             use std::io::Write;
@@ -147,8 +347,7 @@ impl<'x> SyncScheduler<'x> {
             eprintln!("Wrote dot file to {}", path.to_string_lossy());
         }
 
-        // collect dependency information
-        let dataflow_info = DataflowInfo::new(graph).map_err(|e| e.lift(&id_registry)).unwrap();
+        Self::check_lints(&graph, &id_registry, &options);
 
         // Using thread::scope here introduces an unnamed lifetime for
         // the scope, which is captured as 't by the SyncScheduler.
@@ -156,45 +355,111 @@ impl<'x> SyncScheduler<'x> {
         // dataflow_info outlives 't, so that physical contexts
         // can be spawned in threads that capture references
         // to 'x.
-        let initial_time = Instant::now();
         #[cfg(feature = "parallel-runtime")]
         let rayon_thread_pool = rayon::ThreadPoolBuilder::new().num_threads(options.threads).build().unwrap();
 
-        let scheduler = SyncScheduler::new(options, id_registry, &dataflow_info, reactors, initial_time);
+        let (initial_time, initial_time_utc) = Self::wait_for_start_time(options.align_start);
+        let mut state = SchedulerState::new(&options, id_registry, reactors, initial_time, initial_time_utc);
+        #[cfg(feature = "signals")]
+        if options.handle_signals {
+            super::signals::forward_signals(&state.rx, initial_time);
+        }
 
-        cfg_if::cfg_if! {
-            if #[cfg(feature = "parallel-runtime")] {
-                /// The unsafe impl is safe if scheduler instances
-                /// are only sent between threads like this (their Rc
-                /// internals are not copied).
-                /// So long as the framework entirely controls the lifetime
-                /// of SyncScheduler instances, this is enforceable.
-                #[allow(non_local_definitions)]
-                unsafe impl Send for SyncScheduler<'_> {}
+        // collect dependency information
+        let mut dataflow_info = DataflowInfo::new(&graph, &state.id_registry)
+            .map_err(|e| e.lift(&state.id_registry))
+            .unwrap();
+        if builder.is_some() {
+            graph.track_changes();
+        }
 
-                // install makes calls to parallel iterators use that thread pool
-                rayon_thread_pool.install(|| scheduler.launch_event_loop());
-            } else {
-                scheduler.launch_event_loop();
+        // Each iteration runs the event loop until the program
+        // terminates, or until it is suspended to be mutated.
+        loop {
+            let scheduler = SyncScheduler::new(state, &dataflow_info);
+
+            cfg_if::cfg_if! {
+                if #[cfg(feature = "parallel-runtime")] {
+                    /// The unsafe impls are safe if scheduler instances
+                    /// and their state are only sent between threads like
+                    /// this (their Rc internals are not copied).
+                    /// So long as the framework entirely controls the lifetime
+                    /// of SyncScheduler instances, this is enforceable.
+                    #[allow(non_local_definitions)]
+                    unsafe impl Send for SyncScheduler<'_> {}
+                    #[allow(non_local_definitions)]
+                    unsafe impl Send for SchedulerState {}
+
+                    // install makes calls to parallel iterators use that thread pool
+                    let suspended = rayon_thread_pool.install(|| scheduler.launch_event_loop());
+                } else {
+                    let suspended = scheduler.launch_event_loop();
+                }
             }
+
+            state = match suspended {
+                Some(state) => state,
+                None => break,
+            };
+            let builder = builder
+                .as_mut()
+                .expect("only reactions of a ProgramBuilder can request mutations");
+            let mutations = std::mem::take(&mut state.mutations);
+            state.created = builder.apply_mutations(mutations, &options, &mut state.reactors, &mut graph, &mut state.id_registry);
+            dataflow_info
+                .update(&mut graph, &state.id_registry)
+                .map_err(|e| e.lift(&state.id_registry))
+                .unwrap();
         }
     }
 
-    /// Launch the event loop in this thread.
-    fn launch_event_loop(mut self) {
+    /// Returns the start time of the program, both as an instant
+    /// and as a wall-clock time. If `align_to` is set, this first
+    /// sleeps until the next wall-clock time that is a multiple
+    /// of that duration (see [SchedulerOptions::align_start]).
+    fn wait_for_start_time(align_to: Option<Duration>) -> (Instant, SystemTime) {
+        let now = Instant::now();
+        let now_utc = SystemTime::now();
+        let wait = match align_to {
+            Some(period) if !period.is_zero() => {
+                let since_epoch = now_utc.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+                let remainder = since_epoch % period.as_nanos();
+                if remainder == 0 {
+                    Duration::ZERO
+                } else {
+                    Duration::from_nanos((period.as_nanos() - remainder) as u64)
+                }
+            }
+            _ => Duration::ZERO,
+        };
+        if !wait.is_zero() {
+            info!("Waiting {} µs to align the start time...", wait.as_micros());
+            std::thread::sleep(wait);
+        }
+        (now + wait, now_utc + wait)
+    }
+
+    /// Launch the event loop in this thread. If the program
+    /// must be mutated, the event loop is suspended and the
+    /// state of the scheduler is returned. Otherwise this
+    /// returns None once the program has terminated.
+    fn launch_event_loop(mut self) -> Option<SchedulerState> {
         /************************************************
          * This is the main event loop of the scheduler *
          ************************************************/
 
-        self.startup();
+        if self.latest_processed_tag.is_none() {
+            self.startup();
+        }
 
         loop {
-            // flush pending events, this doesn't block
-            for evt in self.rx.try_iter() {
-                let evt = evt.make_executable(self.dataflow);
-                push_event!(self, evt);
+            if !self.mutations.is_empty() {
+                return Some(self.suspend());
             }
 
+            // flush pending events, this doesn't block
+            self.flush_async_events();
+
             if let Some(evt) = self.event_queue.take_earliest() {
                 if self.is_after_shutdown(evt.tag) {
                     trace!("Event is late, shutting down - event tag: {}", evt.tag);
@@ -204,7 +469,7 @@ impl<'x> SyncScheduler<'x> {
                 match self.catch_up_physical_time(evt.tag.to_logical_time(self.initial_time)) {
                     Ok(_) => {}
                     Err(async_event) => {
-                        let async_event = async_event.make_executable(self.dataflow);
+                        let async_event = self.accept_async_event(async_event);
                         // an asynchronous event woke our sleep
                         if async_event.tag < evt.tag {
                             // reinsert both events to order them and try again.
@@ -220,12 +485,13 @@ impl<'x> SyncScheduler<'x> {
                 // at this point we're at the correct time
 
                 if evt.terminate || self.shutdown_time == Some(evt.tag) {
-                    return self.shutdown(evt.tag, evt.reactions);
+                    self.shutdown(evt.tag, evt.reactions, &evt.triggers);
+                    return None;
                 }
 
-                self.process_tag(false, evt.tag, evt.reactions);
+                self.process_tag(false, evt.tag, evt.reactions, &evt.triggers);
             } else if let Some(evt) = self.receive_event() {
-                let evt = evt.make_executable(self.dataflow);
+                let evt = self.accept_async_event(evt);
                 // this may block
                 push_event!(self, evt);
                 continue;
@@ -236,47 +502,121 @@ impl<'x> SyncScheduler<'x> {
             }
         } // end loop
 
-        let shutdown_tag = self.shutdown_time.unwrap_or_else(|| EventTag::now(self.initial_time));
-        self.shutdown(shutdown_tag, None);
+        let shutdown_tag = self.shutdown_time.unwrap_or_else(|| {
+            let now = EventTag::now(self.initial_time);
+            match self.latest_processed_tag {
+                // in fast mode, logical time may be ahead of physical time
+                Some(latest) if latest >= now => latest.next_microstep(),
+                _ => now,
+            }
+        });
+        self.shutdown(shutdown_tag, None, &[]);
 
         // self destructor is called here
+        None
     }
 
-    /// Creates a new scheduler. An empty scheduler doesn't
-    /// do anything unless some events are pushed to the queue.
-    /// See [Self::launch_event_loop].
-    fn new(
-        options: SchedulerOptions,
-        id_registry: DebugInfoRegistry,
-        dependency_info: &'x DataflowInfo,
-        reactors: ReactorVec<'x>,
-        initial_time: Instant,
-    ) -> Self {
-        if !cfg!(feature = "parallel-runtime") && options.threads != 0 {
-            warn!("'workers' runtime parameter has no effect unless feature 'parallel-runtime' is enabled")
-        }
-
-        if options.keep_alive {
-            warn!("'keepalive' runtime parameter has no effect in the Rust target")
-        }
-
-        let (_, rx) = unbounded::<PhysicalEvent>();
-        Self {
+    /// Creates a scheduler from its state. A new scheduler
+    /// doesn't do anything unless some events are pushed to
+    /// the queue. See [Self::launch_event_loop].
+    pub(super) fn new(state: SchedulerState, dataflow: &'x DataflowInfo) -> Self {
+        let SchedulerState {
+            latest_processed_tag,
+            reactors,
+            events,
+            rx,
+            initial_time,
+            initial_time_utc,
+            shutdown_time,
+            was_terminated,
+            id_registry,
+            parallel,
+            #[cfg(feature = "parallel-runtime")]
+            dependency_counting,
+            fast,
+            mutations,
+            created,
+        } = state;
+        debug_assert!(mutations.is_empty(), "Mutations were not applied");
+
+        let mut scheduler = Self {
             rx,
 
             event_queue: Default::default(),
             reactors,
 
             initial_time,
-            latest_processed_tag: None,
-            shutdown_time: options.timeout.map(|timeout| {
-                let shutdown_tag = EventTag::ORIGIN.successor(timeout);
-                trace!("Timeout specified, will shut down at most at tag {}", shutdown_tag);
-                shutdown_tag
-            }),
-            dataflow: dependency_info,
+            initial_time_utc,
+            latest_processed_tag,
+            shutdown_time,
+            dataflow,
             id_registry,
-            was_terminated: Default::default(),
+            was_terminated,
+            parallel,
+            #[cfg(feature = "parallel-runtime")]
+            dependency_counting,
+            #[cfg(feature = "parallel-runtime")]
+            dependency_counters: Default::default(),
+            fast,
+            mutations,
+        };
+
+        // The plans of pending events must be recomputed,
+        // as the dataflow graph may have changed.
+        for SuspendedEvent { tag, reactions, triggers, terminate } in events {
+            let reactions = dataflow.plan_reactions(reactions);
+            if reactions.is_some() || !triggers.is_empty() || terminate {
+                let evt = Event { tag, reactions, triggers, terminate };
+                push_event!(scheduler, evt)
+            }
+        }
+        if let (Some(latest), false) = (latest_processed_tag, created.is_empty()) {
+            // run the startup reactions of the created reactors at the next microstep
+            let startup = dataflow.reactions_triggered_by(&TriggerId::STARTUP);
+            let reactions = dataflow.plan_reactions(
+                startup
+                    .batches()
+                    .flat_map(|(_, level)| level.iter())
+                    .filter(|reaction| created.contains(&reaction.0.container())),
+            );
+            if let Some(reactions) = reactions {
+                let evt = Event::execute(latest.next_microstep(), reactions);
+                push_event!(scheduler, evt)
+            }
+        }
+        scheduler
+    }
+
+    /// Suspend the scheduler, so that the program may be
+    /// mutated. Pending events are kept as the reactions they
+    /// execute, since their plans borrow the [DataflowInfo].
+    fn suspend(mut self) -> SchedulerState {
+        trace!("Suspending the scheduler to mutate the program");
+        let mut events = Vec::new();
+        while let Some(Event { tag, reactions, triggers, terminate }) = self.event_queue.take_earliest() {
+            let reactions = reactions
+                .iter()
+                .flat_map(|plan| plan.batches())
+                .flat_map(|(_, level)| level.iter())
+                .collect();
+            events.push(SuspendedEvent { tag, reactions, triggers, terminate });
+        }
+        SchedulerState {
+            latest_processed_tag: self.latest_processed_tag,
+            reactors: self.reactors,
+            events,
+            rx: self.rx,
+            initial_time: self.initial_time,
+            initial_time_utc: self.initial_time_utc,
+            shutdown_time: self.shutdown_time,
+            was_terminated: self.was_terminated,
+            id_registry: self.id_registry,
+            parallel: self.parallel,
+            #[cfg(feature = "parallel-runtime")]
+            dependency_counting: self.dependency_counting,
+            fast: self.fast,
+            mutations: self.mutations,
+            created: Vec::new(),
         }
     }
 
@@ -288,16 +628,16 @@ impl<'x> SyncScheduler<'x> {
         debug_assert!(!self.reactors.is_empty(), "No registered reactors");
 
         let startup_reactions = self.dataflow.reactions_triggered_by(&TriggerId::STARTUP);
-        self.process_tag(false, EventTag::ORIGIN, Some(Cow::Borrowed(startup_reactions)))
+        self.process_tag(false, EventTag::ORIGIN, Some(Cow::Borrowed(startup_reactions)), &[])
     }
 
-    fn shutdown(&mut self, shutdown_tag: EventTag, reactions: ReactionPlan<'x>) {
+    fn shutdown(&mut self, shutdown_tag: EventTag, reactions: ReactionPlan<'x>, triggers: &[TriggerId]) {
         info!("Scheduler is shutting down, at {}", shutdown_tag);
         self.shutdown_time = Some(shutdown_tag);
         let default_plan: ReactionPlan<'x> = Some(Cow::Borrowed(self.dataflow.reactions_triggered_by(&TriggerId::SHUTDOWN)));
         let reactions = ExecutableReactions::merge_cows(reactions, default_plan);
 
-        self.process_tag(true, shutdown_tag, reactions);
+        self.process_tag(true, shutdown_tag, reactions, triggers);
 
         // notify concurrent threads.
         self.was_terminated.store(true, Ordering::SeqCst);
@@ -313,6 +653,22 @@ impl<'x> SyncScheduler<'x> {
         self.shutdown_time.map(|shutdown_t| shutdown_t < t).unwrap_or(false)
     }
 
+    /// Turn an asynchronous event into an event of the queue.
+    /// The tag of a termination request is normally ahead of
+    /// logical time, but may lag behind in fast mode, or if it
+    /// was received while we were sleeping without listening
+    /// to [Self::rx]. The program is then terminated at the
+    /// next tag instead. Other events are left untouched.
+    fn accept_async_event(&self, evt: PhysicalEvent) -> Event<'x> {
+        let mut evt = evt.make_executable(self.dataflow);
+        if let (Some(latest), true) = (self.latest_processed_tag, evt.terminate) {
+            if evt.tag <= latest {
+                evt.tag = latest.next_microstep();
+            }
+        }
+        evt
+    }
+
     /// Wait for an asynchronous event for as long as we can
     /// expect it.
     fn receive_event(&mut self) -> Option<PhysicalEvent> {
@@ -336,7 +692,7 @@ impl<'x> SyncScheduler<'x> {
     fn catch_up_physical_time(&mut self, target: Instant) -> Result<(), PhysicalEvent> {
         let now = Instant::now();
 
-        if now < target {
+        if now < target && !self.fast {
             let t = target - now;
             trace!("  - Need to sleep {} ns", t.as_nanos());
             // we use recv_timeout as a thread::sleep so that
@@ -384,26 +740,35 @@ impl<'x> SyncScheduler<'x> {
         was_terminated_atomic: &'a Arc<AtomicBool>,
         was_terminated: bool,
     ) -> ReactionCtx<'a, 'x> {
-        ReactionCtx::new(
+        let mut ctx = ReactionCtx::new(
             rx,
             tag,
             self.initial_time,
+            self.initial_time_utc,
             todo,
             self.dataflow,
             debug_info,
             was_terminated_atomic,
             was_terminated,
-        )
+        );
+        // events after the shutdown tag are never processed
+        ctx.next_event_tag = self
+            .event_queue
+            .peek_earliest_tag()
+            .filter(|&next| !self.is_after_shutdown(next));
+        ctx.shutdown_tag = self.shutdown_time;
+        ctx
     }
 
     #[inline]
-    pub(super) fn debug(&self) -> DebugInfoProvider {
+    pub(super) fn debug(&self) -> DebugInfoProvider<'_> {
         debug_info!(self)
     }
 
     /// Actually process a tag. The provided reactions are the
-    /// root reactions that startup the "wave".
-    fn process_tag(&mut self, is_shutdown: bool, tag: EventTag, mut reactions: ReactionPlan<'x>) {
+    /// root reactions that startup the "wave". The triggers are
+    /// the actions and timers that are present at this tag.
+    fn process_tag(&mut self, is_shutdown: bool, tag: EventTag, mut reactions: ReactionPlan<'x>, triggers: &[TriggerId]) {
         if cfg!(debug_assertions) {
             if let Some(latest) = self.latest_processed_tag {
                 debug_assert!(tag > latest, "Tag ordering mismatch")
@@ -411,24 +776,51 @@ impl<'x> SyncScheduler<'x> {
         }
         self.latest_processed_tag = Some(tag);
 
+        // Present actions hold a value until the end of the tag,
+        // even if no reaction of their reactor executes.
+        let present_owners = triggers
+            .iter()
+            .flat_map(|trigger| self.dataflow.reactors_to_clean_up(*trigger))
+            .copied();
+
         let mut next_level = reactions.as_ref().and_then(|todo| todo.first_batch());
         if next_level.is_none() {
+            let touched_reactors = present_owners.collect();
+            self.cleanup_reactors(tag, touched_reactors);
             return;
         }
+        let present_owners: SmallVec<[ReactorId; 4]> = present_owners.collect();
 
         let mut ctx = self.new_reaction_ctx(tag, None, &self.rx, debug_info!(self), &self.was_terminated, is_shutdown);
 
+        #[cfg(feature = "parallel-runtime")]
+        if self.parallel && self.dependency_counting {
+            let plan = reactions.as_deref().unwrap();
+            parallel_rt_impl::process_plan(
+                &mut ctx,
+                &mut self.reactors,
+                self.dataflow,
+                plan,
+                &mut self.dependency_counters,
+            );
+            // all reactions have been executed, skip the level loop
+            next_level = None;
+        }
+
         while let Some((level_no, batch)) = next_level {
             let level_no = level_no.cloned();
             trace!("  - Level {}", level_no);
             ctx.cur_level = level_no.key;
+            ctx.insides
+                .touched_reactors
+                .extend(batch.iter().map(|reaction_id| reaction_id.0.container()));
 
             /// Minimum number of reactions (inclusive) required
             /// to parallelize reactions.
             /// TODO experiment with tweaking this
             const PARALLEL_THRESHOLD: usize = 3;
 
-            if cfg!(feature = "parallel-runtime") && batch.len() >= PARALLEL_THRESHOLD {
+            if self.parallel && batch.len() >= PARALLEL_THRESHOLD {
                 #[cfg(feature = "parallel-runtime")]
                 parallel_rt_impl::process_batch(&mut ctx, &mut self.reactors, batch);
             } else {
@@ -443,26 +835,140 @@ impl<'x> SyncScheduler<'x> {
             next_level = reactions.as_ref().and_then(|todo| todo.next_batch(level_no.as_ref()));
         }
 
+        let mut touched_reactors = std::mem::take(&mut ctx.insides.touched_reactors);
+        touched_reactors.extend(present_owners);
+        if is_shutdown {
+            if !ctx.insides.mutations.is_empty() {
+                warn!("Ignoring mutations requested at shutdown");
+            }
+        } else {
+            self.mutations.append(&mut ctx.insides.mutations);
+        }
+        // Retract cancelled events before pushing new ones, which
+        // may have been scheduled again for the same tag.
+        for (tag, trigger) in ctx.insides.cancelled_events.drain(..) {
+            trace!(
+                "Retracting event of {} at {}",
+                self.debug().id_registry.fmt_component(trigger),
+                tag
+            );
+            self.event_queue.retract(tag, trigger, self.dataflow);
+        }
         for evt in ctx.insides.future_events.drain(..) {
             push_event!(self, evt)
         }
 
-        // cleanup tag-specific resources, eg clear port values
+        self.cleanup_reactors(tag, touched_reactors);
+    }
+
+    /// Cleanup tag-specific resources, eg clear port values and
+    /// forget the values of actions. Only reactors that executed
+    /// a reaction, had a port set or own a present action may
+    /// hold such resources.
+    fn cleanup_reactors(&mut self, tag: EventTag, mut touched_reactors: SmallVec<[ReactorId; 16]>) {
+        touched_reactors.sort_unstable();
+        touched_reactors.dedup();
         let ctx = CleanupCtx { tag };
-        // TODO measure performance of cleaning up all reactors w/ virtual dispatch like this.
-        //   see also efforts in the C runtime to  avoid this
-        for reactor in &mut self.reactors {
-            reactor.cleanup_tag(&ctx)
+        for reactor_id in touched_reactors {
+            self.reactors[reactor_id].cleanup_tag(&ctx)
+        }
+    }
+}
+
+/// Stepping API, used by the test harness to drive the
+/// scheduler in virtual time. Contrary to the event loop,
+/// this never waits for physical time to catch up.
+impl SyncScheduler<'_> {
+    /// Push an event that runs the startup reactions at [EventTag::ORIGIN].
+    pub(super) fn push_startup_event(&mut self) {
+        let startup_reactions = self.dataflow.reactions_triggered_by(&TriggerId::STARTUP);
+        push_event!(self, Event::execute(EventTag::ORIGIN, Cow::Borrowed(startup_reactions)))
+    }
+
+    /// Push an event that runs the reactions triggered by the
+    /// given trigger at the given tag.
+    pub(super) fn push_trigger_event(&mut self, tag: EventTag, trigger: TriggerId) {
+        let evt = PhysicalEvent::trigger(tag, trigger).make_executable(self.dataflow);
+        push_event!(self, evt)
+    }
+
+    /// Returns the tag of the next event to process, if any.
+    pub(super) fn peek_next_tag(&mut self) -> Option<EventTag> {
+        self.flush_async_events();
+        self.event_queue.peek_earliest_tag()
+    }
+
+    /// Process the earliest pending tag, and return it.
+    /// Returns None if there is no more event to process,
+    /// or if the scheduler has been shut down.
+    pub(super) fn step(&mut self) -> Option<EventTag> {
+        if self.is_terminated() {
+            return None;
+        }
+        self.flush_async_events();
+
+        let evt = self.event_queue.take_earliest()?;
+        if self.is_after_shutdown(evt.tag) {
+            trace!("Event is late, shutting down - event tag: {}", evt.tag);
+            let shutdown_tag = self.shutdown_time.unwrap();
+            self.shutdown(shutdown_tag, None, &[]);
+            return Some(shutdown_tag);
+        }
+
+        trace!("Processing event {}", self.debug().display_event(&evt));
+        if evt.terminate || self.shutdown_time == Some(evt.tag) {
+            self.shutdown(evt.tag, evt.reactions, &evt.triggers);
+        } else {
+            self.process_tag(false, evt.tag, evt.reactions, &evt.triggers);
+        }
+        assert!(self.mutations.is_empty(), "Mutations are not supported by the test harness");
+        Some(evt.tag)
+    }
+
+    /// Shut down at the earliest possible tag, if the scheduler
+    /// was not shut down already. Events pending for that tag
+    /// are processed along with the shutdown reactions.
+    pub(super) fn shutdown_now(&mut self) {
+        if self.is_terminated() {
+            return;
+        }
+        let tag = self.latest_processed_tag.map_or(EventTag::ORIGIN, |t| t.next_microstep());
+        match self.peek_next_tag() {
+            Some(next) if next == tag => {
+                let evt = self.event_queue.take_earliest().unwrap();
+                self.shutdown(tag, evt.reactions, &evt.triggers)
+            }
+            _ => self.shutdown(tag, None, &[]),
+        }
+    }
+
+    #[inline]
+    pub(super) fn latest_processed_tag(&self) -> Option<EventTag> {
+        self.latest_processed_tag
+    }
+
+    #[inline]
+    pub(super) fn is_terminated(&self) -> bool {
+        self.was_terminated.load(Ordering::SeqCst)
+    }
+
+    fn flush_async_events(&mut self) {
+        for evt in self.rx.try_iter() {
+            let evt = self.accept_async_event(evt);
+            push_event!(self, evt);
         }
     }
 }
 
 #[cfg(feature = "parallel-runtime")]
 mod parallel_rt_impl {
+    use std::sync::Mutex;
+
     use rayon::prelude::*;
+    use smallvec::SmallVec;
 
     use super::*;
-    use crate::scheduler::dependencies::Level;
+    use crate::scheduler::dependencies::{ExecutableReactions, Level, ReactionDependencies};
 
     pub(super) fn process_batch(ctx: &mut ReactionCtx<'_, '_>, reactors: &mut ReactorVec<'_>, batch: &Level) {
         let reactors_mut = UnsafeSharedPointer(reactors.raw.as_mut_ptr());
@@ -490,6 +996,164 @@ mod parallel_rt_impl {
         );
     }
 
+    /// Buffers of the dependency-counting executor, which are
+    /// reused from one tag to the next. Between tags, all
+    /// counters are zero and all flags are false.
+    #[derive(Default)]
+    pub(super) struct DependencyCounters {
+        /// Number of uncompleted dependencies of each reaction
+        /// that may execute at this tag, indexed by reaction.
+        outstanding: Vec<usize>,
+        /// Whether each reaction is triggered at this tag.
+        triggered: Vec<bool>,
+        /// Marks used to find the reactions that may execute.
+        marks: Vec<bool>,
+        /// The reactions that may execute at this tag.
+        reachable: Vec<usize>,
+    }
+
+    /// Executes the reactions of the plan, and those they trigger,
+    /// without a barrier between levels. Each reaction that may
+    /// execute at this tag counts the reactions it depends on that
+    /// have not completed yet. When that count drops to zero, the
+    /// reaction is spawned if it has been triggered, and otherwise
+    /// skipped, which releases its own dependents.
+    pub(super) fn process_plan<'x>(
+        ctx: &mut ReactionCtx<'_, 'x>,
+        reactors: &mut ReactorVec<'_>,
+        dataflow: &'x DataflowInfo,
+        plan: &ExecutableReactions<'x>,
+        counters: &mut DependencyCounters,
+    ) {
+        let deps = dataflow.reaction_dependencies();
+        let len = deps.ids.len();
+        counters.outstanding.resize(len, 0);
+        counters.triggered.resize(len, false);
+        counters.marks.resize(len, false);
+
+        for reaction in plan.reactions() {
+            counters.triggered[deps.indices[&reaction]] = true;
+        }
+
+        // Reactions that are not reachable from the plan cannot
+        // be triggered at this tag, so they're not waited for.
+        match deps.precomputed_counts(plan) {
+            Some(counts) => {
+                counters.reachable.extend_from_slice(&counts.reachable);
+                for (ix, in_degree) in counts.reachable.iter().zip(&counts.in_degrees) {
+                    counters.outstanding[*ix] = *in_degree;
+                }
+            }
+            None => {
+                let roots = plan.reactions().map(|reaction| deps.indices[&reaction]);
+                deps.count(roots, &mut counters.reachable, &mut counters.outstanding, &mut counters.marks);
+            }
+        }
+
+        let roots: SmallVec<[usize; 16]> = counters
+            .reachable
+            .iter()
+            .copied()
+            .filter(|ix| counters.outstanding[*ix] == 0)
+            .collect();
+        let shared = SharedProgress {
+            reactors: UnsafeSharedPointer(reactors.raw.as_mut_ptr()),
+            deps,
+            progress: Mutex::new(Progress {
+                outstanding: std::mem::take(&mut counters.outstanding),
+                triggered: std::mem::take(&mut counters.triggered),
+                ctx: ctx.fork(),
+            }),
+        };
+
+        rayon::scope(|scope| {
+            for ix in roots {
+                spawn_reaction(scope, &shared, ix);
+            }
+        });
+
+        let Progress { outstanding, mut triggered, ctx: done } = shared.progress.into_inner().unwrap();
+        for ix in counters.reachable.drain(..) {
+            debug_assert_eq!(outstanding[ix], 0, "some reactions were never released");
+            triggered[ix] = false;
+        }
+        counters.outstanding = outstanding;
+        counters.triggered = triggered;
+        ctx.insides.absorb(done.insides);
+    }
+
+    fn spawn_reaction<'s>(scope: &rayon::Scope<'s>, shared: &'s SharedProgress<'_, '_, '_>, ix: usize) {
+        scope.spawn(move |scope| {
+            let reaction_id = shared.deps.ids[ix];
+            let mut ctx = shared.progress.lock().unwrap().ctx.fork();
+            let reactor = unsafe {
+                // safety:
+                // - two reactions of the same reactor are ordered by
+                //   priority edges, so they're never released together
+                // - the vec does not change size so there is no reallocation
+                &mut *shared.reactors.0.add(reaction_id.0.container().index())
+            };
+
+            ctx.insides.touched_reactors.push(reaction_id.0.container());
+            ctx.execute(reactor, reaction_id);
+
+            let ready = shared.progress.lock().unwrap().complete(ix, ctx, shared.deps);
+            for successor in ready {
+                spawn_reaction(scope, shared, successor);
+            }
+        });
+    }
+
+    struct SharedProgress<'a, 'x, 'r> {
+        reactors: UnsafeSharedPointer<ReactorBox<'r>>,
+        deps: &'x ReactionDependencies,
+        progress: Mutex<Progress<'a, 'x>>,
+    }
+
+    /// Execution state of a tag, for [process_plan]. Reactions
+    /// are identified by their index in [ReactionDependencies].
+    struct Progress<'a, 'x> {
+        /// Number of uncompleted dependencies of each
+        /// reaction that may execute at this tag.
+        outstanding: Vec<usize>,
+        /// Whether each reaction will be executed when released.
+        triggered: Vec<bool>,
+        /// Accumulates the side effects of completed reactions.
+        /// New contexts are forked from this one.
+        ctx: ReactionCtx<'a, 'x>,
+    }
+
+    impl<'x> Progress<'_, 'x> {
+        /// Records that the reaction has completed, and returns
+        /// the reactions that are now ready to be executed.
+        fn complete(&mut self, ix: usize, mut ctx: ReactionCtx<'_, 'x>, deps: &ReactionDependencies) -> SmallVec<[usize; 4]> {
+            if let Some(todo) = ctx.insides.todo_now.take() {
+                for reaction in todo.reactions() {
+                    self.triggered[deps.indices[&reaction]] = true;
+                }
+            }
+            self.ctx.insides.absorb(ctx.insides);
+
+            let mut ready = SmallVec::new();
+            let mut completed: SmallVec<[usize; 8]> = smallvec::smallvec![ix];
+            while let Some(reaction) = completed.pop() {
+                for &successor in &deps.successors[reaction] {
+                    let n = &mut self.outstanding[successor];
+                    *n -= 1;
+                    if *n == 0 {
+                        if self.triggered[successor] {
+                            ready.push(successor);
+                        } else {
+                            // not triggered, complete it immediately
+                            completed.push(successor);
+                        }
+                    }
+                }
+            }
+            ready
+        }
+    }
+
     #[derive(Copy, Clone)]
     struct UnsafeSharedPointer<T>(*mut T);
 
@@ -506,4 +1170,137 @@ mod parallel_rt_impl {
             Self(self.0.fork())
         }
     }
+
+    #[cfg(test)]
+    mod test {
+        use crate::assembly::PortKind;
+        use crate::prelude::*;
+        use crate::test::testutil::*;
+        use crate::SchedulerOptions;
+
+        #[test]
+        fn test_dependency_counting_executor() {
+            let options = SchedulerOptions {
+                dependency_counting: true,
+                threads: 4,
+                ..Default::default()
+            };
+            let log = run_logged(options, |builder, main, _| {
+                let mut add_incrementer = |name| {
+                    let r = builder.add_child(main, name, ());
+                    let inp = builder.new_port::<u32, _>(r, "in", PortKind::Input);
+                    let out = builder.new_port::<u32, _>(r, "out", PortKind::Output);
+                    builder
+                        .add_reaction(r, move |ctx, _, c| {
+                            let v = ctx.get(c.port(inp)).unwrap();
+                            ctx.set(c.port_mut(out), v + 1)
+                        })
+                        .triggered_by(inp)
+                        .effects(out);
+                    (inp, out)
+                };
+
+                // a long chain and a short one, which are leveled differently,
+                // and one that is never triggered but must not block the sink
+                let [first, second, third] = ["a", "b", "c"].map(&mut add_incrementer);
+                let short = add_incrementer("short");
+                let idle = add_incrementer("idle");
+                builder.connect(first.1, second.0);
+                builder.connect(second.1, third.0);
+
+                builder
+                    .add_reaction(main, move |ctx, _, c| {
+                        ctx.set(c.port_mut(first.0), 1);
+                        ctx.set(c.port_mut(short.0), 10);
+                    })
+                    .on_startup()
+                    .effects(first.0)
+                    .effects(short.0)
+                    .effects(idle.0);
+                builder
+                    .add_reaction(main, move |ctx, log, c| {
+                        let entry = (ctx.get(c.port(third.1)), ctx.get(c.port(short.1)), ctx.get(c.port(idle.1)));
+                        log.lock().unwrap().push(entry)
+                    })
+                    .triggered_by(third.1)
+                    .triggered_by(short.1)
+                    .triggered_by(idle.1);
+            });
+
+            assert_eq!(log, vec![(Some(4), Some(11), None)]);
+        }
+
+        #[test]
+        fn test_dependency_counting_executor_with_merged_events() {
+            let options = SchedulerOptions {
+                timeout: Some(Duration::from_millis(35)),
+                dependency_counting: true,
+                threads: 4,
+                ..Default::default()
+            };
+            let log = run_logged(options, |builder, main, _| {
+                let relay = builder.add_child(main, "relay", ());
+                let fast = builder.new_timer(relay, "fast", Duration::from_millis(10), Duration::from_millis(10));
+                let slow = builder.new_timer(relay, "slow", Duration::from_millis(10), Duration::from_millis(20));
+                let fast_out = builder.new_port::<u32, _>(relay, "fast_out", PortKind::Output);
+                let slow_out = builder.new_port::<u32, _>(relay, "slow_out", PortKind::Output);
+
+                // the events of both timers are merged every 20 ms,
+                // so their plan is not the plan of a single trigger
+                builder
+                    .add_reaction(relay, move |ctx, _, c| ctx.set(c.port_mut(fast_out), 1))
+                    .triggered_by(fast)
+                    .effects(fast_out);
+                builder
+                    .add_reaction(relay, move |ctx, _, c| ctx.set(c.port_mut(slow_out), 2))
+                    .triggered_by(slow)
+                    .effects(slow_out);
+                builder
+                    .add_reaction(main, move |ctx, log, c| {
+                        let entry = (
+                            ctx.get_elapsed_logical_time(),
+                            ctx.get(c.port(fast_out)),
+                            ctx.get(c.port(slow_out)),
+                        );
+                        log.lock().unwrap().push(entry)
+                    })
+                    .triggered_by(fast_out)
+                    .triggered_by(slow_out);
+            });
+
+            let expected: Vec<_> = [(10, Some(1), Some(2)), (20, Some(1), None), (30, Some(1), Some(2))]
+                .iter()
+                .map(|(ms, f, s)| (Duration::from_millis(*ms), *f, *s))
+                .collect();
+            assert_eq!(log, expected);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::prelude::*;
+    use crate::test::testutil::*;
+    use crate::SchedulerOptions;
+
+    #[test]
+    fn test_fast_mode_does_not_wait_for_physical_time() {
+        let options = SchedulerOptions {
+            timeout: Some(Duration::from_secs(60)),
+            fast: true,
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        let log = run_logged(options, |builder, main, _| {
+            let t = builder.new_timer(main, "t", Duration::ZERO, Duration::from_secs(1));
+            builder
+                .add_reaction(main, |ctx, log, _| log.lock().unwrap().push(ctx.get_elapsed_logical_time()))
+                .triggered_by(t);
+        });
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+        let expected: Vec<_> = (0..=60).map(Duration::from_secs).collect();
+        assert_eq!(log, expected);
+    }
 }
@@ -0,0 +1,166 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! Graceful shutdown on SIGINT and SIGTERM, see [SchedulerOptions::handle_signals].
+
+use std::sync::{Arc, Once, Weak};
+use std::time::Instant;
+
+use crossbeam_channel::reconnectable::Receiver;
+use crossbeam_utils::atomic::AtomicCell;
+
+use super::{EventTag, PhysicalEvent};
+#[cfg(doc)]
+use crate::SchedulerOptions;
+
+/// The running program that signals are forwarded to.
+struct SignalTarget {
+    /// Receiver of the scheduler. We only create a sender when
+    /// a signal is received, as a live sender would keep the
+    /// scheduler waiting for asynchronous events forever.
+    rx: Weak<Receiver<PhysicalEvent>>,
+    initial_time: Instant,
+    /// Whether a signal was already forwarded.
+    signaled: bool,
+}
+
+/// The handler can only be set once per process, so it
+/// forwards signals to the program that started last.
+static TARGET: AtomicCell<Option<SignalTarget>> = AtomicCell::new(None);
+static INSTALL_HANDLER: Once = Once::new();
+
+/// Forward SIGINT and SIGTERM to the scheduler that owns the
+/// given receiver, installing the signal handler if needed.
+/// Signals are forwarded until the receiver is dropped.
+pub(super) fn forward_signals(rx: &Arc<Receiver<PhysicalEvent>>, initial_time: Instant) {
+    INSTALL_HANDLER.call_once(|| {
+        if let Err(e) = install_handler() {
+            warn!(
+                "Could not install the signal handler, signals will not shut down the program gracefully: {}",
+                e
+            );
+        }
+    });
+    TARGET.store(Some(SignalTarget {
+        rx: Arc::downgrade(rx),
+        initial_time,
+        signaled: false,
+    }));
+}
+
+/// Handle signals on a dedicated thread, which is told the
+/// number of each signal received.
+#[cfg(unix)]
+fn install_handler() -> std::io::Result<()> {
+    use signal_hook::consts::{SIGINT, SIGTERM};
+
+    let mut signals = signal_hook::iterator::Signals::new([SIGINT, SIGTERM])?;
+    std::thread::Builder::new().name("signals".into()).spawn(move || {
+        for signo in signals.forever() {
+            on_signal(signo);
+        }
+    })?;
+    Ok(())
+}
+
+/// Outside of UNIX the handler is only called for Ctrl-C,
+/// which we treat as SIGINT.
+#[cfg(not(unix))]
+fn install_handler() -> Result<(), ctrlc::Error> {
+    const SIGINT: i32 = 2;
+    ctrlc::set_handler(|| on_signal(SIGINT))
+}
+
+/// The first signal requests the shutdown of the program at
+/// the next tag. Subsequent signals, and signals received
+/// while no program is running, exit the process immediately,
+/// with the conventional status for the signal received.
+fn on_signal(signo: i32) {
+    if let Some(mut target) = TARGET.take() {
+        if let (false, Some(rx)) = (target.signaled, target.rx.upgrade()) {
+            let tag = EventTag::now(target.initial_time);
+            if rx.new_sender().send(PhysicalEvent::terminate_at(tag)).is_ok() {
+                info!("Received a termination signal, shutting down (signal again to exit immediately)");
+                target.signaled = true;
+                TARGET.store(Some(target));
+                return;
+            }
+        }
+    }
+    std::process::exit(exit_status(signo))
+}
+
+/// Status of a process killed by the given signal, as
+/// reported by shells (130 for SIGINT, 143 for SIGTERM).
+fn exit_status(signo: i32) -> i32 {
+    128 + signo
+}
+
+#[cfg(test)]
+mod test {
+    use crate::prelude::*;
+    use crate::test::testutil::*;
+    use crate::SchedulerOptions;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_exit_status_depends_on_the_signal() {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+
+        assert_eq!(super::exit_status(SIGINT), 130);
+        assert_eq!(super::exit_status(SIGTERM), 143);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_first_signal_shuts_down_gracefully() {
+        let options = SchedulerOptions {
+            timeout: Some(Duration::from_secs(10)),
+            handle_signals: true,
+            ..Default::default()
+        };
+        let log = run_logged(options, |builder, main, _| {
+            let t = builder.new_timer(main, "t", Duration::ZERO, Duration::from_millis(10));
+            builder
+                .add_reaction(main, move |_, _, _| {
+                    let status = std::process::Command::new("kill")
+                        .args(["-INT", &std::process::id().to_string()])
+                        .status()
+                        .unwrap();
+                    assert!(status.success());
+                })
+                .on_startup();
+            builder
+                .add_reaction(main, move |ctx, log, _| log.lock().unwrap().push(("t", ctx.get_tag())))
+                .triggered_by(t);
+            builder
+                .add_reaction(main, move |ctx, log, _| log.lock().unwrap().push(("shutdown", ctx.get_tag())))
+                .on_shutdown();
+        });
+
+        let (last, shutdown_tag) = *log.last().unwrap();
+        assert_eq!(last, "shutdown");
+        assert!(shutdown_tag < tag!(T0 + 10 s));
+    }
+}
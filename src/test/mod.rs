@@ -23,5 +23,12 @@
  */
 
 pub mod stuff_that_must_compile;
+pub mod test_builder;
+#[cfg(feature = "cli")]
+pub mod test_cli;
+pub mod test_codec;
+#[cfg(feature = "config")]
+pub mod test_config;
+pub mod test_harness;
 pub mod test_ports;
 pub mod testutil;
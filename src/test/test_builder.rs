@@ -0,0 +1,91 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use crate::assembly::PortKind;
+use crate::builder::*;
+use crate::SchedulerOptions;
+
+#[test]
+#[should_panic(expected = "Cyclic dependency graph: /a/0 -> /a/out -> /b/in -> /b/0 -> /b/out -> /a/in -> /a/0")]
+fn test_cycle_is_rejected() {
+    let mut builder = ProgramBuilder::new();
+    let main = builder.add_main(());
+    let a = builder.add_child(main, "a", ());
+    let b = builder.add_child(main, "b", ());
+    let mut ports = Vec::new();
+    for r in [a, b] {
+        let inp = builder.new_port::<u32, _>(r, "in", PortKind::Input);
+        let out = builder.new_port::<u32, _>(r, "out", PortKind::Output);
+        builder
+            .add_reaction(r, move |ctx, _, c| {
+                let v = ctx.get(c.port(inp));
+                ctx.set_opt(c.port_mut(out), v)
+            })
+            .triggered_by(inp)
+            .effects(out);
+        ports.push((inp, out));
+    }
+    builder.connect(ports[0].1, ports[1].0);
+    builder.connect(ports[1].1, ports[0].0);
+
+    builder.run(SchedulerOptions::default());
+}
+
+#[test]
+#[should_panic(expected = "Binding from /source/out[0] to /sink/in[0] has mismatched widths (3 vs 2)")]
+fn test_denied_lints_fail_assembly() {
+    let mut builder = ProgramBuilder::new();
+    let main = builder.add_main(());
+    let source = builder.add_child(main, "source", ());
+    let sink = builder.add_child(main, "sink", ());
+    let out = builder.new_multiport::<u32, _>(source, "out", PortKind::Output, 3);
+    let inp = builder.new_multiport::<u32, _>(sink, "in", PortKind::Input, 2);
+    builder.connect_multiports(out, inp);
+
+    builder
+        .add_reaction(source, move |ctx, _, c| ctx.set(c.port_mut(out.channel(0)), 5))
+        .on_startup()
+        .effects(out);
+    builder.add_reaction(sink, |_, _, _| {}).triggered_by(inp);
+
+    builder.run(SchedulerOptions {
+        deny_lints: true,
+        allow_width_mismatch: true,
+        ..Default::default()
+    });
+}
+
+#[test]
+#[should_panic(expected = "Cannot bind /source/out[0] (width 3) to /sink/in[0] (width 2), their widths differ")]
+fn test_width_mismatch_fails_assembly() {
+    let mut builder = ProgramBuilder::new();
+    let main = builder.add_main(());
+    let source = builder.add_child(main, "source", ());
+    let sink = builder.add_child(main, "sink", ());
+    let out = builder.new_multiport::<u32, _>(source, "out", PortKind::Output, 3);
+    let inp = builder.new_multiport::<u32, _>(sink, "in", PortKind::Input, 2);
+    builder.connect_multiports(out, inp);
+
+    builder.run(SchedulerOptions::default());
+}
@@ -0,0 +1,133 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use crate::cli::*;
+use crate::{Duration, SchedulerOptions};
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+fn cli() -> Cli {
+    Cli::new("test")
+        .param::<Duration>("period", "Period")
+        .param::<u32>("sensor-count", "Number of sensors")
+}
+
+#[test]
+fn test_parse_options() {
+    let parsed = cli()
+        .parse_from(
+            args(&["--timeout", "3 sec", "-w", "4", "--fast", "--dump-graph=false", "-k", "true"]),
+            env(&[]),
+        )
+        .unwrap();
+    let options = &parsed.options;
+    assert_eq!(options.timeout, Some(Duration::from_secs(3)));
+    assert_eq!(options.threads, 4);
+    assert!(options.fast);
+    assert!(!options.dump_graph);
+    assert!(options.keep_alive);
+    assert_eq!(parsed.log_level, None);
+}
+
+#[test]
+fn test_args_override_env_and_defaults() {
+    let defaults = SchedulerOptions {
+        timeout: Some(Duration::from_secs(1)),
+        threads: 2,
+        ..Default::default()
+    };
+    let parsed = cli()
+        .defaults(defaults)
+        .parse_from(
+            args(&["--timeout=20ms", "--log-level", "debug"]),
+            env(&[("REACTOR_TIMEOUT", "10 ms"), ("REACTOR_FAST", "true"), ("PATH", "/bin")]),
+        )
+        .unwrap();
+    assert_eq!(parsed.options.timeout, Some(Duration::from_millis(20)));
+    assert_eq!(parsed.options.threads, 2);
+    assert!(parsed.options.fast);
+    assert_eq!(parsed.log_level, Some(log::LevelFilter::Debug));
+}
+
+#[test]
+fn test_parse_params() {
+    let parsed = cli()
+        .parse_from(args(&["--period", "20 ms"]), env(&[("REACTOR_PARAM_SENSOR_COUNT", "3")]))
+        .unwrap();
+    assert_eq!(parsed.param("period", Duration::ZERO), Duration::from_millis(20));
+    assert_eq!(parsed.param("sensor-count", 1u32), 3);
+
+    let parsed = cli().parse_from(args(&[]), env(&[])).unwrap();
+    assert_eq!(parsed.param("period", Duration::from_secs(1)), Duration::from_secs(1));
+}
+
+#[test]
+#[should_panic(expected = "Parameter 'period' was declared with another type")]
+fn test_param_with_wrong_type() {
+    let parsed = cli().parse_from(args(&[]), env(&[])).unwrap();
+    parsed.param("period", 1u32);
+}
+
+#[test]
+fn test_parse_errors() {
+    let err = |a: &[&str]| cli().parse_from(args(a), env(&[])).err().unwrap();
+
+    assert_eq!(err(&["--period"]), CliError::MissingValue("--period".into()));
+    assert_eq!(err(&["--frobnicate"]), CliError::UnknownOption("--frobnicate".into()));
+    assert_eq!(
+        err(&["--period", "20"]),
+        CliError::InvalidValue {
+            option: "--period".into(),
+            value: "20".into(),
+            message: "time unit required".into()
+        }
+    );
+    assert_matches!(err(&["--workers", "many"]), CliError::InvalidValue { .. });
+    assert_matches!(err(&["--log-level", "loud"]), CliError::InvalidValue { .. });
+
+    let env_err = cli()
+        .parse_from(args(&[]), env(&[("REACTOR_PARAM_SENSOR_COUNT", "-1")]))
+        .err()
+        .unwrap();
+    assert_matches!(env_err, CliError::InvalidValue { option, .. } if option == "REACTOR_PARAM_SENSOR_COUNT");
+}
+
+#[test]
+fn test_help() {
+    match cli().parse_from(args(&["--timeout", "1s", "-h"]), env(&[])) {
+        Err(CliError::Help(usage)) => {
+            assert!(usage.starts_with("Usage: test [OPTIONS]"));
+            assert!(usage.contains("--sensor-count <int>"));
+            assert!(usage.contains("[env: REACTOR_PARAM_SENSOR_COUNT]"));
+            assert!(usage.contains("-f, --fast [<bool>]"));
+        }
+        _ => panic!("expected help"),
+    }
+}
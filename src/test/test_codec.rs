@@ -0,0 +1,147 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+use crate::assembly::PortKind;
+use crate::builder::*;
+use crate::codec::*;
+use crate::prelude::*;
+use crate::test::testutil::Log;
+use crate::triggers::TriggerLike;
+use crate::SchedulerOptions;
+
+/// Encodes integers in little-endian order.
+struct LeBytes;
+
+impl ValueCodec<u32> for LeBytes {
+    fn encode(&self, value: &u32) -> Result<Vec<u8>, CodecError> {
+        Ok(value.to_le_bytes().to_vec())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<u32, CodecError> {
+        let bytes = bytes.try_into().map_err(|_| CodecError::Failed("expected 4 bytes".into()))?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+}
+
+#[test]
+fn test_encode_and_decode_port_values() {
+    let log: Log<Result<Option<Vec<u8>>, CodecError>> = Default::default();
+
+    let mut builder = ProgramBuilder::new();
+    let main = builder.add_main(log.clone());
+    let sender = builder.add_child(main, "sender", ());
+    let out = builder.new_port::<u32, _>(sender, "out", PortKind::Output);
+    let raw = builder.new_port::<u32, _>(sender, "raw", PortKind::Output);
+    builder.register_codec(out, LeBytes);
+    builder
+        .add_reaction(sender, move |ctx, _, c| {
+            assert_eq!(
+                ctx.decode(c.port(out), &[1, 2]),
+                Err(CodecError::Failed("expected 4 bytes".into()))
+            );
+            assert_eq!(ctx.decode(c.port(raw), &[7, 0, 0, 0]), Err(CodecError::Unregistered));
+            let v = ctx.decode(c.port(out), &[7, 0, 0, 0]).unwrap();
+            ctx.set(c.port_mut(out), v);
+            ctx.set(c.port_mut(raw), v);
+        })
+        .on_startup()
+        .effects(out)
+        .effects(raw);
+    builder
+        .add_reaction(main, move |ctx, log, c| {
+            let codec = ctx.codec(c.port(out).get_id()).unwrap();
+            assert_eq!(codec.value_type(), "u32");
+            assert_matches!(codec.encode_any(&"seven"), Err(CodecError::TypeMismatch { .. }));
+            assert!(ctx.codec(c.port(raw).get_id()).is_none());
+
+            let mut log = log.lock().unwrap();
+            log.push(ctx.encode(c.port(out)));
+            log.push(ctx.encode(c.port(raw)));
+        })
+        .triggered_by(out)
+        .triggered_by(raw);
+
+    builder.run(SchedulerOptions::default());
+
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![Ok(Some(vec![7, 0, 0, 0])), Err(CodecError::Unregistered)]
+    );
+}
+
+#[test]
+fn test_multiport_codec_applies_to_new_channels() {
+    let log: Log<Vec<bool>> = Default::default();
+
+    let mut builder = ProgramBuilder::new();
+    let main = builder.add_main(log.clone());
+    let outs = builder.new_multiport::<u32, _>(main, "outs", PortKind::Output, 1);
+    let t = builder.new_timer(main, "t", Duration::from_millis(1), Duration::ZERO);
+    builder.register_codec(outs, LeBytes);
+    builder
+        .add_reaction(main, move |_, _, c| {
+            c.mutate(move |b| {
+                b.resize_multiport(outs, 3);
+            })
+        })
+        .on_startup();
+    builder
+        .add_reaction(main, move |ctx, log, c| {
+            let registered = c.multiport(outs).iter().map(|p| ctx.codec(p.get_id()).is_some()).collect();
+            log.lock().unwrap().push(registered);
+        })
+        .triggered_by(t)
+        .effects(outs);
+
+    builder.run(SchedulerOptions::default());
+
+    assert_eq!(*log.lock().unwrap(), vec![vec![true, true, true]]);
+}
+
+#[cfg(feature = "serde-codec")]
+#[test]
+fn test_json_codec() {
+    let log: Log<Vec<u8>> = Default::default();
+
+    let mut builder = ProgramBuilder::new();
+    let main = builder.add_main(log.clone());
+    let a = builder.new_logical_action::<(String, u32), _>(main, "a", None);
+    builder.register_codec(a, JsonCodec);
+    builder
+        .add_reaction(main, move |ctx, _, c| {
+            let v = ctx.decode(c.logical_action(a), br#"["x",1]"#).unwrap();
+            ctx.schedule_with_v(c.logical_action_mut(a), Some(v), Asap);
+        })
+        .on_startup()
+        .effects(a);
+    builder
+        .add_reaction(main, move |ctx, log, c| {
+            let bytes = ctx.encode(c.logical_action(a)).unwrap().unwrap();
+            log.lock().unwrap().push(bytes);
+        })
+        .triggered_by(a);
+
+    builder.run(SchedulerOptions::default());
+
+    assert_eq!(*log.lock().unwrap(), vec![br#"["x",1]"#.to_vec()]);
+}
@@ -0,0 +1,263 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use std::sync::{Arc, Mutex};
+
+use crate::assembly::*;
+use crate::builder::*;
+use crate::cli::Cli;
+use crate::config::*;
+use crate::prelude::*;
+use crate::{CleanupCtx, LocalReactionId, ReactorBehavior, ReactorId, SchedulerOptions};
+
+const TOML: &str = r#"
+[scheduler]
+timeout = "5 sec"
+workers = 4
+fast = true
+log-level = "info"
+
+[params]
+period = "10 ms"
+"main.sensors[3].period" = "20 ms"
+
+[params.main."sensors[2]"]
+period = "30 ms"
+label = "outdoor"
+"#;
+
+#[test]
+fn test_load_toml() {
+    let config = Config::from_toml_str(TOML).unwrap();
+
+    let mut options = SchedulerOptions::default();
+    config.apply_options(&mut options);
+    assert_eq!(options.timeout, Some(Duration::from_secs(5)));
+    assert_eq!(options.threads, 4);
+    assert!(options.fast);
+    assert_eq!(config.log_level(), Some(log::LevelFilter::Info));
+
+    assert_eq!(config.param("period"), Ok(Some(Duration::from_millis(10))));
+    assert_eq!(config.param("main.period"), Ok(Some(Duration::from_millis(10))));
+    assert_eq!(config.param("sensors[3].period"), Ok(Some(Duration::from_millis(20))));
+    assert_eq!(config.param("main.sensors[2].period"), Ok(Some(Duration::from_millis(30))));
+    assert_eq!(config.param("main.sensors[2].label"), Ok(Some("outdoor".to_owned())));
+    assert_eq!(config.param::<Duration>("main.sensors[1].period"), Ok(None));
+    assert_matches!(
+        config.param::<u32>("main.sensors[2].label"),
+        Err(ConfigError::InvalidEntry { key, .. }) if key == "params.main.sensors[2].label"
+    );
+}
+
+#[test]
+fn test_load_json() {
+    let config = Config::from_json_str(
+        r#"{
+            "scheduler": { "timeout": "1 ms", "keepalive": true },
+            "params": { "count": 3, "main": { "sensors[0]": { "enabled": false } } }
+        }"#,
+    )
+    .unwrap();
+
+    let mut options = SchedulerOptions::default();
+    config.apply_options(&mut options);
+    assert_eq!(options.timeout, Some(Duration::from_millis(1)));
+    assert!(options.keep_alive);
+    assert_eq!(config.log_level(), None);
+    assert_eq!(config.param("count"), Ok(Some(3u32)));
+    assert_eq!(config.param("sensors[0].enabled"), Ok(Some(false)));
+}
+
+#[test]
+fn test_invalid_config() {
+    let entry_err = |text: &str| match Config::from_toml_str(text) {
+        Err(ConfigError::InvalidEntry { key, .. }) => key,
+        _ => panic!("expected an invalid entry"),
+    };
+    assert_eq!(entry_err("[scheduler]\ntimeout = 5"), "scheduler.timeout");
+    assert_eq!(entry_err("[scheduler]\nconfig = \"other.toml\""), "scheduler.config");
+    assert_eq!(entry_err("[params]\nperiods = [1, 2]"), "periods");
+    assert_eq!(entry_err("timeout = \"5 sec\""), "timeout");
+
+    assert_matches!(Config::from_toml_str("[scheduler"), Err(ConfigError::Syntax(_)));
+    assert_matches!(Config::from_json_str("{"), Err(ConfigError::Syntax(_)));
+    assert_matches!(Config::load("config.yaml"), Err(ConfigError::UnknownFormat(_)));
+    assert_matches!(Config::load("does/not/exist.toml"), Err(ConfigError::Io(_)));
+}
+
+#[test]
+fn test_cli_loads_config_file() {
+    let path = std::env::temp_dir().join(format!("reactor_rt_test_config_{}.toml", std::process::id()));
+    std::fs::write(&path, TOML).unwrap();
+
+    let args = vec!["--config".to_owned(), path.display().to_string(), "--workers=2".to_owned()];
+    let env = vec![("REACTOR_TIMEOUT".to_owned(), "1 sec".to_owned())];
+    let parsed = Cli::new("test").param::<Duration>("period", "Period").parse_from(args, env);
+    std::fs::remove_file(&path).unwrap();
+
+    let parsed = parsed.ok().unwrap();
+    // the environment and arguments override the file
+    assert_eq!(parsed.options.timeout, Some(Duration::from_secs(1)));
+    assert_eq!(parsed.options.threads, 2);
+    assert!(parsed.options.fast);
+    assert_eq!(parsed.log_level, Some(log::LevelFilter::Info));
+    assert_eq!(parsed.param("period", Duration::ZERO), Duration::from_millis(10));
+    assert_eq!(
+        parsed.options.config.param("sensors[3].period"),
+        Ok(Some(Duration::from_millis(20)))
+    );
+}
+
+#[test]
+fn test_builder_reads_params_by_path() {
+    let config = Config::from_toml_str(TOML).unwrap();
+    let log: Arc<Mutex<Vec<Duration>>> = Default::default();
+
+    let mut builder = ProgramBuilder::new();
+    let main = builder.add_main(());
+    let sensors = builder.add_child_bank(main, "sensors", 4, |_| ());
+    for &sensor in &sensors {
+        let path = builder.path(sensor);
+        let period = config
+            .param(&format!("{}.period", path))
+            .unwrap()
+            .unwrap_or(Duration::from_millis(5));
+        let out = builder.new_port::<u32, _>(sensor, "out", PortKind::Output);
+        let log = log.clone();
+        builder
+            .add_reaction(sensor, move |_, _, _| log.lock().unwrap().push(period))
+            .on_startup()
+            .effects(out);
+    }
+    assert_eq!(builder.path(main), "main");
+    assert_eq!(builder.path(sensors[3]), "main.sensors[3]");
+
+    builder.run(SchedulerOptions::default());
+
+    let mut periods = log.lock().unwrap().clone();
+    periods.sort();
+    let expected: Vec<Duration> = [5, 5, 20, 30].iter().map(|&ms| Duration::from_millis(ms)).collect();
+    assert_eq!(periods, expected);
+}
+
+type PeriodLog = Arc<Mutex<Vec<(u32, Duration)>>>;
+
+/// A reactor written like LFC would generate it, which reads
+/// its parameter from the configuration.
+/// ```lf
+/// reactor Sensor(index: u32 = 0, period: time = 5 ms) {
+///     reaction(startup) {= /* log the period */ =}
+/// }
+/// main reactor {
+///     sensors = new[4] Sensor(index = bank_index);
+/// }
+/// ```
+struct Sensor {
+    __id: ReactorId,
+    index: u32,
+    period: Duration,
+    log: PeriodLog,
+}
+
+impl ReactorInitializer for Sensor {
+    type Wrapped = Sensor;
+    type Params = (u32, Duration, PeriodLog);
+    const MAX_REACTION_ID: LocalReactionId = LocalReactionId::new(1);
+
+    fn assemble((index, period, log): Self::Params, __ctx: AssemblyCtx<Self>) -> AssemblyResult<FinishedReactor<Self>> {
+        let period = __ctx.param("period", period);
+        __ctx.assemble(|__ctx| {
+            __ctx.assemble_self(
+                |_, id| Ok(Sensor { __id: id, index, period, log }),
+                1,
+                [None],
+                |__assembler, _, [react_0]| __assembler.declare_triggers(TriggerId::STARTUP, react_0),
+            )
+        })
+    }
+}
+
+impl ReactorBehavior for Sensor {
+    fn id(&self) -> ReactorId {
+        self.__id
+    }
+
+    fn react(&mut self, _: &mut ReactionCtx, _: LocalReactionId) {
+        self.log.lock().unwrap().push((self.index, self.period));
+    }
+
+    fn cleanup_tag(&mut self, _: &CleanupCtx) {}
+}
+
+struct Main {
+    __id: ReactorId,
+}
+
+impl ReactorInitializer for Main {
+    type Wrapped = Main;
+    type Params = PeriodLog;
+    const MAX_REACTION_ID: LocalReactionId = LocalReactionId::new(0);
+
+    fn assemble(log: Self::Params, __ctx: AssemblyCtx<Self>) -> AssemblyResult<FinishedReactor<Self>> {
+        __ctx.assemble(|__ctx| {
+            __ctx.with_child_bank::<Sensor, _, _>(
+                "sensors",
+                4,
+                |i| (i as u32, Duration::from_millis(5), log.clone()),
+                |__ctx, _| __ctx.assemble_self(|_, id| Ok(Main { __id: id }), 0, [], |_, _, []| Ok(())),
+            )
+        })
+    }
+}
+
+impl ReactorBehavior for Main {
+    fn id(&self) -> ReactorId {
+        self.__id
+    }
+
+    fn react(&mut self, _: &mut ReactionCtx, _: LocalReactionId) {
+        unreachable!()
+    }
+
+    fn cleanup_tag(&mut self, _: &CleanupCtx) {}
+}
+
+#[test]
+fn test_reactors_read_params_by_path() {
+    let log: PeriodLog = Default::default();
+    let options = SchedulerOptions {
+        config: Arc::new(Config::from_toml_str(TOML).unwrap()),
+        ..Default::default()
+    };
+    crate::SyncScheduler::run_main::<Main>(options, log.clone());
+
+    let mut periods = log.lock().unwrap().clone();
+    periods.sort();
+    let expected: Vec<(u32, Duration)> = [5, 5, 30, 20]
+        .iter()
+        .enumerate()
+        .map(|(i, &ms)| (i as u32, Duration::from_millis(ms)))
+        .collect();
+    assert_eq!(periods, expected);
+}
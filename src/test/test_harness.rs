@@ -0,0 +1,296 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use crate::assembly::*;
+use crate::harness::*;
+use crate::prelude::*;
+use crate::{tag, CleanupCtx, LocalReactionId, ReactorBehavior, ReactorId, SchedulerOptions};
+
+/// A reactor written like LFC would generate it.
+/// ```lf
+/// reactor DelayedDoubler {
+///     state started: bool;
+///     state stopped: bool;
+///     input inp: u32;
+///     output out: u32;
+///     logical action act(10 ms): u32;
+///
+///     reaction(startup) {= self.started = true; =}
+///     reaction(inp) -> act {= ctx.schedule_with_v(act, ctx.get(inp), Asap) =}
+///     reaction(act) -> out {= ctx.set(out, 2 * ctx.get(act).unwrap()) =}
+///     reaction(shutdown) {= self.stopped = true; =}
+/// }
+/// ```
+struct DelayedDoubler {
+    __id: ReactorId,
+    started: bool,
+    stopped: bool,
+    __inp: Port<u32>,
+    __out: Port<u32>,
+    __act: LogicalAction<u32>,
+}
+
+impl ReactorInitializer for DelayedDoubler {
+    type Wrapped = DelayedDoubler;
+    type Params = ();
+    const MAX_REACTION_ID: LocalReactionId = LocalReactionId::new(4);
+
+    fn assemble(_: Self::Params, __ctx: AssemblyCtx<Self>) -> AssemblyResult<FinishedReactor<Self>> {
+        __ctx.assemble(|__ctx| {
+            __ctx.assemble_self(
+                |cc, id| {
+                    Ok(DelayedDoubler {
+                        __id: id,
+                        started: false,
+                        stopped: false,
+                        __inp: cc.new_port("inp", PortKind::Input),
+                        __out: cc.new_port("out", PortKind::Output),
+                        __act: cc.new_logical_action("act", Some(Duration::from_millis(10))),
+                    })
+                },
+                4,
+                [None, None, None, None],
+                |__assembler, __self, [react_0, react_1, react_2, react_3]| {
+                    __assembler.declare_triggers(TriggerId::STARTUP, react_0)?;
+                    __assembler.declare_triggers(__self.__inp.get_id(), react_1)?;
+                    __assembler.effects_logical_action(react_1, &__self.__act)?;
+                    __assembler.declare_triggers(__self.__act.get_id(), react_2)?;
+                    __assembler.effects_port(react_2, &__self.__out)?;
+                    __assembler.declare_triggers(TriggerId::SHUTDOWN, react_3)?;
+                    Ok(())
+                },
+            )
+        })
+    }
+}
+
+impl ReactorBehavior for DelayedDoubler {
+    fn id(&self) -> ReactorId {
+        self.__id
+    }
+
+    fn react(&mut self, ctx: &mut ReactionCtx, rid: LocalReactionId) {
+        match rid.raw() {
+            0 => self.started = true,
+            1 => ctx.schedule_with_v(&mut self.__act, ctx.get(&self.__inp), Asap),
+            2 => ctx.set(&mut self.__out, 2 * ctx.get(&self.__act).unwrap()),
+            3 => self.stopped = true,
+            _ => unreachable!(),
+        }
+    }
+
+    fn cleanup_tag(&mut self, ctx: &CleanupCtx) {
+        ctx.cleanup_port(&mut self.__inp);
+        ctx.cleanup_port(&mut self.__out);
+        ctx.cleanup_logical_action(&mut self.__act);
+    }
+}
+
+#[test]
+fn test_feed_and_record() {
+    let mut harness = TestHarness::<DelayedDoubler>::new(());
+    let inp = harness.input(|r| &mut r.__inp);
+    let out = harness.output(|r| &mut r.__out);
+
+    harness.run(|sim| {
+        sim.feed(&inp, tag!(T0 + 5 ms), 1);
+        sim.feed(&inp, tag!(T0 + 6 ms), 2);
+        sim.feed(&inp, tag!(T0 + 1 sec), 3);
+
+        assert_eq!(sim.advance_to(tag!(T0 + 16 ms)), Some(tag!(T0 + 16 ms)));
+        assert_eq!(sim.outputs(&out), vec![(tag!(T0 + 15 ms), 2), (tag!(T0 + 16 ms), 4)]);
+
+        assert_eq!(sim.step(), Some(tag!(T0 + 1 sec)));
+        assert_eq!(sim.step(), Some(tag!(T0 + 1010 ms)));
+        assert_eq!(sim.step(), None);
+        assert_eq!(sim.take_outputs(&out).len(), 3);
+        assert_eq!(sim.outputs(&out), vec![]);
+    });
+}
+
+#[test]
+fn test_scheduled_action_tags() {
+    let mut harness = TestHarness::<DelayedDoubler>::new(());
+    let inp = harness.input(|r| &mut r.__inp);
+
+    harness.run(|sim| {
+        sim.feed(&inp, tag!(T0), 1);
+        sim.feed(&inp, tag!(T0 + 4 ms), 2);
+
+        assert_eq!(sim.step(), Some(tag!(T0)));
+        assert!(sim.with_reactor(|r| r.started));
+        assert_eq!(sim.scheduled_tags(|r| &r.__act), vec![tag!(T0 + 10 ms)]);
+
+        assert_eq!(sim.step(), Some(tag!(T0 + 4 ms)));
+        assert_eq!(sim.scheduled_tags(|r| &r.__act), vec![tag!(T0 + 10 ms), tag!(T0 + 14 ms)]);
+        assert_eq!(sim.next_tag(), Some(tag!(T0 + 10 ms)));
+    });
+}
+
+#[test]
+fn test_shutdown_when_test_returns() {
+    let mut harness = TestHarness::<DelayedDoubler>::new(());
+    let out = harness.output(|r| &mut r.__out);
+
+    let stopped = harness.run(|sim| {
+        sim.advance_to(tag!(T0 + 1 sec));
+        assert_eq!(sim.current_tag(), Some(tag!(T0)));
+        sim.shutdown();
+        assert!(sim.is_shut_down());
+        assert_eq!(sim.current_tag(), Some(tag!(T0, 1)));
+        assert_eq!(sim.outputs(&out), vec![]);
+        sim.with_reactor(|r| r.stopped)
+    });
+    assert!(stopped);
+}
+
+#[test]
+fn test_options_timeout_shuts_down() {
+    let options = SchedulerOptions {
+        timeout: Some(Duration::from_millis(10)),
+        ..Default::default()
+    };
+    let mut harness = TestHarness::<DelayedDoubler>::with_options((), options);
+    let inp = harness.input(|r| &mut r.__inp);
+    let out = harness.output(|r| &mut r.__out);
+
+    harness.run(|sim| {
+        sim.feed(&inp, tag!(T0 + 5 ms), 1);
+
+        assert_eq!(sim.advance_to(tag!(T0 + 5 ms)), Some(tag!(T0 + 5 ms)));
+        assert_eq!(sim.step(), Some(tag!(T0 + 10 ms)));
+        assert!(sim.is_shut_down());
+        assert_eq!(sim.outputs(&out), vec![]);
+        assert!(sim.with_reactor(|r| r.stopped));
+    });
+}
+
+#[test]
+#[should_panic]
+fn test_feed_in_the_past() {
+    let mut harness = TestHarness::<DelayedDoubler>::new(());
+    let inp = harness.input(|r| &mut r.__inp);
+
+    harness.run(|sim| {
+        sim.feed(&inp, tag!(T0 + 5 ms), 1);
+        sim.advance_to(tag!(T0 + 5 ms));
+        sim.feed(&inp, tag!(T0 + 2 ms), 1);
+    });
+}
+
+/// A reactor whose action triggers no reaction, it's only
+/// read as a source.
+/// ```lf
+/// reactor Sampler {
+///     input inp: u32;
+///     logical action sample(10 ms): u32;
+///
+///     reaction(inp) -> sample {= ctx.schedule_with_v(sample, ctx.get(inp), Asap) =}
+///     reaction(inp) sample {= self.seen.push(ctx.get(sample)) =}
+/// }
+/// ```
+struct Sampler {
+    __id: ReactorId,
+    seen: Vec<Option<u32>>,
+    /// The tags at which the reactor was cleaned up.
+    cleanups: Vec<EventTag>,
+    __inp: Port<u32>,
+    __sample: LogicalAction<u32>,
+}
+
+impl ReactorInitializer for Sampler {
+    type Wrapped = Sampler;
+    type Params = ();
+    const MAX_REACTION_ID: LocalReactionId = LocalReactionId::new(2);
+
+    fn assemble(_: Self::Params, __ctx: AssemblyCtx<Self>) -> AssemblyResult<FinishedReactor<Self>> {
+        __ctx.assemble(|__ctx| {
+            __ctx.assemble_self(
+                |cc, id| {
+                    Ok(Sampler {
+                        __id: id,
+                        seen: Vec::new(),
+                        cleanups: Vec::new(),
+                        __inp: cc.new_port("inp", PortKind::Input),
+                        __sample: cc.new_logical_action("sample", Some(Duration::from_millis(10))),
+                    })
+                },
+                2,
+                [None, None],
+                |__assembler, __self, [react_0, react_1]| {
+                    __assembler.declare_triggers(__self.__inp.get_id(), react_0)?;
+                    __assembler.effects_logical_action(react_0, &__self.__sample)?;
+                    __assembler.declare_triggers(__self.__inp.get_id(), react_1)?;
+                    __assembler.declare_uses(react_1, __self.__sample.get_id())?;
+                    Ok(())
+                },
+            )
+        })
+    }
+}
+
+impl ReactorBehavior for Sampler {
+    fn id(&self) -> ReactorId {
+        self.__id
+    }
+
+    fn react(&mut self, ctx: &mut ReactionCtx, rid: LocalReactionId) {
+        match rid.raw() {
+            0 => ctx.schedule_with_v(&mut self.__sample, ctx.get(&self.__inp), Asap),
+            1 => self.seen.push(ctx.get(&self.__sample)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn cleanup_tag(&mut self, ctx: &CleanupCtx) {
+        self.cleanups.push(ctx.tag);
+        ctx.cleanup_port(&mut self.__inp);
+        ctx.cleanup_logical_action(&mut self.__sample);
+    }
+}
+
+#[test]
+fn test_only_touched_reactors_are_cleaned_up() {
+    let mut harness = TestHarness::<Sampler>::new(());
+    let inp = harness.input(|r| &mut r.__inp);
+
+    harness.run(|sim| {
+        sim.feed(&inp, tag!(T0 + 1 ms), 1);
+        sim.feed(&inp, tag!(T0 + 11 ms), 2);
+        sim.advance_to(tag!(T0 + 11 ms));
+        assert_eq!(sim.with_reactor(|r| r.seen.clone()), vec![None, Some(1)]);
+        assert_eq!(sim.scheduled_tags(|r| &r.__sample), vec![tag!(T0 + 21 ms)]);
+
+        // the action is present, but no reaction executes
+        assert_eq!(sim.step(), Some(tag!(T0 + 21 ms)));
+        assert_eq!(sim.scheduled_tags(|r| &r.__sample), vec![]);
+
+        // the reactor is idle at startup and shutdown
+        sim.shutdown();
+        assert_eq!(
+            sim.with_reactor(|r| r.cleanups.clone()),
+            vec![tag!(T0 + 1 ms), tag!(T0 + 11 ms), tag!(T0 + 21 ms)]
+        );
+    });
+}
@@ -24,7 +24,7 @@
 
 use std::borrow::Cow;
 
-use crate::assembly::{PortKind, TriggerId};
+use crate::assembly::{AssemblyResult, PortKind, TriggerId, TriggerLike};
 use crate::*;
 
 struct TestAssembler {
@@ -50,6 +50,13 @@ impl TestAssembler {
         Port::new(id, PortKind::Input)
     }
 
+    pub fn new_multiport<T: Sync>(&mut self, name: &'static str, len: usize) -> Multiport<T> {
+        let id = self.cur_id.get_and_incr().unwrap();
+        self.debug.record_trigger(id, Cow::Borrowed(name));
+        let channels = (0..len).map(|_| self.new_port(name)).collect();
+        Multiport::new(channels, id)
+    }
+
     fn ready(mut self) -> TestFixture {
         self.debug
             .set_id_range(self.reactor_id, TriggerId::FIRST_REGULAR..self.cur_id);
@@ -253,3 +260,127 @@ fn repeated_binding_panics() -> TestResult {
 
     test.ok()
 }
+
+#[test]
+fn multiport_iterates_only_present_channels() -> TestResult {
+    let mut test = TestAssembler::default();
+    let mut multiport = test.new_multiport::<i32>("m", 4);
+    let test = test.ready();
+
+    assert_eq!(multiport.enumerate_set().count(), 0);
+
+    test.set(&mut multiport[2], 2)?;
+    test.set(&mut multiport[0], 0)?;
+    test.set(&mut multiport[2], 3)?;
+    assert_eq!(multiport.enumerate_values().collect::<Vec<_>>(), vec![(0, 0), (2, 3)]);
+
+    multiport[0].set_impl(None);
+    assert_eq!(multiport.iterate_values().collect::<Vec<_>>(), vec![3]);
+
+    multiport.clear_present();
+    assert_eq!(multiport.iterate_set().count(), 0);
+    assert_eq!(multiport[2].get(), None);
+
+    test.ok()
+}
+
+#[test]
+fn multiport_lists_channels_set_again_once() -> TestResult {
+    let mut test = TestAssembler::default();
+    let mut multiport = test.new_multiport::<i32>("m", 5);
+    let test = test.ready();
+
+    // set out of order, and set again after being unset
+    test.set(&mut multiport[3], 3)?;
+    test.set(&mut multiport[1], 1)?;
+    multiport[1].set_impl(None);
+    test.set(&mut multiport[1], 10)?;
+    assert_eq!(multiport.enumerate_values().collect::<Vec<_>>(), vec![(1, 10), (3, 3)]);
+
+    // unset after the channels were sorted
+    multiport[3].set_impl(None);
+    test.set(&mut multiport[0], 0)?;
+    assert_eq!(multiport.enumerate_values().collect::<Vec<_>>(), vec![(0, 0), (1, 10)]);
+
+    multiport.clear_present();
+    assert_eq!(multiport.iterate_set().count(), 0);
+    test.set(&mut multiport[3], 30)?;
+    assert_eq!(multiport.enumerate_values().collect::<Vec<_>>(), vec![(3, 30)]);
+
+    test.ok()
+}
+
+#[test]
+fn multiport_tracks_presence_of_bound_channels() -> TestResult {
+    let mut test = TestAssembler::default();
+    let mut up0 = test.new_port("up0");
+    let mut up1 = test.new_port("up1");
+    let mut mid = test.new_port("mid");
+    let mut multiport = test.new_multiport::<i32>("m", 3);
+    let test = test.ready();
+
+    // up0 -> m[0]
+    // up1 -> mid -> m[2], bound in reverse topological order
+    test.bind(&mut up0, &mut multiport[0])?;
+    test.bind(&mut mid, &mut multiport[2])?;
+    test.bind(&mut up1, &mut mid)?;
+
+    test.set(&mut up1, 5)?;
+    assert_eq!(multiport.enumerate_values().collect::<Vec<_>>(), vec![(2, 5)]);
+    test.set(&mut up0, 4)?;
+    assert_eq!(multiport.enumerate_values().collect::<Vec<_>>(), vec![(0, 4), (2, 5)]);
+
+    multiport.clear_present();
+    up0.clear_value();
+    up1.clear_value();
+    assert_eq!(multiport.enumerate_set().count(), 0);
+    assert_eq!(multiport[0].get(), None);
+
+    test.ok()
+}
+
+struct BankItem {
+    out: Multiport<i32>,
+}
+
+fn interleave(bank: &mut Vec<BankItem>) -> AssemblyResult<Vec<TriggerId>> {
+    let ports = crate::unsafe_iter_bank!(bank # interleaved(out));
+    Ok(ports.map(|p| p.get_id()).collect())
+}
+
+#[test]
+fn interleaving_a_bank_alternates_between_multiports() -> TestResult {
+    let mut test = TestAssembler::default();
+    let a = test.new_multiport::<i32>("a", 2);
+    let b = test.new_multiport::<i32>("b", 2);
+    let test = test.ready();
+    let expected = vec![a[0].get_id(), b[0].get_id(), a[1].get_id(), b[1].get_id()];
+
+    let mut bank = vec![BankItem { out: a }, BankItem { out: b }];
+    assert_eq!(expected, interleave(&mut bank).map_err(|e| e.lift(&test.debug))?);
+    assert_eq!(
+        Vec::<TriggerId>::new(),
+        interleave(&mut vec![]).map_err(|e| e.lift(&test.debug))?
+    );
+
+    test.ok()
+}
+
+#[test]
+fn interleaving_multiports_of_different_widths_is_an_error() -> TestResult {
+    let mut test = TestAssembler::default();
+    let a = test.new_multiport::<i32>("a", 2);
+    let b = test.new_multiport::<i32>("b", 3);
+    let test = test.ready();
+
+    let mut bank = vec![BankItem { out: a }, BankItem { out: b }];
+    match interleave(&mut bank) {
+        Ok(_) => panic!("expected an error"),
+        Err(e) => assert_eq!(
+            e.lift(&test.debug),
+            "Cannot interleave multiports /a (width 2) and /b (width 3) of the same bank, their widths differ"
+        ),
+    }
+
+    test.ok()
+}
@@ -24,9 +24,37 @@
 
 //! Test utilities.
 
+use std::sync::{Arc, Mutex};
+
+use crate::builder::{ProgramBuilder, ReactorHandle};
 use crate::*;
 
+/// Values recorded by the reactions of a test program.
+pub type Log<T> = Arc<Mutex<Vec<T>>>;
+
 /// Set a port to a value
 pub fn set_port<T: Sync>(port: &mut Port<T>, v: T) {
     port.set_impl(Some(v))
 }
+
+/// Default options, with the given timeout.
+pub fn options_with_timeout(timeout: Duration) -> SchedulerOptions {
+    SchedulerOptions { timeout: Some(timeout), ..Default::default() }
+}
+
+/// Builds a program whose main reactor holds a [Log], runs it
+/// with the given options, and returns what was logged. The
+/// log is also passed to `build`, to share it with other reactors.
+pub fn run_logged<T: Send + 'static>(
+    options: SchedulerOptions,
+    build: impl FnOnce(&mut ProgramBuilder, ReactorHandle<Log<T>>, &Log<T>),
+) -> Vec<T> {
+    let log: Log<T> = Default::default();
+    let mut builder = ProgramBuilder::new();
+    let main = builder.add_main(log.clone());
+    build(&mut builder, main, &log);
+    builder.run(options);
+
+    let mut log = log.lock().unwrap();
+    std::mem::take(&mut *log)
+}
@@ -22,8 +22,10 @@
  * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
  */
 
+use std::convert::TryFrom;
 use std::fmt::{Debug, Display, Formatter};
-use std::ops::Add;
+use std::ops::{Add, Neg, Sub};
+use std::time::Duration;
 
 /// Private concrete type of a microstep.
 pub(crate) type MS = u32;
@@ -34,9 +36,18 @@ pub struct MicroStep(MS);
 
 impl MicroStep {
     pub const ZERO: MicroStep = MicroStep(0);
+    /// The largest microstep, which is that of [EventTag::FOREVER](crate::EventTag::FOREVER).
+    pub const MAX: MicroStep = MicroStep(MS::MAX);
+
     pub fn new(u: MS) -> Self {
         Self(u)
     }
+
+    /// Returns the next microstep, or None if this is [Self::MAX].
+    #[inline]
+    pub fn checked_next(self) -> Option<Self> {
+        self.0.checked_add(1).map(Self)
+    }
 }
 
 impl Display for MicroStep {
@@ -47,8 +58,231 @@ impl Display for MicroStep {
 
 impl Add<MS> for MicroStep {
     type Output = Self;
+
+    /// # Panics
+    ///
+    /// If the microstep overflows.
     #[inline]
     fn add(self, rhs: MS) -> Self::Output {
-        Self(self.0 + rhs)
+        match self.0.checked_add(rhs) {
+            Some(u) => Self(u),
+            None => panic!("Microstep overflow: {} + {}", self, rhs),
+        }
+    }
+}
+
+/// A signed time interval in nanoseconds, like the `interval_t`
+/// of the C target of LF. The offset of an [EventTag](crate::EventTag)
+/// from the start of the program is an interval, which is
+/// negative for times before the start.
+///
+/// The sentinels [Self::NEVER] and [Self::FOREVER] are before,
+/// resp. after, any other interval. Arithmetic saturates to them,
+/// and they absorb finite intervals: for instance `FOREVER - 1 sec`
+/// is still `FOREVER`. As in LF, `NEVER + FOREVER` is `NEVER`.
+///
+/// ```
+/// # use reactor_rt::{delay, Interval};
+/// let past = Interval::ZERO - delay!(2 ms).into();
+/// assert_eq!(past, Interval::from_nanos(-2_000_000));
+/// assert_eq!(past.to_duration(), None);
+/// assert_eq!(-past + delay!(1 ms).into(), Interval::from_nanos(3_000_000));
+/// assert_eq!(Interval::FOREVER - past, Interval::FOREVER);
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Interval(i64);
+
+impl Interval {
+    pub const ZERO: Interval = Interval(0);
+    /// An interval before any other.
+    pub const NEVER: Interval = Interval(i64::MIN);
+    /// An interval after any other.
+    pub const FOREVER: Interval = Interval(i64::MAX);
+
+    /// Create an interval from a number of nanoseconds.
+    /// The extreme values are the sentinels [Self::NEVER]
+    /// and [Self::FOREVER].
+    #[inline]
+    pub const fn from_nanos(nanos: i64) -> Self {
+        Self(nanos)
+    }
+
+    /// Returns the number of nanoseconds of this interval.
+    #[inline]
+    pub const fn as_nanos(self) -> i64 {
+        self.0
+    }
+
+    /// Create an interval from a duration. Durations that are
+    /// too large to be represented saturate to [Self::FOREVER].
+    #[inline]
+    pub fn from_duration(duration: Duration) -> Self {
+        i64::try_from(duration.as_nanos()).map_or(Self::FOREVER, Self)
+    }
+
+    /// Returns this interval as a duration, or None if it is
+    /// negative, or one of the sentinels.
+    #[inline]
+    pub fn to_duration(self) -> Option<Duration> {
+        if self.is_finite() && self.0 >= 0 {
+            Some(Duration::from_nanos(self.0 as u64))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn is_never(self) -> bool {
+        self == Self::NEVER
+    }
+
+    #[inline]
+    pub fn is_forever(self) -> bool {
+        self == Self::FOREVER
+    }
+
+    /// Returns whether this interval is neither [Self::NEVER]
+    /// nor [Self::FOREVER].
+    #[inline]
+    pub fn is_finite(self) -> bool {
+        !self.is_never() && !self.is_forever()
+    }
+}
+
+impl From<Duration> for Interval {
+    #[inline]
+    fn from(duration: Duration) -> Self {
+        Self::from_duration(duration)
+    }
+}
+
+impl Add for Interval {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        if self.is_never() || rhs.is_never() {
+            Self::NEVER
+        } else if self.is_forever() || rhs.is_forever() {
+            Self::FOREVER
+        } else {
+            match self.0.checked_add(rhs.0) {
+                Some(nanos) => Self(nanos),
+                None if rhs.0 > 0 => Self::FOREVER,
+                None => Self::NEVER,
+            }
+        }
+    }
+}
+
+impl Neg for Interval {
+    type Output = Self;
+
+    /// Returns the opposite interval. The opposite
+    /// of [Self::NEVER] is [Self::FOREVER], and vice versa.
+    fn neg(self) -> Self::Output {
+        match self {
+            Self::NEVER => Self::FOREVER,
+            Self::FOREVER => Self::NEVER,
+            Self(nanos) => Self(-nanos),
+        }
+    }
+}
+
+impl Sub for Interval {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self + -rhs
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::NEVER => write!(f, "NEVER"),
+            Self::FOREVER => write!(f, "FOREVER"),
+            Self(nanos) => write!(f, "{} ns", nanos),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime};
+
+    use crate::{delay, tag, EventTag, Interval, MicroStep};
+
+    const fn ms(millis: i64) -> Interval {
+        Interval::from_nanos(millis * 1_000_000)
+    }
+
+    #[test]
+    fn test_interval_arithmetic() {
+        assert_eq!(ms(5) - ms(8), ms(-3));
+        assert_eq!(-ms(3), ms(-3));
+        assert_eq!(ms(-3).to_duration(), None);
+        assert_eq!(ms(3).to_duration(), Some(delay!(3 ms)));
+        assert_eq!(Interval::from(Duration::MAX), Interval::FOREVER);
+
+        // sentinels absorb finite intervals
+        assert_eq!(Interval::FOREVER - ms(1), Interval::FOREVER);
+        assert_eq!(Interval::NEVER + ms(1), Interval::NEVER);
+        assert_eq!(Interval::NEVER + Interval::FOREVER, Interval::NEVER);
+        assert_eq!(ms(1) - Interval::FOREVER, Interval::NEVER);
+        assert_eq!(-Interval::NEVER, Interval::FOREVER);
+
+        // overflow saturates
+        assert_eq!(Interval::from_nanos(i64::MAX - 1) + ms(1), Interval::FOREVER);
+        assert_eq!(Interval::from_nanos(i64::MIN + 1) - ms(1), Interval::NEVER);
+    }
+
+    #[test]
+    fn test_tag_order_and_sentinels() {
+        assert!(tag!(NEVER) < tag!(T0 - 5 ms));
+        assert!(tag!(T0 - 5 ms) < tag!(T0));
+        assert!(tag!(T0 + 5 ms, 3) < tag!(FOREVER));
+        assert_eq!(tag!(T0 - 5 ms).offset_from_t0, ms(-5));
+        assert_eq!(tag!(T0 - 5 ms).to_string(), "(T0 - 5000000 ns = 5 ms, 0)");
+        assert_eq!(tag!(FOREVER).to_string(), "(FOREVER, 4294967295)");
+
+        assert_eq!(tag!(T0 - 5 ms).successor(delay!(7 ms)), tag!(T0 + 2 ms));
+        assert_eq!(tag!(T0 + 5 ms).successor(Duration::MAX), tag!(FOREVER));
+        assert_eq!(tag!(FOREVER).successor(delay!(1 ms)), tag!(FOREVER));
+        assert_eq!(tag!(NEVER).successor(delay!(1 ms)), tag!(NEVER));
+    }
+
+    #[test]
+    fn test_microstep_overflow() {
+        let last = EventTag { offset_from_t0: ms(1), microstep: MicroStep::MAX };
+        let err = last.checked_next_microstep().unwrap_err();
+        assert_eq!(err.tag, last);
+        assert_eq!(tag!(T0, 1).checked_next_microstep(), Ok(tag!(T0, 2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Microstep overflow")]
+    fn test_microstep_overflow_panics_in_successor() {
+        let last = EventTag { offset_from_t0: ms(1), microstep: MicroStep::MAX };
+        last.successor(Duration::ZERO);
+    }
+
+    #[test]
+    fn test_tags_before_start() {
+        let t0_utc = SystemTime::UNIX_EPOCH + delay!(1000 sec);
+        let before = t0_utc - delay!(5 ms);
+        assert_eq!(EventTag::from_utc_time(t0_utc, before), Some(tag!(T0 - 5 ms)));
+        assert_eq!(tag!(T0 - 5 ms).to_utc_time(t0_utc), before);
+        assert_eq!(tag!(FOREVER).checked_logical_time(std::time::Instant::now()), None);
+    }
+
+    #[test]
+    fn test_duration_since_start_saturates() {
+        assert_eq!(tag!(T0 + 5 ms).duration_since_start(), delay!(5 ms));
+        assert_eq!(tag!(T0 + 5 ms).checked_duration_since_start(), Some(delay!(5 ms)));
+        assert_eq!(tag!(T0 - 5 ms).duration_since_start(), Duration::ZERO);
+        assert_eq!(tag!(T0 - 5 ms).checked_duration_since_start(), None);
+        assert_eq!(tag!(NEVER).duration_since_start(), Duration::ZERO);
+        assert_eq!(tag!(FOREVER).duration_since_start(), Duration::MAX);
+        assert_eq!(tag!(FOREVER).checked_duration_since_start(), None);
     }
 }
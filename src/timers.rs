@@ -23,6 +23,8 @@
  */
 use std::time::Instant;
 
+use atomic_refcell::AtomicRefCell;
+
 use super::*;
 use crate::assembly::{TriggerId, TriggerLike};
 
@@ -30,7 +32,8 @@ use crate::assembly::{TriggerId, TriggerLike};
 /// itself periodically.
 ///
 /// For periodic timers, a reaction is synthesized which reschedules
-/// the timer.
+/// the timer. Reactions may also stop and restart the timer, or change
+/// its period, see [ReactionCtx::stop_timer].
 pub struct Timer {
     id: TriggerId,
 
@@ -40,21 +43,75 @@ pub struct Timer {
 
     /// Period between events emitted by this timer. A period
     /// of zero means that the timer will trigger exactly once
-    /// after the specified offset.
+    /// after the specified offset. This is the period the timer
+    /// was declared with, see [Self::current_period].
+    pub period: Duration,
+
+    /// State that reactions may change at runtime.
+    state: AtomicRefCell<TimerState>,
+}
+
+/// The runtime state of a [Timer].
+pub(crate) struct TimerState {
+    /// Period used when the timer is rescheduled.
     pub period: Duration,
+    /// Tag of the next firing of the timer, [EventTag::NEVER]
+    /// if the timer is stopped.
+    pub next: EventTag,
+    /// Tag of the latest firing, [EventTag::NEVER] if none. The
+    /// timer is still present at that tag after it has been
+    /// rescheduled.
+    pub latest: EventTag,
+    /// Whether a reaction already stopped or restarted the
+    /// timer, in which case it is not bootstrapped at startup.
+    pub controlled: bool,
+}
+
+impl TimerState {
+    /// Remember that the timer is present at the given tag,
+    /// before its next firing is changed.
+    pub(crate) fn settle(&mut self, now: EventTag) {
+        if self.next == now {
+            self.latest = self.next;
+        }
+    }
 }
 
 impl Timer {
     pub(crate) fn new(id: TriggerId, offset: Duration, period: Duration) -> Self {
-        Self { offset, period, id }
+        let state = TimerState {
+            period,
+            next: EventTag::NEVER,
+            latest: EventTag::NEVER,
+            controlled: false,
+        };
+        Self {
+            offset,
+            period,
+            id,
+            state: AtomicRefCell::new(state),
+        }
     }
 
-    /// Whether the timer should repeat itself. A period of zero
-    /// means that the timer will trigger exactly once after the
-    /// specified offset.
+    /// The period the timer currently uses to reschedule itself.
+    /// This differs from [Self::period] if a reaction changed it,
+    /// see [ReactionCtx::set_timer_period].
+    #[inline]
+    pub fn current_period(&self) -> Duration {
+        self.state.borrow().period
+    }
+
+    /// Whether the timer currently repeats itself, see
+    /// [Self::current_period]. A period of zero means that the
+    /// timer will trigger exactly once more.
     #[inline]
     pub fn is_periodic(&self) -> bool {
-        !self.period.is_zero()
+        !self.current_period().is_zero()
+    }
+
+    #[inline]
+    pub(crate) fn state(&self) -> atomic_refcell::AtomicRefMut<'_, TimerState> {
+        self.state.borrow_mut()
     }
 }
 
@@ -66,14 +123,8 @@ impl TriggerLike for Timer {
 
 impl ReactionTrigger<()> for Timer {
     fn is_present(&self, now: &EventTag, _start: &Instant) -> bool {
-        let elapsed = now.duration_since_start();
-        if elapsed == self.offset {
-            true
-        } else if elapsed < self.offset || !self.is_periodic() {
-            false
-        } else {
-            ((elapsed - self.offset).as_nanos() % self.period.as_nanos()) == 0
-        }
+        let state = self.state.borrow();
+        state.next == *now || state.latest == *now
     }
 
     #[inline]
@@ -94,3 +145,110 @@ impl ReactionTrigger<()> for Timer {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::builder::*;
+    use crate::prelude::*;
+    use crate::test::testutil::*;
+
+    fn millis(ms: &[u64]) -> Vec<Duration> {
+        ms.iter().map(|ms| Duration::from_millis(*ms)).collect()
+    }
+
+    /// Logs the elapsed time whenever `t` fires.
+    fn log_timer(builder: &mut ProgramBuilder, main: ReactorHandle<Log<Duration>>, t: TimerHandle) {
+        builder
+            .add_reaction(main, move |ctx, log, c| {
+                assert!(ctx.is_present(c.timer(t)));
+                log.lock().unwrap().push(ctx.get_elapsed_logical_time())
+            })
+            .triggered_by(t);
+    }
+
+    #[test]
+    fn test_timer_fires_periodically() {
+        let log = run_logged(options_with_timeout(Duration::from_millis(45)), |builder, main, _| {
+            let t = builder.new_timer(main, "t", Duration::ZERO, Duration::from_millis(10));
+            log_timer(builder, main, t);
+        });
+
+        assert_eq!(log, millis(&[0, 10, 20, 30, 40]));
+    }
+
+    #[test]
+    fn test_timer_can_be_controlled_at_runtime() {
+        let log = run_logged(options_with_timeout(Duration::from_millis(50)), |builder, main, _| {
+            let t = builder.new_timer(main, "t", Duration::ZERO, Duration::from_millis(10));
+            let restart = builder.new_logical_action::<(), _>(main, "restart", None);
+            builder
+                .add_reaction(main, move |ctx, _, c| {
+                    ctx.schedule(c.logical_action_mut(restart), after!(40 ms))
+                })
+                .on_startup()
+                .effects(restart);
+            builder
+                .add_reaction(main, move |ctx, log, c| {
+                    let timer = c.timer(t);
+                    assert!(ctx.is_present(timer));
+                    let elapsed = ctx.get_elapsed_logical_time();
+                    log.lock().unwrap().push(elapsed);
+                    if elapsed == Duration::from_millis(20) {
+                        ctx.set_timer_period(timer, Duration::from_millis(5));
+                    } else if elapsed == Duration::from_millis(30) {
+                        ctx.stop_timer(timer);
+                    }
+                })
+                .triggered_by(t);
+            builder
+                .add_reaction(main, move |ctx, _, c| ctx.restart_timer(c.timer(t), Duration::from_millis(2)))
+                .triggered_by(restart);
+        });
+
+        assert_eq!(log, millis(&[0, 10, 20, 25, 30, 42, 47]));
+    }
+
+    #[test]
+    fn test_timer_period_changed_by_a_later_reaction_is_used_next() {
+        let log = run_logged(options_with_timeout(Duration::from_millis(20)), |builder, main, _| {
+            let t = builder.new_timer(main, "t", Duration::ZERO, Duration::from_millis(10));
+            log_timer(builder, main, t);
+            builder
+                .add_reaction(main, move |ctx, _, c| {
+                    if ctx.get_elapsed_logical_time() == Duration::from_millis(10) {
+                        ctx.set_timer_period(c.timer(t), Duration::from_millis(5));
+                    }
+                })
+                .triggered_by(t);
+        });
+
+        assert_eq!(log, millis(&[0, 10, 15, 20]));
+    }
+
+    #[test]
+    fn test_stopped_and_restarted_timers_do_not_fire_at_their_former_tags() {
+        let log = run_logged(options_with_timeout(Duration::from_millis(60)), |builder, main, _| {
+            let t = builder.new_timer(main, "t", Duration::from_millis(10), Duration::from_millis(10));
+            let control = builder.new_logical_action::<Option<u64>, _>(main, "control", None);
+            builder
+                .add_reaction(main, move |ctx, _, c| {
+                    let control = c.logical_action_mut(control);
+                    // stop, then restart twice, the second time before the timer fires
+                    for (ms, restart) in [(15, None), (25, Some(7)), (35, Some(4))] {
+                        ctx.schedule_with_v(control, Some(restart), after!(ms ms));
+                    }
+                })
+                .on_startup()
+                .effects(control);
+            builder
+                .add_reaction(main, move |ctx, _, c| match ctx.get(c.logical_action(control)).flatten() {
+                    Some(ms) => ctx.restart_timer(c.timer(t), Duration::from_millis(ms)),
+                    None => ctx.stop_timer(c.timer(t)),
+                })
+                .triggered_by(control);
+            log_timer(builder, main, t);
+        });
+
+        assert_eq!(log, millis(&[10, 32, 39, 49, 59]));
+    }
+}
@@ -0,0 +1,198 @@
+/*
+ * Copyright (c) 2021, TU Dresden.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice,
+ *    this list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL
+ * THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+ * SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+ * INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+ * STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF
+ * THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// A reference-counted value, that can be passed through
+/// ports and actions without being copied. This is meant
+/// for large values, like buffers, for which cloning at
+/// every hop would be expensive.
+///
+/// Cloning a token only clones the reference. All reactions
+/// that receive a token share read access to the same value.
+/// Mutable access is available only while the token is
+/// uniquely held ([Self::get_mut]), or else by copying the
+/// value on write ([Self::make_mut]). This matches the semantics
+/// of mutable inputs in LF.
+///
+/// ### Examples
+///
+/// ```no_run
+/// # use reactor_rt::{ReactionCtx, Port, Token};
+/// # let ctx: &mut ReactionCtx = panic!();
+/// # let input: &Port<Token<Vec<u8>>> = panic!();
+/// # let output: &mut Port<Token<Vec<u8>>> = panic!();
+/// if let Some(mut frame) = ctx.get_token(input) {
+///     // the port still holds a reference, so this copies the vec
+///     frame.make_mut()[0] = 0;
+///     ctx.set(output, frame);
+/// }
+/// ```
+pub struct Token<T>(Arc<T>);
+
+impl<T> Token<T> {
+    /// Wraps the given value into a new token.
+    pub fn new(value: T) -> Self {
+        Self(Arc::new(value))
+    }
+
+    /// Returns a mutable reference to the value, if this
+    /// is the only reference to it. Returns None otherwise.
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        Arc::get_mut(&mut self.0)
+    }
+
+    /// Returns whether this is the only reference to the value.
+    /// In that case, [Self::get_mut] and [Self::make_mut] do not
+    /// need to copy.
+    #[inline]
+    pub fn is_unique(&self) -> bool {
+        Arc::strong_count(&self.0) == 1
+    }
+
+    /// Returns the value, if this is the only reference to
+    /// it. Otherwise, the token is returned unchanged.
+    pub fn try_unwrap(self) -> Result<T, Self> {
+        Arc::try_unwrap(self.0).map_err(Self)
+    }
+
+    /// Returns whether both tokens refer to the same value.
+    #[inline]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Arc::ptr_eq(&this.0, &other.0)
+    }
+}
+
+impl<T: Clone> Token<T> {
+    /// Returns a mutable reference to the value. If the value
+    /// is shared with other tokens, it is first copied into
+    /// a new value, which this token refers to from then on.
+    /// The other tokens are not affected.
+    #[inline]
+    pub fn make_mut(&mut self) -> &mut T {
+        Arc::make_mut(&mut self.0)
+    }
+
+    /// Returns the value, copying it only if it is shared
+    /// with other tokens.
+    pub fn into_inner(self) -> T {
+        Arc::try_unwrap(self.0).unwrap_or_else(|shared| T::clone(&shared))
+    }
+}
+
+impl<T> Clone for Token<T> {
+    /// Clones the reference, not the value.
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T> Deref for Token<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> From<T> for Token<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Debug> Debug for Token<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Token({:?})", self.0)
+    }
+}
+
+impl<T: PartialEq> PartialEq for Token<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Token::ptr_eq(self, other) || *self.0 == *other.0
+    }
+}
+
+impl<T: Eq> Eq for Token<T> {}
+
+#[cfg(test)]
+mod test {
+    use crate::assembly::PortKind;
+    use crate::prelude::*;
+    use crate::test::testutil::*;
+    use crate::SchedulerOptions;
+
+    #[test]
+    fn test_tokens_are_not_copied() {
+        // addresses of the buffer when it's sent and received
+        let log: Vec<usize> = run_logged(SchedulerOptions::default(), |builder, main, _| {
+            let relay = builder.add_child(main, "relay", ());
+            let inp = builder.new_port::<Token<Vec<u8>>, _>(relay, "in", PortKind::Input);
+            let out = builder.new_port::<Token<Vec<u8>>, _>(relay, "out", PortKind::Output);
+            let act = builder.new_logical_action::<Token<Vec<u8>>, _>(relay, "act", Some(Duration::from_millis(1)));
+
+            builder
+                .add_reaction(main, move |ctx, log, c| {
+                    let frame = Token::new(vec![0u8; 1 << 20]);
+                    log.lock().unwrap().push(frame.as_ptr() as usize);
+                    ctx.set(c.port_mut(inp), frame)
+                })
+                .on_startup()
+                .effects(inp);
+            builder
+                .add_reaction(relay, move |ctx, _, c| {
+                    let frame = ctx.get_token(c.port(inp));
+                    ctx.schedule_with_v(c.logical_action_mut(act), frame, Asap)
+                })
+                .triggered_by(inp)
+                .effects(act);
+            builder
+                .add_reaction(relay, move |ctx, _, c| {
+                    let frame = ctx.get_token(c.logical_action(act)).unwrap();
+                    ctx.set(c.port_mut(out), frame)
+                })
+                .triggered_by(act)
+                .effects(out);
+            builder
+                .add_reaction(main, move |ctx, log, c| {
+                    let mut frame = ctx.get_token(c.port(out)).unwrap();
+                    log.lock().unwrap().push(frame.as_ptr() as usize);
+                    // the port still refers to the value, so writing copies it
+                    assert!(!frame.is_unique());
+                    assert!(frame.get_mut().is_none());
+                    frame.make_mut()[0] = 1;
+                    assert!(frame.is_unique());
+                    log.lock().unwrap().push(frame.as_ptr() as usize);
+                })
+                .triggered_by(out);
+        });
+
+        assert_eq!(log.len(), 3);
+        assert_eq!(log[0], log[1]);
+        assert_ne!(log[1], log[2]);
+    }
+}
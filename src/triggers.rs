@@ -35,7 +35,7 @@ use crate::EventTag;
 /// Common trait for actions, ports, and timer objects handed
 /// to reaction functions. This is meant to be used through the
 /// API of [ReactionCtx](crate::ReactionCtx) instead of directly.
-pub trait ReactionTrigger<T> {
+pub trait ReactionTrigger<T>: TriggerLike {
     /// Returns whether the trigger is present, given that
     /// the current logical time is the parameter.
     #[inline]
@@ -57,7 +57,7 @@ pub trait ReactionTrigger<T> {
 }
 
 #[cfg(not(feature = "no-unsafe"))]
-pub trait ReactionTriggerWithRefAccess<T> {
+pub trait ReactionTriggerWithRefAccess<T>: TriggerLike {
     /// Returns a reference to the value, if it is present. Whether a *value*
     /// is present is not in general the same thing as whether *this trigger*
     /// [Self::is_present]. See [crate::ReactionCtx::get_ref].
@@ -113,16 +113,6 @@ impl TriggerId {
         self.0.checked_add(1).map(TriggerId).ok_or(())
     }
 
-    /// Returns an iterator that iterates over the range `(self+1)..(self+1+len)`.
-    /// Returns `Err` on overflow.
-    pub(crate) fn iter_next_range(&self, len: usize) -> Result<impl Iterator<Item = Self>, ()> {
-        if let Some(upper) = self.0.checked_add(1 + (len as TriggerIdImpl)) {
-            Ok(((self.0 + 1)..upper).map(TriggerId))
-        } else {
-            Err(())
-        }
-    }
-
     pub(crate) fn next_range(&self, len: usize) -> Result<Range<Self>, ()> {
         if let Some(upper) = self.0.checked_add(1 + (len as TriggerIdImpl)) {
             Ok(Range { start: self.next()?, end: Self::new(upper) })
@@ -146,15 +146,29 @@ macro_rules! after {
 /// // with a microstep:
 /// tag!(T0, 1);
 /// tag!(T0 + 3 sec, 1);
+/// // before the start of the program:
+/// tag!(T0 - 5 ms);
+/// // the sentinel tags:
+/// tag!(NEVER);
+/// tag!(FOREVER);
 /// ```
 #[macro_export]
 macro_rules! tag {
     (T0)                          => {$crate::EventTag::ORIGIN};
+    (NEVER)                       => {$crate::EventTag::NEVER};
+    (FOREVER)                     => {$crate::EventTag::FOREVER};
     (T0, $microstep:expr)         => {tag!(T0 + 0 sec, $microstep)};
     (T0 + $amount:tt $unit:ident) => {tag!(T0 + $amount $unit, 0)};
+    (T0 - $amount:tt $unit:ident) => {tag!(T0 - $amount $unit, 0)};
     (T0 + $amount:tt $unit:ident, $microstep:expr) => {
         $crate::EventTag::offset($crate::delay!($amount $unit), $microstep)
     };
+    (T0 - $amount:tt $unit:ident, $microstep:expr) => {
+        $crate::EventTag {
+            offset_from_t0: -$crate::Interval::from_duration($crate::delay!($amount $unit)),
+            microstep: $crate::MicroStep::new($microstep),
+        }
+    };
 }
 
 /// Convenient macro to assert equality of the current tag.
@@ -46,7 +46,7 @@ where
 
     /// Find an entry with assumption that the key is random access.
     /// Logarithmic complexity.
-    pub fn entry(&mut self, key: K) -> Entry<K, V> {
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
         match self.find_k(&key) {
             Ok(index) => Entry::Occupied(OccupiedEntry { map: self, index, key }),
             Err(index) => Entry::Vacant(VacantEntry { map: self, index, key }),
@@ -62,7 +62,7 @@ where
     ///
     /// If either of these assumptions is violated, you might obtain an entry which allows
     /// destroying the well-kept order of the items.
-    pub fn entry_from_ref(&mut self, key_hint: KeyRef<K>, key: K) -> Entry<K, V> {
+    pub fn entry_from_ref(&mut self, key_hint: KeyRef<K>, key: K) -> Entry<'_, K, V> {
         debug_assert!(self.is_valid_keyref(&key_hint.as_ref()));
         let KeyRef { min_idx, .. } = key_hint;
 